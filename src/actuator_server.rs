@@ -1,26 +1,100 @@
-use crate::actuators::{create_driver_from_env, ActuatorDriver};
+use crate::actuator_status::{ActuatorStatus, DoorStatus, FeederStatus};
+use crate::actuators::create_driver_from_env;
+use crate::door::{DoorDirection, DoorState};
+use crate::feeder::{
+    plan_calibration_run, plan_portion, FeederCalibration, FeederCalibrations, FeederRation,
+    PortionError,
+};
+use crate::interlocks::{CommandError, Interlock, InterlockLimits, InterlockedDriver};
+use crate::levels::FeederLog;
 use axum::extract::State;
 use axum::http::header::{HeaderName, CONTENT_TYPE};
 use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
-use axum::routing::post;
+use axum::routing::{get, post};
 use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::net::TcpListener;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedMutexGuard};
 use tower_http::cors::{Any, CorsLayer};
 
 #[derive(Clone)]
 struct AppState {
     api_key: String,
-    driver: Arc<Mutex<Box<dyn ActuatorDriver>>>,
+    driver: Arc<Mutex<InterlockedDriver>>,
+    status: Arc<Mutex<ActuatorStatus>>,
+    status_path: Arc<str>,
+    calibrations: Arc<Mutex<FeederCalibrations>>,
+    calibration_path: Arc<str>,
+    ration: FeederRation,
+    feeder_log_path: Arc<str>,
+}
+
+impl AppState {
+    /// Applies `update` and writes the status file; a failed write is logged, not returned,
+    /// since the command itself already ran.
+    async fn record(&self, update: impl FnOnce(&mut ActuatorStatus)) {
+        let mut status = self.status.lock().await;
+        update(&mut status);
+        if let Err(err) = status.save(&self.status_path) {
+            eprintln!("{err}");
+        }
+    }
+
+    /// Records a command an interlock refused as failed with the interlock's reason, so the
+    /// state endpoints show it, and answers with the interlock.
+    async fn reject(
+        &self,
+        interlock: Interlock,
+        update: impl FnOnce(&mut ActuatorStatus, &Result<(), String>),
+    ) -> (StatusCode, Json<ApiResponse>) {
+        let result = Err(interlock.to_string());
+        self.record(|status| update(status, &result)).await;
+        interlock_rejected(interlock)
+    }
+
+    /// Adds a feeder run to `FEEDER_HISTORY_FILE`, where `status` checks it against the feed
+    /// level; failures are logged like those of [`AppState::record`].
+    fn log_feeder_run(&self, device_key: &str, at: SystemTime) {
+        let recorded = FeederLog::load(&self.feeder_log_path).and_then(|mut log| {
+            log.record_activation(device_key, at);
+            log.save(&self.feeder_log_path)
+        });
+        if let Err(err) = recorded {
+            eprintln!("{err}");
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct FeederRequest {
     device_key: String,
     duration_ms: Option<u64>,
+    grams: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct CalibrationRunRequest {
+    device_key: String,
+    duration_ms: u64,
+}
+
+#[derive(Deserialize)]
+struct CalibrationRequest {
+    device_key: String,
+    duration_ms: u64,
+    grams: f32,
+}
+
+#[derive(Serialize)]
+struct FeederView {
+    #[serde(flatten)]
+    status: FeederStatus,
+    daily_ration_grams: Option<f32>,
+    calibration: BTreeMap<String, FeederCalibration>,
 }
 
 #[derive(Deserialize)]
@@ -42,32 +116,102 @@ fn authorized(headers: &HeaderMap, expected_key: &str) -> bool {
         .unwrap_or(false)
 }
 
+fn error_response(code: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    (
+        code,
+        Json(ApiResponse {
+            status: "error",
+            message,
+        }),
+    )
+}
+
+fn unauthorized() -> (StatusCode, Json<ApiResponse>) {
+    error_response(StatusCode::UNAUTHORIZED, "unauthorized".to_string())
+}
+
+fn portion_rejected(err: PortionError) -> (StatusCode, Json<ApiResponse>) {
+    let code = match err {
+        PortionError::Invalid(_) => StatusCode::BAD_REQUEST,
+        PortionError::NotCalibrated(_) | PortionError::CalibrationPending(_) => {
+            StatusCode::CONFLICT
+        }
+        PortionError::RationReached { .. } => StatusCode::TOO_MANY_REQUESTS,
+    };
+    error_response(code, err.to_string())
+}
+
+/// Interlocks that clear with time answer 429; an oversized feeder pulse is refused with 409.
+fn interlock_rejected(interlock: Interlock) -> (StatusCode, Json<ApiResponse>) {
+    let code = if interlock.is_rate_limit() {
+        StatusCode::TOO_MANY_REQUESTS
+    } else {
+        StatusCode::CONFLICT
+    };
+    error_response(code, interlock.to_string())
+}
+
+/// Runs a driver command on the blocking pool, since motor moves sleep and poll for seconds.
+/// The guard comes back so a caller can keep the driver locked until the run is recorded.
+async fn run_driver<T: Send + 'static>(
+    mut driver: OwnedMutexGuard<InterlockedDriver>,
+    command: impl FnOnce(&mut InterlockedDriver) -> T + Send + 'static,
+) -> (OwnedMutexGuard<InterlockedDriver>, T) {
+    tokio::task::spawn_blocking(move || {
+        let output = command(&mut driver);
+        (driver, output)
+    })
+    .await
+    .expect("actuator driver panicked")
+}
+
 pub async fn run_actuator_server(
     bind_addr: &str,
     api_key: String,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let limits = InterlockLimits::from_env().map_err(std::io::Error::other)?;
     let driver = create_driver_from_env().map_err(std::io::Error::other)?;
+    let mut driver = InterlockedDriver::new(driver, limits);
+    let status_path = ActuatorStatus::path_from_env();
+    let mut status = ActuatorStatus::load(&status_path).map_err(std::io::Error::other)?;
+    status.door.state = status.resume_door_state();
+    driver.restore_door_state(status.door.state);
+    let calibration_path = FeederCalibrations::path_from_env();
+    let calibrations =
+        FeederCalibrations::load(&calibration_path).map_err(std::io::Error::other)?;
     let state = AppState {
         api_key,
         driver: Arc::new(Mutex::new(driver)),
+        status: Arc::new(Mutex::new(status)),
+        status_path: status_path.into(),
+        calibrations: Arc::new(Mutex::new(calibrations)),
+        calibration_path: calibration_path.into(),
+        ration: FeederRation::from_env().map_err(std::io::Error::other)?,
+        feeder_log_path: FeederLog::path_from_env().into(),
     };
 
-    let app = Router::new()
+    let listener = TcpListener::bind(bind_addr).await?;
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/actuators/feeder", get(feeder_status))
         .route("/actuators/feeder/activate", post(feeder_activate))
+        .route("/actuators/feeder/calibrate", post(feeder_calibration_run))
+        .route("/actuators/feeder/calibration", post(feeder_calibration))
+        .route("/actuators/door", get(door_status))
         .route("/actuators/door/open", post(door_open))
         .route("/actuators/door/close", post(door_close))
         .with_state(state)
-        .layer(cors_layer());
-
-    let listener = TcpListener::bind(bind_addr).await?;
-    axum::serve(listener, app).await?;
-    Ok(())
+        .layer(cors_layer())
 }
 
 fn cors_layer() -> CorsLayer {
     let x_api_key = HeaderName::from_static("x-api-key");
     let base = CorsLayer::new()
-        .allow_methods([Method::POST, Method::OPTIONS])
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
         .allow_headers([CONTENT_TYPE, x_api_key]);
 
     match env::var("ACTUATOR_ALLOWED_ORIGIN") {
@@ -82,104 +226,474 @@ fn cors_layer() -> CorsLayer {
     }
 }
 
+async fn feeder_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<FeederView>, (StatusCode, Json<ApiResponse>)> {
+    if !authorized(&headers, &state.api_key) {
+        return Err(unauthorized());
+    }
+    Ok(Json(FeederView {
+        status: state.status.lock().await.feeder.clone(),
+        daily_ration_grams: state.ration.daily_grams,
+        calibration: state.calibrations.lock().await.0.clone(),
+    }))
+}
+
+async fn door_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<DoorStatus>, (StatusCode, Json<ApiResponse>)> {
+    if !authorized(&headers, &state.api_key) {
+        return Err(unauthorized());
+    }
+    Ok(Json(state.status.lock().await.door.clone()))
+}
+
 async fn feeder_activate(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(payload): Json<FeederRequest>,
 ) -> (StatusCode, Json<ApiResponse>) {
     if !authorized(&headers, &state.api_key) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(ApiResponse {
-                status: "error",
-                message: "unauthorized".to_string(),
-            }),
-        );
+        return unauthorized();
     }
 
-    let mut driver = state.driver.lock().await;
-    match driver.feeder_activate(&payload.device_key, payload.duration_ms.unwrap_or(2500)) {
+    // Holding the driver from the ration check until the run is recorded keeps concurrent
+    // requests from both fitting into the same remaining ration.
+    let driver = Arc::clone(&state.driver).lock_owned().await;
+    let now = SystemTime::now();
+    let day = state.ration.day(now);
+    let calibration = state
+        .calibrations
+        .lock()
+        .await
+        .0
+        .get(&payload.device_key)
+        .copied();
+    let dispensed_today = state
+        .status
+        .lock()
+        .await
+        .feeder
+        .dispensed_on(&payload.device_key, day);
+    let portion = match plan_portion(
+        &payload.device_key,
+        payload.duration_ms,
+        payload.grams,
+        calibration.as_ref(),
+        &state.ration,
+        dispensed_today,
+    ) {
+        Ok(portion) => portion,
+        Err(err) => return portion_rejected(err),
+    };
+    let device_key = payload.device_key.clone();
+    let (driver, result) = run_driver(driver, move |driver| {
+        driver.feeder_activate(&device_key, portion.duration_ms)
+    })
+    .await;
+    let result = match result {
+        Ok(()) => Ok(()),
+        Err(CommandError::Failed(err)) => Err(err),
+        Err(CommandError::Blocked(interlock)) => {
+            return state
+                .reject(interlock, |status, result| {
+                    status.record_feeder("activate", &payload.device_key, portion, day, result, now)
+                })
+                .await
+        }
+    };
+    state
+        .record(|status| {
+            status.record_feeder("activate", &payload.device_key, portion, day, &result, now)
+        })
+        .await;
+    if result.is_ok() {
+        state.log_feeder_run(&payload.device_key, now);
+    }
+    drop(driver);
+    match result {
         Ok(_) => (
             StatusCode::OK,
             Json(ApiResponse {
                 status: "ok",
-                message: "feeder activated".to_string(),
-            }),
-        ),
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                status: "error",
-                message: err,
+                message: match portion.grams {
+                    Some(grams) => format!(
+                        "feeder activated: {grams:.0} g in {}ms",
+                        portion.duration_ms
+                    ),
+                    None => "feeder activated".to_string(),
+                },
             }),
         ),
+        Err(err) => error_response(StatusCode::INTERNAL_SERVER_ERROR, err),
     }
 }
 
-async fn door_open(
+/// Runs the feeder for a fixed time so its output can be weighed. The run counts towards the
+/// ration by the current calibration's estimate until its weight is saved.
+async fn feeder_calibration_run(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<DoorRequest>,
+    Json(payload): Json<CalibrationRunRequest>,
 ) -> (StatusCode, Json<ApiResponse>) {
     if !authorized(&headers, &state.api_key) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(ApiResponse {
-                status: "error",
-                message: "unauthorized".to_string(),
-            }),
-        );
+        return unauthorized();
     }
 
-    let mut driver = state.driver.lock().await;
-    match driver.door_open(&payload.device_key) {
+    let driver = Arc::clone(&state.driver).lock_owned().await;
+    let now = SystemTime::now();
+    let day = state.ration.day(now);
+    let calibration = state
+        .calibrations
+        .lock()
+        .await
+        .0
+        .get(&payload.device_key)
+        .copied();
+    let (dispensed_today, pending) = {
+        let status = state.status.lock().await;
+        (
+            status.feeder.dispensed_on(&payload.device_key, day),
+            status
+                .feeder
+                .pending_calibration
+                .contains_key(&payload.device_key),
+        )
+    };
+    let portion = match plan_calibration_run(
+        &payload.device_key,
+        payload.duration_ms,
+        calibration.as_ref(),
+        &state.ration,
+        dispensed_today,
+        pending,
+    ) {
+        Ok(portion) => portion,
+        Err(err) => return portion_rejected(err),
+    };
+    let device_key = payload.device_key.clone();
+    let (driver, result) = run_driver(driver, move |driver| {
+        driver.feeder_activate(&device_key, portion.duration_ms)
+    })
+    .await;
+    let result = match result {
+        Ok(()) => Ok(()),
+        Err(CommandError::Failed(err)) => Err(err),
+        Err(CommandError::Blocked(interlock)) => {
+            return state
+                .reject(interlock, |status, result| {
+                    status.record_calibration_run(&payload.device_key, portion, day, result, now)
+                })
+                .await
+        }
+    };
+    state
+        .record(|status| {
+            status.record_calibration_run(&payload.device_key, portion, day, &result, now)
+        })
+        .await;
+    if result.is_ok() {
+        state.log_feeder_run(&payload.device_key, now);
+    }
+    drop(driver);
+    match result {
         Ok(_) => (
             StatusCode::OK,
             Json(ApiResponse {
                 status: "ok",
-                message: "door opened".to_string(),
-            }),
-        ),
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                status: "error",
-                message: err,
+                message: format!("feeder ran for {}ms", payload.duration_ms),
             }),
         ),
+        Err(err) => error_response(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+async fn feeder_calibration(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CalibrationRequest>,
+) -> (StatusCode, Json<ApiResponse>) {
+    if !authorized(&headers, &state.api_key) {
+        return unauthorized();
+    }
+
+    let calibration =
+        match FeederCalibration::measure(payload.duration_ms, payload.grams, SystemTime::now()) {
+            Ok(calibration) => calibration,
+            Err(err) => return error_response(StatusCode::BAD_REQUEST, err),
+        };
+    let mut calibrations = state.calibrations.lock().await;
+    calibrations
+        .0
+        .insert(payload.device_key.clone(), calibration);
+    if let Err(err) = calibrations.save(&state.calibration_path) {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, err);
     }
+    state
+        .record(|status| {
+            status
+                .feeder
+                .settle_calibration(&payload.device_key, payload.grams)
+        })
+        .await;
+    (
+        StatusCode::OK,
+        Json(ApiResponse {
+            status: "ok",
+            message: format!(
+                "feeder {} calibrated at {:.2} g/s",
+                payload.device_key, calibration.grams_per_second
+            ),
+        }),
+    )
+}
+
+async fn door_open(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<DoorRequest>,
+) -> (StatusCode, Json<ApiResponse>) {
+    move_door(state, headers, payload, DoorDirection::Open).await
 }
 
 async fn door_close(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(payload): Json<DoorRequest>,
+) -> (StatusCode, Json<ApiResponse>) {
+    move_door(state, headers, payload, DoorDirection::Close).await
+}
+
+async fn move_door(
+    state: AppState,
+    headers: HeaderMap,
+    payload: DoorRequest,
+    direction: DoorDirection,
 ) -> (StatusCode, Json<ApiResponse>) {
     if !authorized(&headers, &state.api_key) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(ApiResponse {
-                status: "error",
-                message: "unauthorized".to_string(),
-            }),
-        );
+        return unauthorized();
     }
 
-    let mut driver = state.driver.lock().await;
-    match driver.door_close(&payload.device_key) {
+    let (command, done) = match direction {
+        DoorDirection::Open => ("open", "door opened"),
+        DoorDirection::Close => ("close", "door closed"),
+    };
+    let mut driver = Arc::clone(&state.driver).lock_owned().await;
+    if let Err(interlock) = driver.check_door(direction) {
+        let door_state = driver.door_state();
+        return state
+            .reject(interlock, |status, result| {
+                status.record_door(command, result, door_state, SystemTime::now())
+            })
+            .await;
+    }
+    // Saved before the motor starts, so a restart mid-move resumes as `unknown`.
+    state.record(|status| status.start_door(direction)).await;
+    let (_, (result, door_state)) = run_driver(driver, move |driver| {
+        let result = match direction {
+            DoorDirection::Open => driver.door_open(&payload.device_key),
+            DoorDirection::Close => driver.door_close(&payload.device_key),
+        };
+        (result, driver.door_state())
+    })
+    .await;
+    let result = match result {
+        Ok(()) => Ok(()),
+        Err(CommandError::Failed(err)) => Err(err),
+        Err(CommandError::Blocked(interlock)) => {
+            return state
+                .reject(interlock, |status, result| {
+                    status.record_door(command, result, door_state, SystemTime::now())
+                })
+                .await
+        }
+    };
+    state
+        .record(|status| status.record_door(command, &result, door_state, SystemTime::now()))
+        .await;
+    match result {
         Ok(_) => (
             StatusCode::OK,
             Json(ApiResponse {
                 status: "ok",
-                message: "door closed".to_string(),
+                message: done.to_string(),
             }),
         ),
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                status: "error",
-                message: err,
-            }),
+        Err(err) => error_response(
+            door_failed(door_state),
+            format!("{err} (door {door_state})"),
         ),
     }
 }
+
+/// A jam, obstruction or travel timeout leaves the door stalled or reversed and answers 409, so
+/// clients do not drive it into the fault again; only a motor or I/O error leaves it unknown.
+fn door_failed(state: DoorState) -> StatusCode {
+    if state == DoorState::Unknown {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::CONFLICT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{router, AppState};
+    use crate::actuator_status::ActuatorStatus;
+    use crate::actuators::ActuatorDriver;
+    use crate::door::DoorState;
+    use crate::feeder::{FeederCalibrations, FeederRation};
+    use crate::interlocks::{InterlockLimits, InterlockedDriver};
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::Mutex;
+
+    struct IdleDriver;
+
+    impl ActuatorDriver for IdleDriver {
+        fn feeder_activate(&mut self, _device_key: &str, _duration_ms: u64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn door_open(&mut self, _device_key: &str) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn door_close(&mut self, _device_key: &str) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn door_state(&self) -> DoorState {
+            DoorState::Unknown
+        }
+
+        fn restore_door_state(&mut self, _state: DoorState) {}
+    }
+
+    /// Serves `driver` with default limits, keeping state files under a per-test `name`.
+    async fn serve(driver: Box<dyn ActuatorDriver>, name: &str) -> (SocketAddr, PathBuf) {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let path = |file: &str| dir.join(format!("{name}-{file}-{pid}.json"));
+        let status_path = path("state");
+        let state = AppState {
+            api_key: "KEY".to_string(),
+            driver: Arc::new(Mutex::new(InterlockedDriver::new(
+                driver,
+                InterlockLimits::default(),
+            ))),
+            status: Arc::new(Mutex::new(ActuatorStatus::default())),
+            status_path: status_path.to_str().expect("temp path").into(),
+            calibrations: Arc::new(Mutex::new(FeederCalibrations::default())),
+            calibration_path: path("calibration").to_str().expect("temp path").into(),
+            ration: FeederRation::default(),
+            feeder_log_path: path("history").to_str().expect("temp path").into(),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+        (addr, status_path)
+    }
+
+    #[tokio::test]
+    async fn blocked_commands_answer_with_the_interlock() {
+        let (addr, status_path) = serve(Box::new(IdleDriver), "interlock").await;
+        let client = reqwest::Client::new();
+        let post = |path: &str, body: serde_json::Value| {
+            client
+                .post(format!("http://{addr}/actuators/{path}"))
+                .header("x-api-key", "KEY")
+                .json(&body)
+                .send()
+        };
+        let expect = |response: reqwest::Response, code: u16| async move {
+            assert_eq!(response.status().as_u16(), code);
+            let body: serde_json::Value = response.json().await.expect("json body");
+            body["message"].as_str().expect("message").to_string()
+        };
+        let door = serde_json::json!({ "device_key": "DOOR" });
+
+        let feed = post(
+            "feeder/activate",
+            serde_json::json!({ "device_key": "FEEDER", "duration_ms": 600_000 }),
+        );
+        let message = expect(feed.await.expect("feed"), 409).await;
+        assert!(message.contains("`max_pulse`"), "{message}");
+
+        expect(post("door/open", door.clone()).await.expect("open"), 200).await;
+        let message = expect(post("door/open", door.clone()).await.expect("open"), 429).await;
+        assert!(message.contains("`min_interval`"), "{message}");
+        let message = expect(post("door/close", door).await.expect("close"), 429).await;
+        assert!(message.contains("`reversal_cooldown`"), "{message}");
+
+        let saved = ActuatorStatus::load(status_path.to_str().expect("temp path")).expect("load");
+        assert_eq!(
+            saved.feeder.command.last_command.as_deref(),
+            Some("activate")
+        );
+        assert_eq!(saved.feeder.command.last_command_ok, Some(false));
+        assert!(saved.feeder.last_activated_at_ms.is_none());
+        assert_eq!(saved.door.command.last_command.as_deref(), Some("close"));
+        assert_eq!(saved.door.command.last_error, Some(message));
+
+        let _ = std::fs::remove_file(status_path);
+    }
+
+    /// A door that jams on every close and reverses back open.
+    struct JammedDoor {
+        closes: Arc<AtomicU32>,
+    }
+
+    impl ActuatorDriver for JammedDoor {
+        fn feeder_activate(&mut self, _device_key: &str, _duration_ms: u64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn door_open(&mut self, _device_key: &str) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn door_close(&mut self, _device_key: &str) -> Result<(), String> {
+            self.closes.fetch_add(1, Ordering::SeqCst);
+            Err("door did not reach closed within 15000ms while closing; reversed to open".into())
+        }
+
+        fn door_state(&self) -> DoorState {
+            DoorState::Open
+        }
+
+        fn restore_door_state(&mut self, _state: DoorState) {}
+    }
+
+    #[tokio::test]
+    async fn jammed_door_is_reported_without_inviting_a_retry() {
+        let closes = Arc::new(AtomicU32::new(0));
+        let driver = JammedDoor {
+            closes: Arc::clone(&closes),
+        };
+        let (addr, status_path) = serve(Box::new(driver), "jammed").await;
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{addr}/actuators/door/close"))
+            .header("x-api-key", "KEY")
+            .json(&serde_json::json!({ "device_key": "DOOR" }))
+            .send()
+            .await
+            .expect("close");
+        assert_eq!(response.status().as_u16(), 409);
+        let body: serde_json::Value = response.json().await.expect("json body");
+        let message = body["message"].as_str().expect("message");
+        assert!(
+            message.ends_with("reversed to open (door open)"),
+            "{message}"
+        );
+        assert_eq!(closes.load(Ordering::SeqCst), 1);
+
+        let _ = std::fs::remove_file(status_path);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::door::{DoorDirection, DoorState};
+use crate::feeder::{DailyDispense, PendingCalibration, Portion};
+use crate::util::millis;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+use std::time::SystemTime;
+
+const ACTUATOR_STATE_FILE_DEFAULT: &str = "actuator-state.json";
+
+/// The last command sent to one device and the last one that failed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandStatus {
+    pub last_command: Option<String>,
+    pub last_command_at_ms: Option<u64>,
+    pub last_command_ok: Option<bool>,
+    pub last_error: Option<String>,
+    pub last_error_at_ms: Option<u64>,
+}
+
+impl CommandStatus {
+    fn record(&mut self, command: &str, result: &Result<(), String>, at: SystemTime) {
+        let at_ms = millis(at);
+        self.last_command = Some(command.to_string());
+        self.last_command_at_ms = Some(at_ms);
+        self.last_command_ok = Some(result.is_ok());
+        if let Err(err) = result {
+            self.last_error = Some(err.clone());
+            self.last_error_at_ms = Some(at_ms);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DoorStatus {
+    pub state: DoorState,
+    #[serde(flatten)]
+    pub command: CommandStatus,
+}
+
+impl Default for DoorStatus {
+    fn default() -> Self {
+        DoorStatus {
+            state: DoorState::Unknown,
+            command: CommandStatus::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeederStatus {
+    pub last_activated_at_ms: Option<u64>,
+    pub last_duration_ms: Option<u64>,
+    /// Grams of the last run, when the feeder was calibrated.
+    pub last_grams: Option<f32>,
+    /// Grams dispensed on the current ration day, per feeder device key.
+    pub dispensed: BTreeMap<String, DailyDispense>,
+    /// Calibration runs not weighed yet, per feeder device key.
+    pub pending_calibration: BTreeMap<String, PendingCalibration>,
+    #[serde(flatten)]
+    pub command: CommandStatus,
+}
+
+impl FeederStatus {
+    pub fn dispensed_on(&self, device_key: &str, day: i64) -> f32 {
+        self.dispensed
+            .get(device_key)
+            .map(|dispense| dispense.grams_on(day))
+            .unwrap_or(0.0)
+    }
+
+    /// Replaces the estimate counted for a pending calibration run with the `grams` it was
+    /// weighed at. A lighter weight keeps the estimate, so reporting less than came out cannot
+    /// win back ration.
+    pub fn settle_calibration(&mut self, device_key: &str, grams: f32) {
+        let Some(pending) = self.pending_calibration.remove(device_key) else {
+            return;
+        };
+        let correction = (grams - pending.estimated_grams.unwrap_or(0.0)).max(0.0);
+        self.dispensed
+            .entry(device_key.to_string())
+            .or_default()
+            .add(pending.day, correction);
+    }
+}
+
+/// What the actuator server last did, kept in `ACTUATOR_STATE_FILE` across restarts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActuatorStatus {
+    pub door: DoorStatus,
+    pub feeder: FeederStatus,
+}
+
+impl ActuatorStatus {
+    pub fn path_from_env() -> String {
+        env::var("ACTUATOR_STATE_FILE").unwrap_or_else(|_| ACTUATOR_STATE_FILE_DEFAULT.to_string())
+    }
+
+    /// Loads `path`; a missing file means nothing has run yet.
+    pub fn load(path: &str) -> Result<ActuatorStatus, String> {
+        if !Path::new(path).exists() {
+            return Ok(ActuatorStatus::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read actuator state `{path}`: {e}"))?;
+        serde_json::from_str(&content).map_err(|e| format!("invalid actuator state `{path}`: {e}"))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to encode actuator state: {e}"))?;
+        std::fs::write(path, content + "\n")
+            .map_err(|e| format!("failed to write actuator state `{path}`: {e}"))
+    }
+
+    /// The door position to resume from. A move cut short by a restart leaves the door
+    /// somewhere in between, so it is unknown rather than opening or closing.
+    pub fn resume_door_state(&self) -> DoorState {
+        match self.door.state {
+            DoorState::Opening | DoorState::Closing => DoorState::Unknown,
+            state => state,
+        }
+    }
+
+    /// Marks the door as moving before the motor starts, so a crash mid-move is remembered.
+    pub fn start_door(&mut self, direction: DoorDirection) {
+        self.door.state = direction.moving();
+    }
+
+    pub fn record_door(
+        &mut self,
+        command: &str,
+        result: &Result<(), String>,
+        state: DoorState,
+        at: SystemTime,
+    ) {
+        self.door.state = state;
+        self.door.command.record(command, result, at);
+    }
+
+    /// Records a feeder run; `day` is the ration day its grams count towards.
+    pub fn record_feeder(
+        &mut self,
+        command: &str,
+        device_key: &str,
+        portion: Portion,
+        day: i64,
+        result: &Result<(), String>,
+        at: SystemTime,
+    ) {
+        if result.is_ok() {
+            self.feeder.last_activated_at_ms = Some(millis(at));
+            self.feeder.last_duration_ms = Some(portion.duration_ms);
+            self.feeder.last_grams = portion.grams;
+            if let Some(grams) = portion.grams {
+                self.feeder
+                    .dispensed
+                    .entry(device_key.to_string())
+                    .or_default()
+                    .add(day, grams);
+            }
+        }
+        self.feeder.command.record(command, result, at);
+    }
+
+    /// Records a calibration run, counting its estimate until the weight is saved.
+    pub fn record_calibration_run(
+        &mut self,
+        device_key: &str,
+        portion: Portion,
+        day: i64,
+        result: &Result<(), String>,
+        at: SystemTime,
+    ) {
+        self.record_feeder("calibrate", device_key, portion, day, result, at);
+        if result.is_ok() {
+            self.feeder.pending_calibration.insert(
+                device_key.to_string(),
+                PendingCalibration {
+                    day,
+                    estimated_grams: portion.grams,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActuatorStatus;
+    use crate::door::{DoorDirection, DoorState};
+    use crate::feeder::Portion;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn records_commands_and_survives_a_restart() {
+        let at = UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut status = ActuatorStatus::default();
+        let portion = Portion {
+            duration_ms: 2000,
+            grams: Some(25.0),
+        };
+        status.record_feeder("activate", "FEEDER", portion, 3, &Ok(()), at);
+        status.record_feeder("activate", "FEEDER", portion, 3, &Ok(()), at);
+        status.record_feeder(
+            "activate",
+            "FEEDER",
+            portion,
+            3,
+            &Err("relay fault".to_string()),
+            at,
+        );
+        status.record_door(
+            "close",
+            &Err("door obstruction detected while closing; reversed to open".to_string()),
+            DoorState::Open,
+            at + Duration::from_secs(5),
+        );
+        status.record_door(
+            "open",
+            &Ok(()),
+            DoorState::Open,
+            at + Duration::from_secs(9),
+        );
+
+        assert_eq!(status.feeder.last_activated_at_ms, Some(1_000_000));
+        assert_eq!(status.feeder.dispensed_on("FEEDER", 3), 50.0);
+        assert_eq!(status.feeder.dispensed_on("FEEDER", 4), 0.0);
+
+        let estimate = Portion {
+            duration_ms: 2000,
+            grams: Some(20.0),
+        };
+        status.record_calibration_run("FEEDER", estimate, 3, &Ok(()), at);
+        assert_eq!(status.feeder.dispensed_on("FEEDER", 3), 70.0);
+        status.feeder.settle_calibration("FEEDER", 26.0);
+        assert_eq!(status.feeder.dispensed_on("FEEDER", 3), 76.0);
+        assert!(status.feeder.pending_calibration.is_empty());
+        status.feeder.settle_calibration("FEEDER", 26.0);
+        assert_eq!(status.feeder.dispensed_on("FEEDER", 3), 76.0);
+        status.record_calibration_run("FEEDER", estimate, 3, &Ok(()), at);
+        status.feeder.settle_calibration("FEEDER", 0.5);
+        assert_eq!(status.feeder.dispensed_on("FEEDER", 3), 96.0);
+        assert_eq!(status.door.command.last_command.as_deref(), Some("open"));
+        assert_eq!(status.door.command.last_command_ok, Some(true));
+        assert_eq!(status.door.command.last_error_at_ms, Some(1_005_000));
+
+        let path = std::env::temp_dir().join(format!("actuator-state-{}.json", std::process::id()));
+        let path = path.to_str().expect("temp path");
+        status.save(path).expect("save");
+        let restored = ActuatorStatus::load(path).expect("load");
+        let _ = std::fs::remove_file(path);
+        assert_eq!(restored, status);
+        assert_eq!(restored.resume_door_state(), DoorState::Open);
+
+        status.start_door(DoorDirection::Close);
+        status.save(path).expect("save");
+        let crashed = ActuatorStatus::load(path).expect("load");
+        let _ = std::fs::remove_file(path);
+        assert_eq!(crashed.resume_door_state(), DoorState::Unknown);
+        assert_eq!(
+            ActuatorStatus::load("missing-actuator-state.json").expect("missing file"),
+            ActuatorStatus::default()
+        );
+    }
+}
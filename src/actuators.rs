@@ -1,10 +1,17 @@
+use crate::door::{DoorConfig, DoorController, DoorDirection, DoorHardware, DoorState};
+use crate::interlocks::InterlockLimits;
+use crate::retry::{RetryPolicy, Retryable};
 use reqwest::blocking::Client;
-use serde::Serialize;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::fmt;
 use std::process::Command;
 use std::time::Duration;
 
 const ACTUATOR_API_BASE_URL_DEFAULT: &str = "http://127.0.0.1:8081";
+/// How long the controller gets to answer on top of the time the command keeps a motor running.
+const ACTUATOR_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
 
 fn redact_key(key: &str) -> String {
     let shown: String = key.chars().take(4).collect();
@@ -15,33 +22,176 @@ fn actuator_api_base_url() -> String {
     env::var("ACTUATOR_API_BASE_URL").unwrap_or_else(|_| ACTUATOR_API_BASE_URL_DEFAULT.to_string())
 }
 
-fn actuator_client() -> Client {
+fn actuator_client(run_time: Duration) -> Client {
     Client::builder()
-        .timeout(Duration::from_secs(2))
+        .timeout(ACTUATOR_REPLY_TIMEOUT.saturating_add(run_time))
         .build()
         .expect("failed to build actuator http client")
 }
 
+/// The server answers a feeder command once the pulse is over, at most `FEEDER_MAX_PULSE_MS`.
+fn feeder_run_time() -> Duration {
+    InterlockLimits::from_env()
+        .unwrap_or_default()
+        .max_feeder_pulse
+}
+
+/// The server answers a door command once the move, with its retries and reversal, is over.
+fn door_run_time() -> Duration {
+    DoorConfig::from_env().unwrap_or_default().longest_move()
+}
+
 #[derive(Serialize)]
 struct ActuatorCommand<'a> {
     device_key: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grams: Option<f32>,
+}
+
+impl<'a> ActuatorCommand<'a> {
+    fn new(device_key: &'a str) -> Self {
+        ActuatorCommand {
+            device_key,
+            duration_ms: None,
+            grams: None,
+        }
+    }
+}
+
+/// The actuator server's reply to a command, with its HTTP status code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActuatorReply {
+    pub http_status: u16,
+    pub status: String,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct ApiResponse {
+    status: String,
+    message: String,
+}
+
+impl ActuatorReply {
+    /// Parses the server's `{"status":..,"message":..}`; other bodies (e.g. an empty 204 or a
+    /// proxy's error page) keep the raw text as the message.
+    fn parse(http_status: StatusCode, body: &str) -> Self {
+        match serde_json::from_str::<ApiResponse>(body) {
+            Ok(api) => ActuatorReply {
+                http_status: http_status.as_u16(),
+                status: api.status,
+                message: api.message,
+            },
+            Err(_) => ActuatorReply {
+                http_status: http_status.as_u16(),
+                status: if http_status.is_success() {
+                    "ok"
+                } else {
+                    "error"
+                }
+                .to_string(),
+                message: body.trim().to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActuatorError {
+    /// The server answered with a non-success status.
+    Rejected(ActuatorReply),
+    /// No reply arrived: connection refused, timeout, ...
+    Transport(String),
+    /// The endpoint failed repeatedly and is not being called for now.
+    CircuitOpen(String),
+}
+
+impl fmt::Display for ActuatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActuatorError::Rejected(reply) if reply.message.is_empty() => {
+                write!(f, "controller returned HTTP {}", reply.http_status)
+            }
+            ActuatorError::Rejected(reply) => write!(
+                f,
+                "controller returned HTTP {}: {}",
+                reply.http_status, reply.message
+            ),
+            ActuatorError::Transport(err) => write!(f, "controller unreachable: {err}"),
+            ActuatorError::CircuitOpen(endpoint) => {
+                write!(f, "circuit open after repeated failures: {endpoint}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ActuatorError {}
+
+/// An attempt's error and whether sending the command again is safe.
+struct AttemptError {
+    error: ActuatorError,
+    retryable: bool,
+}
+
+impl Retryable for AttemptError {
+    fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+
+    fn circuit_open(endpoint: &str) -> Self {
+        AttemptError {
+            error: ActuatorError::CircuitOpen(endpoint.to_string()),
+            retryable: false,
+        }
+    }
 }
 
-fn send_command(path: &str, device_key: &str, api_key: &str) -> bool {
+/// Posts a command, retrying per [`RetryPolicy`]; each attempt waits `run_time` longer than
+/// [`ACTUATOR_REPLY_TIMEOUT`] for the reply. Commands that are not `idempotent` (the feeder) are
+/// only retried when the request never reached the controller, so a slow acknowledgement cannot
+/// dispense twice.
+fn send_command(
+    path: &str,
+    command: &ActuatorCommand,
+    api_key: &str,
+    idempotent: bool,
+    run_time: Duration,
+) -> Result<ActuatorReply, ActuatorError> {
     let url = format!("{}/{}", actuator_api_base_url().trim_end_matches('/'), path);
-    actuator_client()
-        .post(url)
-        .header("x-api-key", api_key)
-        .json(&ActuatorCommand { device_key })
-        .send()
-        .and_then(|resp| resp.error_for_status())
-        .is_ok()
+    let client = actuator_client(run_time);
+    RetryPolicy::shared()
+        .run_blocking(&url, || {
+            let response = client
+                .post(&url)
+                .header("x-api-key", api_key)
+                .json(command)
+                .send()
+                .map_err(|err| AttemptError {
+                    retryable: idempotent || err.is_connect(),
+                    error: ActuatorError::Transport(err.to_string()),
+                })?;
+            let status = response.status();
+            let reply = ActuatorReply::parse(status, &response.text().unwrap_or_default());
+            if status.is_success() {
+                Ok(reply)
+            } else {
+                Err(AttemptError {
+                    error: ActuatorError::Rejected(reply),
+                    retryable: idempotent && status.is_server_error(),
+                })
+            }
+        })
+        .map_err(|attempt| attempt.error)
 }
 
 pub trait ActuatorDriver: Send {
     fn feeder_activate(&mut self, device_key: &str, duration_ms: u64) -> Result<(), String>;
     fn door_open(&mut self, device_key: &str) -> Result<(), String>;
     fn door_close(&mut self, device_key: &str) -> Result<(), String>;
+    fn door_state(&self) -> DoorState;
+    fn restore_door_state(&mut self, state: DoorState);
 }
 
 pub fn create_driver_from_env() -> Result<Box<dyn ActuatorDriver>, String> {
@@ -49,46 +199,15 @@ pub fn create_driver_from_env() -> Result<Box<dyn ActuatorDriver>, String> {
     match backend.as_str() {
         "command" => Ok(Box::new(LocalActuatorDriver::default())),
         "rpi-gpio" => create_rpi_driver_from_env(),
+        "modbus" => Ok(Box::new(crate::modbus::modbus_driver_from_env()?)),
         _ => Err(format!(
-            "unsupported ACTUATOR_BACKEND `{backend}` (expected `command` or `rpi-gpio`)"
+            "unsupported ACTUATOR_BACKEND `{backend}` (expected `command`, `rpi-gpio` or `modbus`)"
         )),
     }
 }
 
-#[cfg(all(feature = "pi-hw", target_os = "linux"))]
-fn parse_u8_env(name: &str, default: u8) -> Result<u8, String> {
-    match env::var(name) {
-        Ok(value) => value
-            .parse::<u8>()
-            .map_err(|_| format!("invalid value for {name}: {value}")),
-        Err(_) => Ok(default),
-    }
-}
-
-#[cfg(all(feature = "pi-hw", target_os = "linux"))]
-fn parse_u64_env(name: &str, default: u64) -> Result<u64, String> {
-    match env::var(name) {
-        Ok(value) => value
-            .parse::<u64>()
-            .map_err(|_| format!("invalid value for {name}: {value}")),
-        Err(_) => Ok(default),
-    }
-}
-
-#[cfg(all(feature = "pi-hw", target_os = "linux"))]
-fn parse_bool_env(name: &str, default: bool) -> Result<bool, String> {
-    match env::var(name) {
-        Ok(value) => match value.as_str() {
-            "1" | "true" | "TRUE" | "yes" | "on" => Ok(true),
-            "0" | "false" | "FALSE" | "no" | "off" => Ok(false),
-            _ => Err(format!("invalid value for {name}: {value}")),
-        },
-        Err(_) => Ok(default),
-    }
-}
-
 pub struct LocalActuatorDriver {
-    pub door_is_open: bool,
+    pub door: DoorController,
     feeder_activate_cmd: Option<String>,
     door_open_cmd: Option<String>,
     door_close_cmd: Option<String>,
@@ -97,7 +216,11 @@ pub struct LocalActuatorDriver {
 impl Default for LocalActuatorDriver {
     fn default() -> Self {
         LocalActuatorDriver {
-            door_is_open: false,
+            // The door commands run to completion, so there is nothing to time.
+            door: DoorController::new(DoorConfig {
+                run_time: Duration::ZERO,
+                ..DoorConfig::default()
+            }),
             feeder_activate_cmd: env::var("FEEDER_ACTIVATE_CMD").ok(),
             door_open_cmd: env::var("DOOR_OPEN_CMD").ok(),
             door_close_cmd: env::var("DOOR_CLOSE_CMD").ok(),
@@ -107,17 +230,25 @@ impl Default for LocalActuatorDriver {
 
 #[cfg(all(feature = "pi-hw", target_os = "linux"))]
 fn create_rpi_driver_from_env() -> Result<Box<dyn ActuatorDriver>, String> {
-    let feeder_pin = parse_u8_env("FEEDER_GPIO_PIN", 17)?;
-    let door_open_pin = parse_u8_env("DOOR_OPEN_GPIO_PIN", 27)?;
-    let door_close_pin = parse_u8_env("DOOR_CLOSE_GPIO_PIN", 22)?;
+    use crate::util::{parse_bool_env, parse_env, parse_optional_env};
+
+    let feeder_pin = parse_env::<u8>("FEEDER_GPIO_PIN", 17)?;
+    let door_open_pin = parse_env::<u8>("DOOR_OPEN_GPIO_PIN", 27)?;
+    let door_close_pin = parse_env::<u8>("DOOR_CLOSE_GPIO_PIN", 22)?;
     let active_high = parse_bool_env("ACTUATOR_ACTIVE_HIGH", true)?;
-    let door_pulse_ms = parse_u64_env("DOOR_PULSE_MS", 1200)?;
+    let inputs = rpi_gpio::DoorInputPins {
+        top_limit: parse_optional_env::<u8>("DOOR_TOP_LIMIT_GPIO_PIN")?,
+        bottom_limit: parse_optional_env::<u8>("DOOR_BOTTOM_LIMIT_GPIO_PIN")?,
+        obstruction: parse_optional_env::<u8>("DOOR_OBSTRUCTION_GPIO_PIN")?,
+        active_low: parse_bool_env("DOOR_SWITCH_ACTIVE_LOW", true)?,
+    };
     let driver = RpiGpioActuatorDriver::new(
         feeder_pin,
         door_open_pin,
         door_close_pin,
         active_high,
-        door_pulse_ms,
+        inputs,
+        DoorConfig::from_env()?,
     )?;
     Ok(Box::new(driver))
 }
@@ -165,26 +296,56 @@ impl ActuatorDriver for LocalActuatorDriver {
     }
 
     fn door_open(&mut self, device_key: &str) -> Result<(), String> {
-        self.door_is_open = true;
-        println!(
-            "Door motor set to OPEN using device {}",
-            redact_key(device_key)
-        );
-        if let Some(cmd) = &self.door_open_cmd {
-            run_hardware_command(cmd, device_key, None)?;
-        }
-        Ok(())
+        let mut hardware = CommandDoor {
+            open_cmd: self.door_open_cmd.as_deref(),
+            close_cmd: self.door_close_cmd.as_deref(),
+            device_key,
+        };
+        self.door.open(&mut hardware).map(|_| ())
     }
 
     fn door_close(&mut self, device_key: &str) -> Result<(), String> {
-        self.door_is_open = false;
+        let mut hardware = CommandDoor {
+            open_cmd: self.door_open_cmd.as_deref(),
+            close_cmd: self.door_close_cmd.as_deref(),
+            device_key,
+        };
+        self.door.close(&mut hardware).map(|_| ())
+    }
+
+    fn door_state(&self) -> DoorState {
+        self.door.state()
+    }
+
+    fn restore_door_state(&mut self, state: DoorState) {
+        self.door.restore(state);
+    }
+}
+
+/// `DOOR_OPEN_CMD` / `DOOR_CLOSE_CMD` as door hardware without limit switches.
+struct CommandDoor<'a> {
+    open_cmd: Option<&'a str>,
+    close_cmd: Option<&'a str>,
+    device_key: &'a str,
+}
+
+impl DoorHardware for CommandDoor<'_> {
+    fn start(&mut self, direction: DoorDirection) -> Result<(), String> {
+        let (label, cmd) = match direction {
+            DoorDirection::Open => ("OPEN", self.open_cmd),
+            DoorDirection::Close => ("CLOSE", self.close_cmd),
+        };
         println!(
-            "Door motor set to CLOSE using device {}",
-            redact_key(device_key)
+            "Door motor set to {label} using device {}",
+            redact_key(self.device_key)
         );
-        if let Some(cmd) = &self.door_close_cmd {
-            run_hardware_command(cmd, device_key, None)?;
+        match cmd {
+            Some(cmd) => run_hardware_command(cmd, self.device_key, None),
+            None => Ok(()),
         }
+    }
+
+    fn stop(&mut self) -> Result<(), String> {
         Ok(())
     }
 }
@@ -192,17 +353,92 @@ impl ActuatorDriver for LocalActuatorDriver {
 #[cfg(all(feature = "pi-hw", target_os = "linux"))]
 mod rpi_gpio {
     use super::ActuatorDriver;
-    use rppal::gpio::{Gpio, Level, OutputPin};
+    use crate::door::{DoorConfig, DoorController, DoorDirection, DoorHardware, DoorState};
+    use rppal::gpio::{Gpio, InputPin, Level, OutputPin};
     use std::thread::sleep;
     use std::time::Duration;
 
+    /// Optional door inputs; switches close to ground when `active_low`.
+    pub struct DoorInputPins {
+        pub top_limit: Option<u8>,
+        pub bottom_limit: Option<u8>,
+        pub obstruction: Option<u8>,
+        pub active_low: bool,
+    }
+
+    struct GpioDoor {
+        open_pin: OutputPin,
+        close_pin: OutputPin,
+        top_limit: Option<InputPin>,
+        bottom_limit: Option<InputPin>,
+        obstruction: Option<InputPin>,
+        active_level: Level,
+        inactive_level: Level,
+        switch_level: Level,
+    }
+
+    impl DoorHardware for GpioDoor {
+        fn start(&mut self, direction: DoorDirection) -> Result<(), String> {
+            let (on, off) = match direction {
+                DoorDirection::Open => (&mut self.open_pin, &mut self.close_pin),
+                DoorDirection::Close => (&mut self.close_pin, &mut self.open_pin),
+            };
+            off.write(self.inactive_level);
+            on.write(self.active_level);
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), String> {
+            self.open_pin.write(self.inactive_level);
+            self.close_pin.write(self.inactive_level);
+            Ok(())
+        }
+
+        fn limit_reached(&mut self, direction: DoorDirection) -> Result<Option<bool>, String> {
+            let pin = match direction {
+                DoorDirection::Open => &self.top_limit,
+                DoorDirection::Close => &self.bottom_limit,
+            };
+            Ok(pin.as_ref().map(|pin| pin.read() == self.switch_level))
+        }
+
+        fn obstructed(&mut self) -> Result<bool, String> {
+            Ok(self
+                .obstruction
+                .as_ref()
+                .is_some_and(|pin| pin.read() == self.switch_level))
+        }
+    }
+
     pub struct RpiGpioActuatorDriver {
         feeder_pin: OutputPin,
-        door_open_pin: OutputPin,
-        door_close_pin: OutputPin,
+        door_hardware: GpioDoor,
+        door: DoorController,
         active_level: Level,
         inactive_level: Level,
-        door_pulse_ms: u64,
+    }
+
+    fn output(gpio: &Gpio, pin: u8, inactive: Level) -> Result<OutputPin, String> {
+        let mut output = gpio
+            .get(pin)
+            .map_err(|e| format!("gpio pin {pin} unavailable: {e}"))?
+            .into_output();
+        output.write(inactive);
+        Ok(output)
+    }
+
+    fn input(gpio: &Gpio, pin: Option<u8>, active_low: bool) -> Result<Option<InputPin>, String> {
+        let Some(pin) = pin else {
+            return Ok(None);
+        };
+        let input = gpio
+            .get(pin)
+            .map_err(|e| format!("gpio pin {pin} unavailable: {e}"))?;
+        Ok(Some(if active_low {
+            input.into_input_pullup()
+        } else {
+            input.into_input_pulldown()
+        }))
     }
 
     impl RpiGpioActuatorDriver {
@@ -211,78 +447,60 @@ mod rpi_gpio {
             door_open_pin: u8,
             door_close_pin: u8,
             active_high: bool,
-            door_pulse_ms: u64,
+            inputs: DoorInputPins,
+            door: DoorConfig,
         ) -> Result<Self, String> {
             let gpio = Gpio::new().map_err(|e| format!("gpio init failed: {e}"))?;
             let active_level = if active_high { Level::High } else { Level::Low };
             let inactive_level = if active_high { Level::Low } else { Level::High };
 
-            let mut feeder = gpio
-                .get(feeder_pin)
-                .map_err(|e| format!("gpio pin {feeder_pin} unavailable: {e}"))?
-                .into_output();
-            feeder.write(inactive_level);
-
-            let mut door_open = gpio
-                .get(door_open_pin)
-                .map_err(|e| format!("gpio pin {door_open_pin} unavailable: {e}"))?
-                .into_output();
-            door_open.write(inactive_level);
-
-            let mut door_close = gpio
-                .get(door_close_pin)
-                .map_err(|e| format!("gpio pin {door_close_pin} unavailable: {e}"))?
-                .into_output();
-            door_close.write(inactive_level);
+            let door_hardware = GpioDoor {
+                open_pin: output(&gpio, door_open_pin, inactive_level)?,
+                close_pin: output(&gpio, door_close_pin, inactive_level)?,
+                top_limit: input(&gpio, inputs.top_limit, inputs.active_low)?,
+                bottom_limit: input(&gpio, inputs.bottom_limit, inputs.active_low)?,
+                obstruction: input(&gpio, inputs.obstruction, inputs.active_low)?,
+                active_level,
+                inactive_level,
+                switch_level: if inputs.active_low {
+                    Level::Low
+                } else {
+                    Level::High
+                },
+            };
 
             Ok(Self {
-                feeder_pin: feeder,
-                door_open_pin: door_open,
-                door_close_pin: door_close,
+                feeder_pin: output(&gpio, feeder_pin, inactive_level)?,
+                door_hardware,
+                door: DoorController::new(door),
                 active_level,
                 inactive_level,
-                door_pulse_ms,
             })
         }
-
-        fn pulse(pin: &mut OutputPin, active: Level, inactive: Level, ms: u64) {
-            pin.write(active);
-            sleep(Duration::from_millis(ms));
-            pin.write(inactive);
-        }
     }
 
     impl ActuatorDriver for RpiGpioActuatorDriver {
         fn feeder_activate(&mut self, _device_key: &str, duration_ms: u64) -> Result<(), String> {
-            Self::pulse(
-                &mut self.feeder_pin,
-                self.active_level,
-                self.inactive_level,
-                duration_ms,
-            );
+            self.feeder_pin.write(self.active_level);
+            sleep(Duration::from_millis(duration_ms));
+            self.feeder_pin.write(self.inactive_level);
             Ok(())
         }
 
         fn door_open(&mut self, _device_key: &str) -> Result<(), String> {
-            self.door_close_pin.write(self.inactive_level);
-            Self::pulse(
-                &mut self.door_open_pin,
-                self.active_level,
-                self.inactive_level,
-                self.door_pulse_ms,
-            );
-            Ok(())
+            self.door.open(&mut self.door_hardware).map(|_| ())
         }
 
         fn door_close(&mut self, _device_key: &str) -> Result<(), String> {
-            self.door_open_pin.write(self.inactive_level);
-            Self::pulse(
-                &mut self.door_close_pin,
-                self.active_level,
-                self.inactive_level,
-                self.door_pulse_ms,
-            );
-            Ok(())
+            self.door.close(&mut self.door_hardware).map(|_| ())
+        }
+
+        fn door_state(&self) -> DoorState {
+            self.door.state()
+        }
+
+        fn restore_door_state(&mut self, state: DoorState) {
+            self.door.restore(state);
         }
     }
 }
@@ -293,6 +511,8 @@ use rpi_gpio::RpiGpioActuatorDriver;
 pub struct FeederMotor {
     pub key: String,
     pub api_key: String,
+    /// Grams to ask for per activation; `None` leaves the portion to the server's default run.
+    pub portion_grams: Option<f32>,
 }
 
 impl FeederMotor {
@@ -300,14 +520,62 @@ impl FeederMotor {
         FeederMotor {
             key: key.to_string(),
             api_key: api_key.to_string(),
+            portion_grams: None,
         }
     }
 
-    pub fn activate(&self) {
+    pub fn activate(&self) -> Result<ActuatorReply, ActuatorError> {
         println!("Sending feeder command using key {}", redact_key(&self.key));
-        if !send_command("actuators/feeder/activate", &self.key, &self.api_key) {
-            eprintln!("Feeder command failed.");
-        }
+        let command = ActuatorCommand {
+            grams: self.portion_grams,
+            ..ActuatorCommand::new(&self.key)
+        };
+        send_command(
+            "actuators/feeder/activate",
+            &command,
+            &self.api_key,
+            false,
+            feeder_run_time(),
+        )
+    }
+
+    /// Runs the feeder for `duration_ms` so the output can be weighed.
+    pub fn calibration_run(&self, duration_ms: u64) -> Result<ActuatorReply, ActuatorError> {
+        println!(
+            "Sending feeder calibration run using key {}",
+            redact_key(&self.key)
+        );
+        let command = ActuatorCommand {
+            duration_ms: Some(duration_ms),
+            ..ActuatorCommand::new(&self.key)
+        };
+        send_command(
+            "actuators/feeder/calibrate",
+            &command,
+            &self.api_key,
+            false,
+            Duration::from_millis(duration_ms),
+        )
+    }
+
+    /// Stores the rate measured from `grams` dispensed in `duration_ms`.
+    pub fn save_calibration(
+        &self,
+        duration_ms: u64,
+        grams: f32,
+    ) -> Result<ActuatorReply, ActuatorError> {
+        let command = ActuatorCommand {
+            duration_ms: Some(duration_ms),
+            grams: Some(grams),
+            ..ActuatorCommand::new(&self.key)
+        };
+        send_command(
+            "actuators/feeder/calibration",
+            &command,
+            &self.api_key,
+            true,
+            Duration::ZERO,
+        )
     }
 }
 
@@ -324,30 +592,38 @@ impl CoopDoor {
         }
     }
 
-    pub fn open(&self) {
+    pub fn open(&self) -> Result<ActuatorReply, ActuatorError> {
         println!(
             "Sending door open command using key {}",
             redact_key(&self.key)
         );
-        if !send_command("actuators/door/open", &self.key, &self.api_key) {
-            eprintln!("Door open command failed.");
-        }
+        send_command(
+            "actuators/door/open",
+            &ActuatorCommand::new(&self.key),
+            &self.api_key,
+            true,
+            door_run_time(),
+        )
     }
 
-    pub fn close(&self) {
+    pub fn close(&self) -> Result<ActuatorReply, ActuatorError> {
         println!(
             "Sending door close command using key {}",
             redact_key(&self.key)
         );
-        if !send_command("actuators/door/close", &self.key, &self.api_key) {
-            eprintln!("Door close command failed.");
-        }
+        send_command(
+            "actuators/door/close",
+            &ActuatorCommand::new(&self.key),
+            &self.api_key,
+            true,
+            door_run_time(),
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{create_driver_from_env, CoopDoor, FeederMotor};
+    use super::{create_driver_from_env, ActuatorError, ActuatorReply, CoopDoor, FeederMotor};
     use std::io::{Read, Write};
     use std::net::TcpListener;
     use std::sync::{Arc, Mutex, OnceLock};
@@ -359,6 +635,16 @@ mod tests {
     }
 
     fn spawn_actuator_server(requests_to_serve: usize) -> (String, Arc<Mutex<Vec<String>>>) {
+        spawn_actuator_server_with(
+            requests_to_serve,
+            "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+        )
+    }
+
+    fn spawn_actuator_server_with(
+        requests_to_serve: usize,
+        response: String,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
         let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
         let addr = listener.local_addr().expect("local addr");
         let paths = Arc::new(Mutex::new(Vec::new()));
@@ -376,8 +662,6 @@ mod tests {
                         .expect("lock paths")
                         .push(first_line.to_string());
                 }
-                let response =
-                    "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
                 stream.write_all(response.as_bytes()).expect("write");
                 stream.flush().expect("flush");
             }
@@ -395,9 +679,10 @@ mod tests {
         let feeder = FeederMotor::new("FEEDER_DEVICE", "ACTUATOR_API_TOKEN");
         let door = CoopDoor::new("DOOR_DEVICE", "ACTUATOR_API_TOKEN");
 
-        feeder.activate();
-        door.open();
-        door.close();
+        let reply = feeder.activate().expect("feeder acknowledged");
+        assert_eq!(reply.http_status, 204);
+        door.open().expect("door opened");
+        door.close().expect("door closed");
 
         std::thread::sleep(std::time::Duration::from_millis(20));
         let seen = paths.lock().expect("lock paths");
@@ -414,6 +699,59 @@ mod tests {
         std::env::remove_var("ACTUATOR_API_BASE_URL");
     }
 
+    #[test]
+    fn rejected_command_carries_server_reply() {
+        let _guard = env_lock().lock().expect("env lock");
+        let body = r#"{"status":"error","message":"feeder jammed"}"#;
+        let response = format!(
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let (url, paths) = spawn_actuator_server_with(1, response);
+        std::env::set_var("ACTUATOR_API_BASE_URL", url);
+
+        let feeder = FeederMotor::new("FEEDER_DEVICE", "ACTUATOR_API_TOKEN");
+        let err = feeder.activate().expect_err("server rejected the command");
+        assert_eq!(
+            err,
+            ActuatorError::Rejected(ActuatorReply {
+                http_status: 500,
+                status: "error".to_string(),
+                message: "feeder jammed".to_string(),
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "controller returned HTTP 500: feeder jammed"
+        );
+        // The feeder is not idempotent, so a 500 is not retried.
+        assert_eq!(paths.lock().expect("lock paths").len(), 1);
+
+        std::env::remove_var("ACTUATOR_API_BASE_URL");
+    }
+
+    #[test]
+    fn jammed_door_close_is_sent_once() {
+        let _guard = env_lock().lock().expect("env lock");
+        let body = r#"{"status":"error","message":"door obstruction detected while closing; reversed to open (door open)"}"#;
+        let response = format!(
+            "HTTP/1.1 409 Conflict\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let (url, paths) = spawn_actuator_server_with(2, response);
+        std::env::set_var("ACTUATOR_API_BASE_URL", url);
+
+        let door = CoopDoor::new("DOOR_DEVICE", "ACTUATOR_API_TOKEN");
+        let err = door.close().expect_err("door jammed");
+        assert!(
+            matches!(&err, ActuatorError::Rejected(reply) if reply.http_status == 409),
+            "{err}"
+        );
+        assert_eq!(paths.lock().expect("lock paths").len(), 1);
+
+        std::env::remove_var("ACTUATOR_API_BASE_URL");
+    }
+
     #[test]
     fn command_backend_is_default() {
         let _guard = env_lock().lock().expect("env lock");
@@ -0,0 +1,334 @@
+use crate::i2c_sensors::I2cBus;
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use async_trait::async_trait;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+const ADS1115_REG_CONVERSION: u8 = 0x00;
+const ADS1115_REG_CONFIG: u8 = 0x01;
+/// Start single-shot, ±4.096 V range, 128 samples/s, comparator off; OR in the channel mux.
+const ADS1115_CONFIG_SINGLE_SHOT: u16 = 0x8383;
+const ADS1115_FULL_SCALE_VOLTS: f32 = 4.096;
+const ADS1115_CONVERSION_MS: u64 = 9;
+
+/// Fit of the MQ-137 NH3 sensitivity curve: log10(Rs/R0) = M * log10(ppm) + B.
+const MQ137_CURVE_M: f32 = -0.263;
+const MQ137_CURVE_B: f32 = 0.42;
+
+const MHZ19_CMD_READ_CO2: [u8; 9] = [0xFF, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79];
+
+/// Minimal serial access needed by the MH-Z19 driver, so it can run against a mock port.
+pub trait SerialPort: Send {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String>;
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), String>;
+}
+
+/// One gas concentration per measurement, in ppm.
+pub trait GasDriver: Send {
+    fn measure_ppm(&mut self) -> Result<f32, SensorError>;
+}
+
+/// ADS1115 single-ended voltage on `channel` (0-3).
+fn ads1115_read_volts<B: I2cBus>(bus: &mut B, addr: u8, channel: u8) -> Result<f32, SensorError> {
+    let i2c_error = |e: String| SensorError::Source(format!("ads1115: {e}"));
+    let config = ADS1115_CONFIG_SINGLE_SHOT | ((4 + (channel as u16 & 0x3)) << 12);
+    let [high, low] = config.to_be_bytes();
+    bus.write(addr, &[ADS1115_REG_CONFIG, high, low])
+        .map_err(i2c_error)?;
+    sleep(Duration::from_millis(ADS1115_CONVERSION_MS));
+    let mut raw = [0u8; 2];
+    bus.write_read(addr, &[ADS1115_REG_CONVERSION], &mut raw)
+        .map_err(i2c_error)?;
+    Ok(i16::from_be_bytes(raw) as f32 * ADS1115_FULL_SCALE_VOLTS / 32768.0)
+}
+
+/// MQ-137 ammonia module read through an ADS1115.
+pub struct Mq137<B: I2cBus> {
+    pub bus: B,
+    pub addr: u8,
+    pub channel: u8,
+    /// Load resistor on the module, in kΩ.
+    pub load_kohm: f32,
+    /// Sensor resistance at the datasheet reference point, calibrated per module, in kΩ.
+    pub r0_kohm: f32,
+    pub supply_volts: f32,
+    /// Module output voltage per volt seen at the ADC, e.g. `1.5` behind a 5V-to-3.3V divider.
+    pub divider: f32,
+}
+
+impl<B: I2cBus> Mq137<B> {
+    fn ppm_from_volts(&self, adc_volts: f32) -> Result<f32, SensorError> {
+        let vout = adc_volts * self.divider;
+        if vout <= 0.0 || vout >= self.supply_volts {
+            return Err(SensorError::Source(format!(
+                "mq137 output {vout:.3}V outside 0..{}V",
+                self.supply_volts
+            )));
+        }
+        let rs_kohm = self.load_kohm * (self.supply_volts - vout) / vout;
+        let ratio = rs_kohm / self.r0_kohm;
+        Ok(10f32.powf((ratio.log10() - MQ137_CURVE_B) / MQ137_CURVE_M))
+    }
+}
+
+impl<B: I2cBus> GasDriver for Mq137<B> {
+    fn measure_ppm(&mut self) -> Result<f32, SensorError> {
+        let volts = ads1115_read_volts(&mut self.bus, self.addr, self.channel)?;
+        self.ppm_from_volts(volts)
+    }
+}
+
+fn mhz19_checksum(packet: &[u8; 9]) -> u8 {
+    let sum = packet[1..8].iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    0xFFu8.wrapping_sub(sum).wrapping_add(1)
+}
+
+/// MH-Z19 NDIR CO2 module on a UART at 9600 baud.
+pub struct MhZ19<S: SerialPort> {
+    port: S,
+}
+
+impl<S: SerialPort> MhZ19<S> {
+    pub fn new(port: S) -> Self {
+        MhZ19 { port }
+    }
+}
+
+impl<S: SerialPort> GasDriver for MhZ19<S> {
+    fn measure_ppm(&mut self) -> Result<f32, SensorError> {
+        let uart_error = |e: String| SensorError::Source(format!("mhz19: {e}"));
+        self.port.write(&MHZ19_CMD_READ_CO2).map_err(uart_error)?;
+        let mut response = [0u8; 9];
+        self.port.read_exact(&mut response).map_err(uart_error)?;
+        if response[0] != 0xFF || response[1] != 0x86 {
+            return Err(SensorError::Source(
+                "mhz19 sent an unexpected response".to_string(),
+            ));
+        }
+        if response[8] != mhz19_checksum(&response) {
+            return Err(SensorError::Source("mhz19 checksum mismatch".to_string()));
+        }
+        Ok(u16::from_be_bytes([response[2], response[3]]) as f32)
+    }
+}
+
+/// Serves ammonia and CO2 from whichever gas modules are fitted.
+pub struct AirQualitySource {
+    ammonia: Option<Mutex<Box<dyn GasDriver>>>,
+    co2: Option<Mutex<Box<dyn GasDriver>>>,
+}
+
+impl AirQualitySource {
+    pub fn new(ammonia: Option<Box<dyn GasDriver>>, co2: Option<Box<dyn GasDriver>>) -> Self {
+        AirQualitySource {
+            ammonia: ammonia.map(Mutex::new),
+            co2: co2.map(Mutex::new),
+        }
+    }
+}
+
+#[async_trait]
+impl SensorSource for AirQualitySource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let driver = match kind {
+            SensorKind::Ammonia => self.ammonia.as_ref(),
+            SensorKind::Co2 => self.co2.as_ref(),
+            _ => None,
+        }
+        .ok_or(SensorError::Unsupported(kind))?;
+        let ppm = driver
+            .lock()
+            .map_err(|_| SensorError::Source("gas driver lock poisoned".to_string()))?
+            .measure_ppm()?;
+        Ok(Reading::new(
+            kind.as_str(),
+            kind,
+            SensorValue::Numeric(ppm),
+            SystemTime::now(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) mod rpi_uart {
+    use super::SerialPort;
+    use rppal::uart::{Parity, Uart};
+    use std::time::Duration;
+
+    pub struct RppalSerialPort {
+        uart: Uart,
+    }
+
+    impl RppalSerialPort {
+        pub fn new(path: &str) -> Result<Self, String> {
+            RppalSerialPort::with_baud(path, 9600)
+        }
+
+        pub fn with_baud(path: &str, baud: u32) -> Result<Self, String> {
+            let uart = Uart::with_path(path, baud, Parity::None, 8, 1)
+                .map_err(|e| format!("uart {path} unavailable: {e}"))?;
+            Ok(RppalSerialPort { uart })
+        }
+    }
+
+    impl SerialPort for RppalSerialPort {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+            self.uart
+                .write(bytes)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+
+        fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), String> {
+            self.uart
+                .set_read_mode(buffer.len().min(255) as u8, Duration::from_secs(1))
+                .map_err(|e| e.to_string())?;
+            let read = self.uart.read(buffer).map_err(|e| e.to_string())?;
+            if read < buffer.len() {
+                return Err(format!("timed out after {read} of {} bytes", buffer.len()));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Builds the modules listed in `AIR_SENSORS` (default `mq137,mhz19`).
+#[cfg(target_os = "linux")]
+pub fn air_quality_source_from_env() -> Result<AirQualitySource, String> {
+    use crate::i2c_sensors::{i2c_bus_from_env, parse_addr_env};
+    use crate::util::parse_env;
+
+    let modules = std::env::var("AIR_SENSORS").unwrap_or_else(|_| "mq137,mhz19".to_string());
+    let mut ammonia: Option<Box<dyn GasDriver>> = None;
+    let mut co2: Option<Box<dyn GasDriver>> = None;
+    for module in modules.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+        match module {
+            "mq137" => {
+                ammonia = Some(Box::new(Mq137 {
+                    bus: i2c_bus_from_env()?,
+                    addr: parse_addr_env("ADS1115_ADDR", 0x48)?,
+                    channel: parse_env("MQ137_ADC_CHANNEL", 0)?,
+                    load_kohm: parse_env("MQ137_LOAD_KOHM", 47.0)?,
+                    r0_kohm: parse_env("MQ137_R0_KOHM", 30.0)?,
+                    supply_volts: parse_env("MQ137_SUPPLY_VOLTS", 5.0)?,
+                    divider: parse_env("MQ137_DIVIDER", 1.0)?,
+                }));
+            }
+            "mhz19" => {
+                let path =
+                    std::env::var("MHZ19_UART").unwrap_or_else(|_| "/dev/serial0".to_string());
+                co2 = Some(Box::new(MhZ19::new(rpi_uart::RppalSerialPort::new(&path)?)));
+            }
+            other => {
+                return Err(format!(
+                    "unknown AIR_SENSORS module `{other}` (expected `mq137` or `mhz19`)"
+                ))
+            }
+        }
+    }
+    Ok(AirQualitySource::new(ammonia, co2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mhz19_checksum, AirQualitySource, I2cBus, MhZ19, Mq137, SerialPort};
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers every conversion-register read with `conversion` and records writes.
+    struct MockAdc {
+        conversion: [u8; 2],
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl I2cBus for MockAdc {
+        fn write(&mut self, _addr: u8, bytes: &[u8]) -> Result<(), String> {
+            self.writes.lock().expect("writes").push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, _addr: u8, _buffer: &mut [u8]) -> Result<(), String> {
+            Err("unexpected plain read".to_string())
+        }
+
+        fn write_read(&mut self, _addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), String> {
+            assert_eq!(bytes, [0x00]);
+            buffer.copy_from_slice(&self.conversion);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockSerial {
+        responses: VecDeque<[u8; 9]>,
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl SerialPort for MockSerial {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+            self.writes.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), String> {
+            let response = self.responses.pop_front().ok_or("timed out")?;
+            buffer.copy_from_slice(&response);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn mq137_converts_adc_volts_to_ppm() {
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        // 20000 counts = 2.5V: half the 5V supply, so Rs equals the 47k load and R0.
+        let mq137 = Mq137 {
+            bus: MockAdc {
+                conversion: 20000i16.to_be_bytes(),
+                writes: Arc::clone(&writes),
+            },
+            addr: 0x48,
+            channel: 1,
+            load_kohm: 47.0,
+            r0_kohm: 47.0,
+            supply_volts: 5.0,
+            divider: 1.0,
+        };
+        let source = AirQualitySource::new(Some(Box::new(mq137)), None);
+
+        let reading = source.sample(SensorKind::Ammonia).await.expect("ammonia");
+        let SensorValue::Numeric(ppm) = reading.value else {
+            panic!("expected numeric ammonia");
+        };
+        assert!((ppm - 39.5).abs() < 0.5, "got {ppm}");
+        assert_eq!(reading.to_string(), format!("{ppm:.1}ppm"));
+        assert_eq!(writes.lock().expect("writes")[0], [0x01, 0xD3, 0x83]);
+        assert_eq!(
+            source.sample(SensorKind::Co2).await,
+            Err(SensorError::Unsupported(SensorKind::Co2))
+        );
+    }
+
+    #[tokio::test]
+    async fn mhz19_reads_co2_and_checks_frames() {
+        let good = [0xFF, 0x86, 0x02, 0x60, 0x47, 0x00, 0x00, 0x00, 0xD1];
+        assert_eq!(mhz19_checksum(&good), 0xD1);
+        let mut corrupt = good;
+        corrupt[3] = 0x61;
+        let port = MockSerial {
+            responses: VecDeque::from([good, corrupt]),
+            ..MockSerial::default()
+        };
+        let source = AirQualitySource::new(None, Some(Box::new(MhZ19::new(port))));
+
+        let co2 = source.sample(SensorKind::Co2).await.expect("co2");
+        assert_eq!(co2.value, SensorValue::Numeric(608.0));
+        assert!(matches!(
+            source.sample(SensorKind::Co2).await,
+            Err(SensorError::Source(_))
+        ));
+    }
+}
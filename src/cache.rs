@@ -1,8 +1,11 @@
+use crate::sensors::Reading;
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 pub struct DataCache {
     pub key: String,
     pub store: HashMap<String, String>,
+    pub captured_at: HashMap<String, SystemTime>,
 }
 
 fn redact_key(key: &str) -> String {
@@ -16,12 +19,28 @@ impl DataCache {
         DataCache {
             key: key.to_string(),
             store: HashMap::new(),
+            captured_at: HashMap::new(),
         }
     }
 
     pub fn store(&mut self, k: &str, v: &str) {
         println!("Storing {} => {} in cache {}", k, v, redact_key(&self.key));
         self.store.insert(k.to_string(), v.to_string());
+        self.captured_at.remove(k);
+    }
+
+    pub fn store_reading(&mut self, k: &str, reading: &Reading) {
+        self.store(k, &reading.to_string());
+        self.captured_at.insert(k.to_string(), reading.captured_at);
+    }
+
+    /// Age of the reading behind `k`, if it was stored with a capture time.
+    pub fn age(&self, k: &str) -> Option<Duration> {
+        self.captured_at.get(k).map(|t| {
+            SystemTime::now()
+                .duration_since(*t)
+                .unwrap_or(Duration::ZERO)
+        })
     }
 
     pub fn retrieve(&self, k: &str) -> Option<&String> {
@@ -32,6 +51,8 @@ impl DataCache {
 #[cfg(test)]
 mod tests {
     use super::DataCache;
+    use crate::sensors::{Reading, SensorKind, SensorValue};
+    use std::time::{Duration, SystemTime};
 
     #[test]
     fn cache_store_and_retrieve_round_trip() {
@@ -41,4 +62,23 @@ mod tests {
         assert_eq!(cache.retrieve("last_temp"), Some(&"32.0C".to_string()));
         assert_eq!(cache.retrieve("missing"), None);
     }
+
+    #[test]
+    fn cached_readings_track_their_age() {
+        let mut cache = DataCache::new("CACHE_KEY");
+        let captured_at = SystemTime::now() - Duration::from_secs(120);
+        let reading = Reading::new(
+            "temperature",
+            SensorKind::Temperature,
+            SensorValue::Numeric(21.5),
+            captured_at,
+        );
+        cache.store_reading("last_temp", &reading);
+
+        assert_eq!(cache.retrieve("last_temp"), Some(&"21.5C".to_string()));
+        assert!(cache.age("last_temp").expect("age") >= Duration::from_secs(120));
+
+        cache.store("last_temp", "unavailable");
+        assert_eq!(cache.age("last_temp"), None);
+    }
 }
@@ -0,0 +1,268 @@
+use crate::sensors::{Reading, SensorKind, SensorValue, Unit};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::Path;
+
+const CALIBRATION_FILE_DEFAULT: &str = "calibration.json";
+
+/// A raw sensor value paired with what a trusted reference instrument showed at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationPoint {
+    pub raw: f32,
+    pub reference: f32,
+}
+
+/// Correction for one sensor: a linear `raw * scale + offset`, or a table of reference points.
+///
+/// With one point the sensor is shifted onto it; with two or more, values are interpolated
+/// between neighbouring points and extrapolated along the outermost segments. Points take
+/// precedence over `offset`/`scale`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Calibration {
+    pub offset: f32,
+    pub scale: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<CalibrationPoint>,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration {
+            offset: 0.0,
+            scale: 1.0,
+            points: Vec::new(),
+        }
+    }
+}
+
+impl Calibration {
+    pub fn apply_value(&self, raw: f32) -> f32 {
+        match self.points.as_slice() {
+            [] => raw * self.scale + self.offset,
+            [only] => raw + (only.reference - only.raw),
+            points => {
+                let upper = points
+                    .iter()
+                    .position(|p| p.raw >= raw)
+                    .unwrap_or(points.len() - 1)
+                    .max(1);
+                let (a, b) = (points[upper - 1], points[upper]);
+                if b.raw == a.raw {
+                    return raw + (b.reference - b.raw);
+                }
+                let t = (raw - a.raw) / (b.raw - a.raw);
+                a.reference + t * (b.reference - a.reference)
+            }
+        }
+    }
+
+    /// Corrects numeric readings; binary readings pass through untouched.
+    pub fn apply(&self, mut reading: Reading) -> Reading {
+        if let SensorValue::Numeric(raw) = reading.value {
+            reading.value = SensorValue::Numeric(self.apply_value(raw));
+        }
+        reading
+    }
+
+    /// Adds a reference point, replacing any earlier point taken at the same raw value.
+    pub fn record_point(&mut self, raw: f32, reference: f32) {
+        self.points.retain(|p| p.raw != raw);
+        self.points.push(CalibrationPoint { raw, reference });
+        self.points.sort_by(|a, b| a.raw.total_cmp(&b.raw));
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.points.is_empty() {
+            return write!(f, "scale {} offset {:+}", self.scale, self.offset);
+        }
+        let points: Vec<String> = self
+            .points
+            .iter()
+            .map(|p| format!("{} -> {}", p.raw, p.reference))
+            .collect();
+        write!(f, "points {}", points.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemperatureUnit {
+    #[default]
+    #[serde(rename = "C")]
+    Celsius,
+    #[serde(rename = "F")]
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// Converts a temperature typed in this unit (e.g. off a reference thermometer) to Celsius.
+    pub fn to_celsius(self, value: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => value,
+            TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+        }
+    }
+}
+
+/// Calibration file contents: per-sensor corrections keyed by sensor id, plus display units.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalibrationConfig {
+    pub temperature_unit: TemperatureUnit,
+    pub sensors: BTreeMap<String, Calibration>,
+}
+
+impl CalibrationConfig {
+    /// Path from `CALIBRATION_FILE`, defaulting to `calibration.json` in the working directory.
+    pub fn path_from_env() -> String {
+        env::var("CALIBRATION_FILE").unwrap_or_else(|_| CALIBRATION_FILE_DEFAULT.to_string())
+    }
+
+    /// Loads `path`; a missing file means no calibration and Celsius display.
+    pub fn load(path: &str) -> Result<CalibrationConfig, String> {
+        if !Path::new(path).exists() {
+            return Ok(CalibrationConfig::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read calibration file `{path}`: {e}"))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("invalid calibration file `{path}`: {e}"))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to encode calibration: {e}"))?;
+        std::fs::write(path, content + "\n")
+            .map_err(|e| format!("failed to write calibration file `{path}`: {e}"))
+    }
+
+    pub fn for_sensor(&self, sensor_id: &str) -> Calibration {
+        self.sensors.get(sensor_id).cloned().unwrap_or_default()
+    }
+
+    pub fn apply(&self, reading: Reading) -> Reading {
+        match self.sensors.get(&reading.sensor_id) {
+            Some(calibration) => calibration.apply(reading),
+            None => reading,
+        }
+    }
+
+    /// Converts Celsius readings to the configured display unit; other readings are unchanged.
+    pub fn display(&self, reading: &Reading) -> Reading {
+        let mut shown = reading.clone();
+        if let (TemperatureUnit::Fahrenheit, Unit::Celsius, SensorValue::Numeric(c)) =
+            (self.temperature_unit, reading.unit, reading.value)
+        {
+            shown.unit = Unit::Fahrenheit;
+            shown.value = SensorValue::Numeric(c * 9.0 / 5.0 + 32.0);
+        }
+        shown
+    }
+
+    /// Records a reference point for `sensor_id`; `reference` is in the display unit for temperatures.
+    pub fn record_point(&mut self, sensor_id: &str, kind: SensorKind, raw: f32, reference: f32) {
+        let reference = if kind == SensorKind::Temperature {
+            self.temperature_unit.to_celsius(reference)
+        } else {
+            reference
+        };
+        self.sensors
+            .entry(sensor_id.to_string())
+            .or_default()
+            .record_point(raw, reference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Calibration, CalibrationConfig, CalibrationPoint, TemperatureUnit};
+    use crate::sensors::{Reading, SensorKind, SensorValue};
+    use std::time::SystemTime;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn linear_and_single_point_corrections() {
+        let offset = Calibration {
+            offset: -1.5,
+            ..Calibration::default()
+        };
+        assert_close(offset.apply_value(22.0), 20.5);
+
+        let scaled = Calibration {
+            scale: 1.1,
+            offset: -2.0,
+            points: Vec::new(),
+        };
+        assert_close(scaled.apply_value(50.0), 53.0);
+
+        let mut single = Calibration::default();
+        single.record_point(21.5, 20.0);
+        assert_close(single.apply_value(30.0), 28.5);
+    }
+
+    #[test]
+    fn multi_point_table_interpolates_and_extrapolates() {
+        let table = Calibration {
+            points: vec![
+                CalibrationPoint {
+                    raw: 33.0,
+                    reference: 30.0,
+                },
+                CalibrationPoint {
+                    raw: 53.0,
+                    reference: 50.0,
+                },
+                CalibrationPoint {
+                    raw: 78.0,
+                    reference: 80.0,
+                },
+            ],
+            ..Calibration::default()
+        };
+        assert_close(table.apply_value(43.0), 40.0);
+        assert_close(table.apply_value(65.5), 65.0);
+        assert_close(table.apply_value(23.0), 20.0);
+        assert_close(table.apply_value(83.0), 86.0);
+    }
+
+    #[test]
+    fn config_round_trips_and_displays_fahrenheit() {
+        let path =
+            std::env::temp_dir().join(format!("coop-calibration-{}.json", std::process::id()));
+        let path = path.to_str().expect("utf8 temp path");
+        let _ = std::fs::remove_file(path);
+        assert_eq!(
+            CalibrationConfig::load(path).expect("missing file"),
+            CalibrationConfig::default()
+        );
+
+        let mut config = CalibrationConfig {
+            temperature_unit: TemperatureUnit::Fahrenheit,
+            ..CalibrationConfig::default()
+        };
+        config.record_point("temperature", SensorKind::Temperature, 23.0, 70.7);
+        config.save(path).expect("save");
+        let config = CalibrationConfig::load(path).expect("load");
+        let _ = std::fs::remove_file(path);
+
+        let raw = Reading::new(
+            "temperature",
+            SensorKind::Temperature,
+            SensorValue::Numeric(23.0),
+            SystemTime::now(),
+        );
+        let calibrated = config.apply(raw);
+        assert_eq!(calibrated.to_string(), "21.5C");
+        assert_eq!(config.display(&calibrated).to_string(), "70.7F");
+    }
+}
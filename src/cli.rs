@@ -14,6 +14,10 @@ pub enum Commands {
         #[command(subcommand)]
         action: FeedCommands,
     },
+    Door {
+        #[command(subcommand)]
+        action: DoorCommands,
+    },
     Run {
         #[command(subcommand)]
         action: RunCommands,
@@ -22,11 +26,29 @@ pub enum Commands {
         #[command(subcommand)]
         action: ServeCommands,
     },
+    Sensors {
+        #[command(subcommand)]
+        action: SensorCommands,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum FeedCommands {
     Now,
+    /// Measure the feeder's grams per second: run it, then weigh what it dispensed.
+    Calibrate {
+        #[arg(long, default_value_t = 5000)]
+        duration_ms: u64,
+        /// Grams from an earlier run of `duration_ms`; skips running the feeder.
+        #[arg(long)]
+        grams: Option<f32>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DoorCommands {
+    Open,
+    Close,
 }
 
 #[derive(Subcommand)]
@@ -38,10 +60,31 @@ pub enum RunCommands {
         camera_index: u32,
         #[arg(long, default_value_t = 1)]
         frames: u32,
+        /// Wait for a PIR motion start before each webcam frame.
+        #[arg(long)]
+        on_motion: bool,
     },
 }
 
 #[derive(Subcommand)]
 pub enum ServeCommands {
     Actuators,
+    Sensors,
+}
+
+#[derive(Subcommand)]
+pub enum SensorCommands {
+    /// Show calibrations, or record a reference point for one sensor.
+    Calibrate {
+        /// Sensor id, e.g. `temperature`, `humidity` or a named DS18B20 probe.
+        sensor: Option<String>,
+        /// Value shown by a trusted reference instrument right now.
+        #[arg(long)]
+        reference: Option<f32>,
+        /// Remove the sensor's calibration.
+        #[arg(long)]
+        clear: bool,
+    },
+    /// List readings rejected by the plausibility filter.
+    Diagnostics,
 }
@@ -0,0 +1,431 @@
+use crate::util::{parse_bool_env, parse_env};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+pub const DOOR_PULSE_MS_DEFAULT: u64 = 1200;
+const DOOR_TRAVEL_TIMEOUT_MS_DEFAULT: u64 = 15_000;
+const DOOR_LIMIT_POLL_MS_DEFAULT: u64 = 50;
+const DOOR_RETRIES_DEFAULT: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+    /// A move timed out or was obstructed and could not be recovered.
+    Stalled,
+    /// Not known since startup, or the motor failed mid-move.
+    Unknown,
+}
+
+impl fmt::Display for DoorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DoorState::Closed => "closed",
+            DoorState::Opening => "opening",
+            DoorState::Open => "open",
+            DoorState::Closing => "closing",
+            DoorState::Stalled => "stalled",
+            DoorState::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorDirection {
+    Open,
+    Close,
+}
+
+impl DoorDirection {
+    pub fn moving(self) -> DoorState {
+        match self {
+            DoorDirection::Open => DoorState::Opening,
+            DoorDirection::Close => DoorState::Closing,
+        }
+    }
+
+    fn target(self) -> DoorState {
+        match self {
+            DoorDirection::Open => DoorState::Open,
+            DoorDirection::Close => DoorState::Closed,
+        }
+    }
+}
+
+/// Motor outputs and optional switch inputs of one door, implemented by each actuator backend.
+pub trait DoorHardware {
+    fn start(&mut self, direction: DoorDirection) -> Result<(), String>;
+    fn stop(&mut self) -> Result<(), String>;
+
+    /// Whether the limit switch at the end `direction` travels to is pressed; `None` when that
+    /// end has no switch fitted.
+    fn limit_reached(&mut self, _direction: DoorDirection) -> Result<Option<bool>, String> {
+        Ok(None)
+    }
+
+    fn obstructed(&mut self) -> Result<bool, String> {
+        Ok(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoorConfig {
+    /// How long to drive when the end being travelled to has no limit switch; the door is then
+    /// assumed to have arrived.
+    pub run_time: Duration,
+    /// Longest a move may take before the door is considered jammed.
+    pub travel_timeout: Duration,
+    pub poll_interval: Duration,
+    /// Further attempts after a jam or obstruction.
+    pub retries: u32,
+    /// Drive a closing door back open when it jams or is obstructed, so nothing stays trapped.
+    pub reverse_on_obstruction: bool,
+}
+
+impl Default for DoorConfig {
+    fn default() -> Self {
+        DoorConfig {
+            run_time: Duration::from_millis(DOOR_PULSE_MS_DEFAULT),
+            travel_timeout: Duration::from_millis(DOOR_TRAVEL_TIMEOUT_MS_DEFAULT),
+            poll_interval: Duration::from_millis(DOOR_LIMIT_POLL_MS_DEFAULT),
+            retries: DOOR_RETRIES_DEFAULT,
+            reverse_on_obstruction: true,
+        }
+    }
+}
+
+impl DoorConfig {
+    /// Reads `DOOR_PULSE_MS`, `DOOR_TRAVEL_TIMEOUT_MS`, `DOOR_LIMIT_POLL_MS`, `DOOR_RETRIES`
+    /// and `DOOR_REVERSE_ON_OBSTRUCTION`.
+    pub fn from_env() -> Result<DoorConfig, String> {
+        let config = DoorConfig {
+            run_time: Duration::from_millis(parse_env("DOOR_PULSE_MS", DOOR_PULSE_MS_DEFAULT)?),
+            travel_timeout: Duration::from_millis(parse_env(
+                "DOOR_TRAVEL_TIMEOUT_MS",
+                DOOR_TRAVEL_TIMEOUT_MS_DEFAULT,
+            )?),
+            poll_interval: Duration::from_millis(parse_env(
+                "DOOR_LIMIT_POLL_MS",
+                DOOR_LIMIT_POLL_MS_DEFAULT,
+            )?),
+            retries: parse_env("DOOR_RETRIES", DOOR_RETRIES_DEFAULT)?,
+            reverse_on_obstruction: parse_bool_env("DOOR_REVERSE_ON_OBSTRUCTION", true)?,
+        };
+        if config.run_time > config.travel_timeout {
+            return Err("DOOR_PULSE_MS must not exceed DOOR_TRAVEL_TIMEOUT_MS".to_string());
+        }
+        if config.poll_interval.is_zero() {
+            return Err("DOOR_LIMIT_POLL_MS must be at least 1".to_string());
+        }
+        Ok(config)
+    }
+
+    /// Longest one open or close can take: every attempt running into the travel timeout and,
+    /// when closing, reversing back open.
+    pub fn longest_move(&self) -> Duration {
+        let per_attempt = if self.reverse_on_obstruction {
+            self.travel_timeout.saturating_mul(2)
+        } else {
+            self.travel_timeout
+        };
+        per_attempt.saturating_mul(self.retries.saturating_add(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Travel {
+    Arrived,
+    Jammed,
+    Obstructed,
+}
+
+/// Tracks where the door is and drives it through [`DoorHardware`], stopping on the limit
+/// switch, flagging jams after the travel timeout and backing off from obstructions.
+pub struct DoorController {
+    config: DoorConfig,
+    state: DoorState,
+}
+
+impl DoorController {
+    pub fn new(config: DoorConfig) -> Self {
+        DoorController {
+            config,
+            state: DoorState::Unknown,
+        }
+    }
+
+    pub fn state(&self) -> DoorState {
+        self.state
+    }
+
+    /// Resumes from a position remembered across a restart; limit switches still win on the
+    /// next move.
+    pub fn restore(&mut self, state: DoorState) {
+        self.state = state;
+    }
+
+    pub fn open(&mut self, hardware: &mut dyn DoorHardware) -> Result<DoorState, String> {
+        self.move_door(hardware, DoorDirection::Open)
+    }
+
+    pub fn close(&mut self, hardware: &mut dyn DoorHardware) -> Result<DoorState, String> {
+        self.move_door(hardware, DoorDirection::Close)
+    }
+
+    fn move_door(
+        &mut self,
+        hardware: &mut dyn DoorHardware,
+        direction: DoorDirection,
+    ) -> Result<DoorState, String> {
+        if hardware.limit_reached(direction)? == Some(true) {
+            self.state = direction.target();
+            return Ok(self.state);
+        }
+        let mut attempt = 0;
+        loop {
+            let reason = match self.travel(hardware, direction)? {
+                Travel::Arrived => {
+                    self.state = direction.target();
+                    return Ok(self.state);
+                }
+                Travel::Jammed => format!(
+                    "did not reach {} within {}ms",
+                    direction.target(),
+                    self.config.travel_timeout.as_millis()
+                ),
+                Travel::Obstructed => "obstruction detected".to_string(),
+            };
+            self.state = DoorState::Stalled;
+            if direction == DoorDirection::Close && self.config.reverse_on_obstruction {
+                if self.travel(hardware, DoorDirection::Open)? != Travel::Arrived {
+                    self.state = DoorState::Stalled;
+                    return Err(format!(
+                        "door {reason} while closing and could not reverse; motor stopped"
+                    ));
+                }
+                self.state = DoorState::Open;
+            }
+            if attempt >= self.config.retries {
+                return Err(match self.state {
+                    DoorState::Open => format!("door {reason} while closing; reversed to open"),
+                    _ => format!("door stalled: {reason}"),
+                });
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Drives one way until the limit switch, the run time, a jam or an obstruction, and always
+    /// stops the motor afterwards.
+    fn travel(
+        &mut self,
+        hardware: &mut dyn DoorHardware,
+        direction: DoorDirection,
+    ) -> Result<Travel, String> {
+        self.state = direction.moving();
+        let outcome = hardware
+            .start(direction)
+            .and_then(|_| self.watch(hardware, direction));
+        let stopped = hardware.stop();
+        match (outcome, stopped) {
+            (Ok(travel), Ok(())) => Ok(travel),
+            (Err(err), _) | (Ok(_), Err(err)) => {
+                self.state = DoorState::Unknown;
+                Err(err)
+            }
+        }
+    }
+
+    fn watch(
+        &self,
+        hardware: &mut dyn DoorHardware,
+        direction: DoorDirection,
+    ) -> Result<Travel, String> {
+        let started = Instant::now();
+        loop {
+            if hardware.obstructed()? {
+                return Ok(Travel::Obstructed);
+            }
+            let elapsed = started.elapsed();
+            let (deadline, outcome) = match hardware.limit_reached(direction)? {
+                Some(true) => return Ok(Travel::Arrived),
+                Some(false) => (self.config.travel_timeout, Travel::Jammed),
+                None => (self.config.run_time, Travel::Arrived),
+            };
+            if elapsed >= deadline {
+                return Ok(outcome);
+            }
+            sleep(self.config.poll_interval.min(deadline - elapsed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DoorConfig, DoorController, DoorDirection, DoorHardware, DoorState};
+    use std::time::Duration;
+
+    const TRAVEL_STEPS: i32 = 5;
+
+    /// A door that moves one step per poll; position 0 is closed and `TRAVEL_STEPS` open.
+    struct FakeDoor {
+        position: i32,
+        moving: Option<DoorDirection>,
+        limit_switches: bool,
+        jam_below: Option<i32>,
+        obstructed_polls: u32,
+        starts: Vec<DoorDirection>,
+        stops: u32,
+    }
+
+    impl FakeDoor {
+        fn new(position: i32) -> Self {
+            FakeDoor {
+                position,
+                moving: None,
+                limit_switches: true,
+                jam_below: None,
+                obstructed_polls: 0,
+                starts: Vec::new(),
+                stops: 0,
+            }
+        }
+    }
+
+    impl DoorHardware for FakeDoor {
+        fn start(&mut self, direction: DoorDirection) -> Result<(), String> {
+            self.moving = Some(direction);
+            self.starts.push(direction);
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), String> {
+            self.moving = None;
+            self.stops += 1;
+            Ok(())
+        }
+
+        fn limit_reached(&mut self, direction: DoorDirection) -> Result<Option<bool>, String> {
+            match self.moving {
+                Some(DoorDirection::Open) => self.position = (self.position + 1).min(TRAVEL_STEPS),
+                Some(DoorDirection::Close) => {
+                    let floor = self.jam_below.unwrap_or(0);
+                    self.position = (self.position - 1).max(floor);
+                }
+                None => {}
+            }
+            if !self.limit_switches {
+                return Ok(None);
+            }
+            Ok(Some(match direction {
+                DoorDirection::Open => self.position == TRAVEL_STEPS,
+                DoorDirection::Close => self.position == 0,
+            }))
+        }
+
+        fn obstructed(&mut self) -> Result<bool, String> {
+            if self.moving == Some(DoorDirection::Close) && self.obstructed_polls > 0 {
+                self.obstructed_polls -= 1;
+                return Ok(true);
+            }
+            Ok(false)
+        }
+    }
+
+    fn config() -> DoorConfig {
+        DoorConfig {
+            run_time: Duration::from_millis(5),
+            travel_timeout: Duration::from_millis(40),
+            poll_interval: Duration::from_millis(1),
+            retries: 1,
+            reverse_on_obstruction: true,
+        }
+    }
+
+    #[test]
+    fn moves_until_the_limit_switch_and_skips_when_already_there() {
+        let mut door = FakeDoor::new(0);
+        let mut controller = DoorController::new(config());
+        assert_eq!(controller.state(), DoorState::Unknown);
+
+        assert_eq!(controller.open(&mut door), Ok(DoorState::Open));
+        assert_eq!(door.position, TRAVEL_STEPS);
+        assert_eq!(controller.open(&mut door), Ok(DoorState::Open));
+        assert_eq!(door.starts, vec![DoorDirection::Open]);
+        assert_eq!(door.stops, 1);
+
+        assert_eq!(controller.close(&mut door), Ok(DoorState::Closed));
+        assert_eq!(door.position, 0);
+    }
+
+    #[test]
+    fn jam_while_closing_reverses_and_retries() {
+        let mut door = FakeDoor::new(TRAVEL_STEPS);
+        door.jam_below = Some(2);
+        let mut controller = DoorController::new(config());
+
+        let err = controller.close(&mut door).expect_err("door jammed");
+        assert_eq!(
+            err,
+            "door did not reach closed within 40ms while closing; reversed to open"
+        );
+        assert_eq!(controller.state(), DoorState::Open);
+        assert_eq!(
+            door.starts,
+            vec![
+                DoorDirection::Close,
+                DoorDirection::Open,
+                DoorDirection::Close,
+                DoorDirection::Open
+            ]
+        );
+        assert_eq!(door.moving, None);
+        // Two attempts, each closing and reversing into the travel timeout.
+        assert_eq!(config().longest_move(), Duration::from_millis(160));
+
+        let config = DoorConfig {
+            reverse_on_obstruction: false,
+            retries: 0,
+            ..config()
+        };
+        assert_eq!(config.longest_move(), Duration::from_millis(40));
+        let mut controller = DoorController::new(config);
+        assert!(controller.close(&mut door).is_err());
+        assert_eq!(controller.state(), DoorState::Stalled);
+    }
+
+    #[test]
+    fn obstruction_is_retried_after_reversing() {
+        let mut door = FakeDoor::new(TRAVEL_STEPS);
+        door.obstructed_polls = 1;
+        let mut controller = DoorController::new(config());
+
+        assert_eq!(controller.close(&mut door), Ok(DoorState::Closed));
+        assert_eq!(
+            door.starts,
+            vec![
+                DoorDirection::Close,
+                DoorDirection::Open,
+                DoorDirection::Close
+            ]
+        );
+    }
+
+    #[test]
+    fn without_switches_the_door_runs_for_the_pulse_time() {
+        let mut door = FakeDoor::new(0);
+        door.limit_switches = false;
+        let mut controller = DoorController::new(config());
+
+        assert_eq!(controller.open(&mut door), Ok(DoorState::Open));
+        assert_eq!(controller.open(&mut door), Ok(DoorState::Open));
+        assert_eq!(door.starts.len(), 2);
+        assert_eq!(door.stops, 2);
+    }
+}
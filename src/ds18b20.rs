@@ -0,0 +1,219 @@
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, Sensor, SensorError, SensorKind, SensorValue};
+use async_trait::async_trait;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const W1_SYSFS_ROOT_DEFAULT: &str = "/sys/bus/w1/devices";
+const DS18B20_FAMILY_PREFIX: &str = "28-";
+
+/// Parses a `w1_slave` dump: a CRC line ending in `YES` followed by a `t=<millidegrees>` line.
+fn parse_w1_slave(content: &str) -> Result<f32, SensorError> {
+    let mut lines = content.lines();
+    let crc_line = lines
+        .next()
+        .ok_or_else(|| SensorError::Source("empty w1_slave output".to_string()))?;
+    if !crc_line.trim_end().ends_with("YES") {
+        return Err(SensorError::Source("ds18b20 crc check failed".to_string()));
+    }
+    let millidegrees = lines
+        .next()
+        .and_then(|line| line.split("t=").nth(1))
+        .ok_or_else(|| SensorError::Source("w1_slave output has no t= value".to_string()))?
+        .trim()
+        .parse::<i32>()
+        .map_err(|e| SensorError::Source(format!("invalid ds18b20 temperature: {e}")))?;
+    Ok(millidegrees as f32 / 1000.0)
+}
+
+/// One DS18B20 probe, e.g. the coop interior, a nest box or the waterer.
+pub struct Ds18b20Probe {
+    pub id: String,
+    pub device_dir: PathBuf,
+}
+
+#[async_trait]
+impl Sensor for Ds18b20Probe {
+    async fn read(&self) -> Result<Reading, SensorError> {
+        let path = self.device_dir.join("w1_slave");
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| SensorError::Source(format!("failed to read {}: {e}", path.display())))?;
+        let celsius = parse_w1_slave(&content)?;
+        Ok(Reading::new(
+            &self.id,
+            SensorKind::Temperature,
+            SensorValue::Numeric(celsius),
+            SystemTime::now(),
+        ))
+    }
+}
+
+/// DS18B20 probes on the 1-Wire bus; the first probe answers for `SensorKind::Temperature`.
+pub struct Ds18b20Source {
+    probes: Vec<Ds18b20Probe>,
+}
+
+impl Ds18b20Source {
+    /// `probes` is `name=device` pairs such as `interior=28-0316a2799aff`; when empty,
+    /// every `28-*` device under `root` is used, named after its device id.
+    pub fn new(root: &Path, probes: &str) -> Result<Self, String> {
+        let probes = if probes.trim().is_empty() {
+            Self::discover(root)?
+        } else {
+            probes
+                .split(',')
+                .map(|entry| {
+                    let (id, device) = entry
+                        .split_once('=')
+                        .ok_or_else(|| format!("invalid DS18B20_PROBES entry `{entry}`"))?;
+                    Ok(Ds18b20Probe {
+                        id: id.trim().to_string(),
+                        device_dir: root.join(device.trim()),
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?
+        };
+        if probes.is_empty() {
+            return Err(format!("no DS18B20 probes found under {}", root.display()));
+        }
+        Ok(Ds18b20Source { probes })
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let root = env::var("W1_SYSFS_ROOT").unwrap_or_else(|_| W1_SYSFS_ROOT_DEFAULT.to_string());
+        let probes = env::var("DS18B20_PROBES").unwrap_or_default();
+        Self::new(Path::new(&root), &probes)
+    }
+
+    fn discover(root: &Path) -> Result<Vec<Ds18b20Probe>, String> {
+        let entries = std::fs::read_dir(root)
+            .map_err(|e| format!("failed to list {}: {e}", root.display()))?;
+        let mut probes: Vec<Ds18b20Probe> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with(DS18B20_FAMILY_PREFIX)
+                    .then(|| Ds18b20Probe {
+                        id: name,
+                        device_dir: entry.path(),
+                    })
+            })
+            .collect();
+        probes.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(probes)
+    }
+}
+
+#[async_trait]
+impl SensorSource for Ds18b20Source {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        match (kind, self.probes.first()) {
+            (SensorKind::Temperature, Some(primary)) => primary.read().await,
+            _ => Err(SensorError::Unsupported(kind)),
+        }
+    }
+
+    async fn auxiliary(&self) -> Vec<(String, Result<Reading, SensorError>)> {
+        let mut readings = Vec::new();
+        for probe in self.probes.iter().skip(1) {
+            readings.push((probe.id.clone(), probe.read().await));
+        }
+        readings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_w1_slave, Ds18b20Source};
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn fake_w1_tree(name: &str, devices: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("coop-w1-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (device, w1_slave) in devices {
+            fs::create_dir_all(root.join(device)).expect("create device dir");
+            fs::write(root.join(device).join("w1_slave"), w1_slave).expect("write w1_slave");
+        }
+        fs::create_dir_all(root.join("w1_bus_master1")).expect("create bus master dir");
+        root
+    }
+
+    #[test]
+    fn parses_w1_slave_output() {
+        let ok = "72 01 4b 46 7f ff 0e 10 57 : crc=57 YES\n72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+        assert_eq!(parse_w1_slave(ok), Ok(23.125));
+        let negative =
+            "5e ff 4b 46 7f ff 02 10 e1 : crc=e1 YES\n5e ff 4b 46 7f ff 02 10 e1 t=-10125\n";
+        assert_eq!(parse_w1_slave(negative), Ok(-10.125));
+        let bad_crc =
+            "72 01 4b 46 7f ff 0e 10 57 : crc=12 NO\n72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+        assert!(matches!(
+            parse_w1_slave(bad_crc),
+            Err(SensorError::Source(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_named_probes_from_sysfs_tree() {
+        let root = fake_w1_tree(
+            "named",
+            &[
+                ("28-000001", "aa : crc=57 YES\naa t=21500\n"),
+                ("28-000002", "aa : crc=57 YES\naa t=30250\n"),
+                ("28-000003", "aa : crc=57 YES\naa t=8000\n"),
+            ],
+        );
+        let source = Ds18b20Source::new(
+            &root,
+            "interior=28-000002, nest_box=28-000001, water=28-000003",
+        )
+        .expect("source");
+
+        let interior = source
+            .sample(SensorKind::Temperature)
+            .await
+            .expect("interior");
+        assert_eq!(interior.sensor_id, "interior");
+        assert_eq!(interior.value, SensorValue::Numeric(30.25));
+        assert_eq!(
+            source.sample(SensorKind::Humidity).await,
+            Err(SensorError::Unsupported(SensorKind::Humidity))
+        );
+
+        let extra = source.auxiliary().await;
+        let ids: Vec<&str> = extra.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["nest_box", "water"]);
+        assert_eq!(
+            extra[1].1.as_ref().map(|r| r.value),
+            Ok(SensorValue::Numeric(8.0))
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn discovers_probes_when_unnamed() {
+        let root = fake_w1_tree(
+            "discover",
+            &[
+                ("28-00000b", "aa : crc=57 YES\naa t=19000\n"),
+                ("28-00000a", "aa : crc=57 YES\naa t=20000\n"),
+            ],
+        );
+        let source = Ds18b20Source::new(&root, "").expect("source");
+
+        let primary = source
+            .sample(SensorKind::Temperature)
+            .await
+            .expect("primary");
+        assert_eq!(primary.sensor_id, "28-00000a");
+        assert_eq!(source.auxiliary().await.len(), 1);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::sensors::{Reading, SensorError, SensorValue};
+use crate::util::parse_env;
+use std::env;
+
+/// A typical large hen's egg; overridable with `EGG_WEIGHT_G`.
+const EGG_WEIGHT_G_DEFAULT: f32 = 58.0;
+
+/// Nest boxes from `NEST_BOXES`, e.g. `left,middle,right`; empty when not configured.
+pub fn nest_boxes_from_env() -> Vec<String> {
+    env::var("NEST_BOXES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Env name segment for a nest box, e.g. `left-top` -> `LEFT_TOP` in `NEST_BOX_LEFT_TOP_TARE_KG`.
+pub fn box_env_name(nest_box: &str) -> String {
+    nest_box
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// How a nest box reports its eggs: a direct count, or the weight on a scale under the nest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EggCounting {
+    Count,
+    Scale { tare_kg: f32, egg_weight_g: f32 },
+}
+
+impl EggCounting {
+    /// Reads `EGG_COUNT_METHOD` (`count` or `scale`), `EGG_WEIGHT_G` and `NEST_BOX_<BOX>_TARE_KG`.
+    pub fn from_env(nest_box: &str) -> Result<EggCounting, String> {
+        match env::var("EGG_COUNT_METHOD").as_deref() {
+            Ok("count") | Err(_) => Ok(EggCounting::Count),
+            Ok("scale") => {
+                let egg_weight_g = parse_env("EGG_WEIGHT_G", EGG_WEIGHT_G_DEFAULT)?;
+                if egg_weight_g <= 0.0 {
+                    return Err("EGG_WEIGHT_G must be positive".to_string());
+                }
+                let tare_var = format!("NEST_BOX_{}_TARE_KG", box_env_name(nest_box));
+                Ok(EggCounting::Scale {
+                    tare_kg: parse_env(&tare_var, 0.0)?,
+                    egg_weight_g,
+                })
+            }
+            Ok(other) => Err(format!(
+                "invalid value for EGG_COUNT_METHOD: {other} (expected `count` or `scale`)"
+            )),
+        }
+    }
+
+    /// A nest box given its own `tare_kg` or `egg_weight_g` is read as a scale whatever
+    /// `EGG_COUNT_METHOD` says; missing values come from the environment.
+    pub fn with_overrides(
+        self,
+        tare_kg: Option<f32>,
+        egg_weight_g: Option<f32>,
+    ) -> Result<EggCounting, String> {
+        if tare_kg.is_none() && egg_weight_g.is_none() {
+            return Ok(self);
+        }
+        let (base_tare_kg, base_egg_weight_g) = match self {
+            EggCounting::Scale {
+                tare_kg,
+                egg_weight_g,
+            } => (tare_kg, egg_weight_g),
+            EggCounting::Count => (0.0, parse_env("EGG_WEIGHT_G", EGG_WEIGHT_G_DEFAULT)?),
+        };
+        let egg_weight_g = egg_weight_g.unwrap_or(base_egg_weight_g);
+        if egg_weight_g <= 0.0 {
+            return Err("egg_weight_g must be positive".to_string());
+        }
+        Ok(EggCounting::Scale {
+            tare_kg: tare_kg.unwrap_or(base_tare_kg),
+            egg_weight_g,
+        })
+    }
+
+    /// Eggs for a raw value: counts pass through, weights in kg are divided by the egg weight.
+    pub fn count(&self, value: SensorValue) -> Result<u32, SensorError> {
+        let raw = match value {
+            SensorValue::Count(count) => return Ok(count),
+            SensorValue::Numeric(raw) => raw,
+            SensorValue::Binary(_) => {
+                return Err(SensorError::Source(
+                    "expected an egg count or weight, got a boolean".to_string(),
+                ))
+            }
+        };
+        let eggs = match self {
+            EggCounting::Count => raw,
+            EggCounting::Scale {
+                tare_kg,
+                egg_weight_g,
+            } => (raw - tare_kg) * 1000.0 / egg_weight_g,
+        };
+        Ok(eggs.round().max(0.0) as u32)
+    }
+
+    pub fn apply(&self, mut reading: Reading) -> Result<Reading, SensorError> {
+        reading.value = SensorValue::Count(self.count(reading.value)?);
+        Ok(reading)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{box_env_name, EggCounting};
+    use crate::sensors::SensorValue;
+
+    #[test]
+    fn counts_and_weights_become_egg_counts() {
+        assert_eq!(EggCounting::Count.count(SensorValue::Count(3)), Ok(3));
+        assert_eq!(EggCounting::Count.count(SensorValue::Numeric(2.0)), Ok(2));
+        assert!(EggCounting::Count.count(SensorValue::Binary(true)).is_err());
+
+        let scale = EggCounting::Scale {
+            tare_kg: 0.4,
+            egg_weight_g: 58.0,
+        };
+        // Three eggs of slightly different sizes still round to three.
+        assert_eq!(scale.count(SensorValue::Numeric(0.4 + 0.171)), Ok(3));
+        assert_eq!(scale.count(SensorValue::Numeric(0.39)), Ok(0));
+        assert_eq!(box_env_name("left-top"), "LEFT_TOP");
+
+        assert_eq!(
+            scale.with_overrides(Some(0.5), None),
+            Ok(EggCounting::Scale {
+                tare_kg: 0.5,
+                egg_weight_g: 58.0
+            })
+        );
+        assert_eq!(
+            EggCounting::Count.with_overrides(None, None),
+            Ok(EggCounting::Count)
+        );
+        assert!(scale.with_overrides(None, Some(0.0)).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use crate::util::{millis, parse_env, parse_optional_env};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::time::SystemTime;
+
+const FEEDER_CALIBRATION_FILE_DEFAULT: &str = "feeder-calibration.json";
+const FEEDER_DURATION_MS_DEFAULT: u64 = 2500;
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Measured dispensing rate of one feeder.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeederCalibration {
+    pub grams_per_second: f32,
+    pub measured_at_ms: u64,
+}
+
+impl FeederCalibration {
+    /// Rate from weighing what a run of `duration_ms` dispensed.
+    pub fn measure(duration_ms: u64, grams: f32, at: SystemTime) -> Result<Self, String> {
+        if duration_ms == 0 {
+            return Err("calibration run must last at least 1ms".to_string());
+        }
+        if !(grams.is_finite() && grams > 0.0) {
+            return Err(format!("dispensed grams must be positive, got {grams}"));
+        }
+        Ok(FeederCalibration {
+            grams_per_second: grams * 1000.0 / duration_ms as f32,
+            measured_at_ms: millis(at),
+        })
+    }
+
+    pub fn duration_ms(&self, grams: f32) -> u64 {
+        (grams / self.grams_per_second * 1000.0).round() as u64
+    }
+
+    pub fn grams(&self, duration_ms: u64) -> f32 {
+        self.grams_per_second * duration_ms as f32 / 1000.0
+    }
+}
+
+/// Calibrations keyed by feeder device key, from `FEEDER_CALIBRATION_FILE`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FeederCalibrations(pub BTreeMap<String, FeederCalibration>);
+
+impl FeederCalibrations {
+    pub fn path_from_env() -> String {
+        env::var("FEEDER_CALIBRATION_FILE")
+            .unwrap_or_else(|_| FEEDER_CALIBRATION_FILE_DEFAULT.to_string())
+    }
+
+    /// Loads `path`; a missing file means no feeder is calibrated yet.
+    pub fn load(path: &str) -> Result<FeederCalibrations, String> {
+        if !Path::new(path).exists() {
+            return Ok(FeederCalibrations::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read feeder calibration `{path}`: {e}"))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("invalid feeder calibration `{path}`: {e}"))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to encode feeder calibration: {e}"))?;
+        std::fs::write(path, content + "\n")
+            .map_err(|e| format!("failed to write feeder calibration `{path}`: {e}"))
+    }
+}
+
+/// Grams dispensed by one feeder on one ration day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailyDispense {
+    pub day: i64,
+    pub grams: f32,
+}
+
+impl DailyDispense {
+    pub fn grams_on(&self, day: i64) -> f32 {
+        if self.day == day {
+            self.grams
+        } else {
+            0.0
+        }
+    }
+
+    pub fn add(&mut self, day: i64, grams: f32) {
+        *self = DailyDispense {
+            day,
+            grams: self.grams_on(day) + grams,
+        };
+    }
+}
+
+/// A calibration run whose output has not been weighed yet.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PendingCalibration {
+    /// Ration day the run counted towards.
+    pub day: i64,
+    /// Grams the previous calibration predicted, already counted until the weight is known.
+    pub estimated_grams: Option<f32>,
+}
+
+/// Daily limit per feeder; days start at local midnight `FEEDER_UTC_OFFSET_HOURS` from UTC.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FeederRation {
+    pub daily_grams: Option<f32>,
+    pub utc_offset_hours: f32,
+}
+
+impl FeederRation {
+    /// Reads `FEEDER_DAILY_RATION_GRAMS` (unset means no limit) and `FEEDER_UTC_OFFSET_HOURS`.
+    pub fn from_env() -> Result<FeederRation, String> {
+        let daily_grams = parse_optional_env::<f32>("FEEDER_DAILY_RATION_GRAMS")?;
+        if let Some(grams) = daily_grams.filter(|grams| !(grams.is_finite() && *grams > 0.0)) {
+            return Err(format!(
+                "invalid value for FEEDER_DAILY_RATION_GRAMS: {grams}"
+            ));
+        }
+        let utc_offset_hours = parse_env::<f32>("FEEDER_UTC_OFFSET_HOURS", 0.0)?;
+        if utc_offset_hours.abs() > 14.0 {
+            return Err(format!(
+                "invalid value for FEEDER_UTC_OFFSET_HOURS: {utc_offset_hours}"
+            ));
+        }
+        Ok(FeederRation {
+            daily_grams,
+            utc_offset_hours,
+        })
+    }
+
+    pub fn day(&self, at: SystemTime) -> i64 {
+        let offset_ms = (self.utc_offset_hours * 3_600_000.0) as i64;
+        (millis(at) as i64 + offset_ms).div_euclid(MS_PER_DAY)
+    }
+}
+
+/// How long to run the feeder and, when calibrated, how much that dispenses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Portion {
+    pub duration_ms: u64,
+    pub grams: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortionError {
+    /// The request itself is malformed.
+    Invalid(String),
+    /// Grams were requested, or a ration applies, but the feeder has no calibration.
+    NotCalibrated(String),
+    /// The feeder's last calibration run has not been weighed yet.
+    CalibrationPending(String),
+    RationReached {
+        ration_grams: f32,
+        dispensed_grams: f32,
+        requested_grams: f32,
+    },
+}
+
+impl fmt::Display for PortionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortionError::Invalid(reason) => f.write_str(reason),
+            PortionError::NotCalibrated(device_key) => write!(
+                f,
+                "feeder `{device_key}` is not calibrated; run `coop feed calibrate`"
+            ),
+            PortionError::CalibrationPending(device_key) => write!(
+                f,
+                "feeder `{device_key}` has a calibration run waiting to be weighed; save it with \
+                 `coop feed calibrate --grams`"
+            ),
+            PortionError::RationReached {
+                ration_grams,
+                dispensed_grams,
+                requested_grams,
+            } => write!(
+                f,
+                "daily ration of {ration_grams:.0} g would be exceeded: {dispensed_grams:.0} g \
+                 dispensed today, {requested_grams:.0} g requested"
+            ),
+        }
+    }
+}
+
+/// Turns a request for `grams` or `duration_ms` (neither means the default run) into a portion,
+/// refusing it if it would take the feeder past today's ration.
+pub fn plan_portion(
+    device_key: &str,
+    duration_ms: Option<u64>,
+    grams: Option<f32>,
+    calibration: Option<&FeederCalibration>,
+    ration: &FeederRation,
+    dispensed_today: f32,
+) -> Result<Portion, PortionError> {
+    let portion = match (duration_ms, grams) {
+        (Some(_), Some(_)) => {
+            return Err(PortionError::Invalid(
+                "give either grams or duration_ms, not both".to_string(),
+            ))
+        }
+        (None, Some(grams)) => {
+            if !(grams.is_finite() && grams > 0.0) {
+                return Err(PortionError::Invalid(format!(
+                    "grams must be positive, got {grams}"
+                )));
+            }
+            let calibration =
+                calibration.ok_or_else(|| PortionError::NotCalibrated(device_key.to_string()))?;
+            Portion {
+                duration_ms: calibration.duration_ms(grams),
+                grams: Some(grams),
+            }
+        }
+        (duration_ms, None) => {
+            let duration_ms = duration_ms.unwrap_or(FEEDER_DURATION_MS_DEFAULT);
+            Portion {
+                duration_ms,
+                grams: calibration.map(|calibration| calibration.grams(duration_ms)),
+            }
+        }
+    };
+    if ration.daily_grams.is_some() && portion.grams.is_none() {
+        return Err(PortionError::NotCalibrated(device_key.to_string()));
+    }
+    check_ration(portion, ration, dispensed_today)
+}
+
+/// Plans a calibration run of `duration_ms`. Only one run per feeder may wait to be weighed,
+/// and a calibrated feeder's estimate must still fit into today's ration.
+pub fn plan_calibration_run(
+    device_key: &str,
+    duration_ms: u64,
+    calibration: Option<&FeederCalibration>,
+    ration: &FeederRation,
+    dispensed_today: f32,
+    pending: bool,
+) -> Result<Portion, PortionError> {
+    if pending {
+        return Err(PortionError::CalibrationPending(device_key.to_string()));
+    }
+    if duration_ms == 0 {
+        return Err(PortionError::Invalid(
+            "calibration run must last at least 1ms".to_string(),
+        ));
+    }
+    let portion = Portion {
+        duration_ms,
+        grams: calibration.map(|calibration| calibration.grams(duration_ms)),
+    };
+    check_ration(portion, ration, dispensed_today)
+}
+
+fn check_ration(
+    portion: Portion,
+    ration: &FeederRation,
+    dispensed_today: f32,
+) -> Result<Portion, PortionError> {
+    if let (Some(ration_grams), Some(requested_grams)) = (ration.daily_grams, portion.grams) {
+        if dispensed_today + requested_grams > ration_grams {
+            return Err(PortionError::RationReached {
+                ration_grams,
+                dispensed_grams: dispensed_today,
+                requested_grams,
+            });
+        }
+    }
+    Ok(portion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        plan_calibration_run, plan_portion, FeederCalibration, FeederRation, Portion, PortionError,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn grams_become_run_time_from_the_calibration() {
+        let calibration = FeederCalibration::measure(4000, 50.0, UNIX_EPOCH).expect("calibration");
+        assert_eq!(calibration.grams_per_second, 12.5);
+        assert!(FeederCalibration::measure(0, 50.0, UNIX_EPOCH).is_err());
+        assert!(FeederCalibration::measure(4000, -1.0, UNIX_EPOCH).is_err());
+
+        let unlimited = FeederRation::default();
+        assert_eq!(
+            plan_portion("F", None, Some(25.0), Some(&calibration), &unlimited, 0.0),
+            Ok(Portion {
+                duration_ms: 2000,
+                grams: Some(25.0)
+            })
+        );
+        assert_eq!(
+            plan_portion("F", None, None, None, &unlimited, 0.0),
+            Ok(Portion {
+                duration_ms: 2500,
+                grams: None
+            })
+        );
+        assert_eq!(
+            plan_portion("F", None, Some(25.0), None, &unlimited, 0.0),
+            Err(PortionError::NotCalibrated("F".to_string()))
+        );
+        assert!(matches!(
+            plan_portion(
+                "F",
+                Some(1000),
+                Some(25.0),
+                Some(&calibration),
+                &unlimited,
+                0.0
+            ),
+            Err(PortionError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn ration_caps_what_a_feeder_dispenses_per_day() {
+        let calibration = FeederCalibration::measure(1000, 10.0, UNIX_EPOCH).expect("calibration");
+        let ration = FeederRation {
+            daily_grams: Some(100.0),
+            utc_offset_hours: 2.0,
+        };
+        assert!(plan_portion("F", None, Some(40.0), Some(&calibration), &ration, 60.0).is_ok());
+        let err = plan_portion("F", Some(5000), None, Some(&calibration), &ration, 60.0)
+            .expect_err("over the ration");
+        assert_eq!(
+            err.to_string(),
+            "daily ration of 100 g would be exceeded: 60 g dispensed today, 50 g requested"
+        );
+        assert_eq!(
+            plan_portion("F", Some(1000), None, None, &ration, 0.0),
+            Err(PortionError::NotCalibrated("F".to_string()))
+        );
+
+        // Calibration runs count too, and only one may wait to be weighed.
+        assert_eq!(
+            plan_calibration_run("F", 3000, Some(&calibration), &ration, 60.0, false),
+            Ok(Portion {
+                duration_ms: 3000,
+                grams: Some(30.0)
+            })
+        );
+        assert!(matches!(
+            plan_calibration_run("F", 5000, Some(&calibration), &ration, 60.0, false),
+            Err(PortionError::RationReached { .. })
+        ));
+        assert!(plan_calibration_run("F", 5000, None, &ration, 60.0, false).is_ok());
+        assert_eq!(
+            plan_calibration_run("F", 1000, None, &ration, 0.0, true),
+            Err(PortionError::CalibrationPending("F".to_string()))
+        );
+
+        // 23:00 UTC is already the next day two hours east of UTC.
+        let late = UNIX_EPOCH + Duration::from_secs(23 * 3600);
+        assert_eq!(ration.day(late), 1);
+        assert_eq!(FeederRation::default().day(late), 0);
+    }
+}
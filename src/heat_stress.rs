@@ -0,0 +1,243 @@
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use std::env;
+use std::fmt;
+
+/// Default THI band edges (°F scale) for alert, danger and emergency; `THI_BANDS` overrides.
+const THI_BANDS_DEFAULT: [f32; 3] = [72.0, 78.0, 84.0];
+
+fn c_to_f(c: f32) -> f32 {
+    c * 9.0 / 5.0 + 32.0
+}
+
+fn f_to_c(f: f32) -> f32 {
+    (f - 32.0) * 5.0 / 9.0
+}
+
+/// Dew point in °C (Magnus formula).
+pub fn dew_point_c(temp_c: f32, humidity: f32) -> f32 {
+    let (a, b) = (17.62, 243.12);
+    let gamma = (humidity.clamp(1.0, 100.0) / 100.0).ln() + a * temp_c / (b + temp_c);
+    b * gamma / (a - gamma)
+}
+
+/// Wet-bulb temperature in °C (Stull 2011), good for 5-99% humidity.
+pub fn wet_bulb_c(temp_c: f32, humidity: f32) -> f32 {
+    let rh = humidity.clamp(5.0, 99.0);
+    temp_c * (0.151_977 * (rh + 8.313_659).sqrt()).atan() + (temp_c + rh).atan()
+        - (rh - 1.676_331).atan()
+        + 0.003_918_38 * rh.powf(1.5) * (0.023_101 * rh).atan()
+        - 4.686_035
+}
+
+/// NWS heat index in °C: the simple formula in mild weather, Rothfusz regression above 80°F.
+pub fn heat_index_c(temp_c: f32, humidity: f32) -> f32 {
+    let t = c_to_f(temp_c);
+    let rh = humidity.clamp(0.0, 100.0);
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    if (simple + t) / 2.0 < 80.0 {
+        return f_to_c(simple);
+    }
+    let mut hi = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+        - 0.224_755_4 * t * rh
+        - 0.006_837_83 * t * t
+        - 0.054_817_17 * rh * rh
+        + 0.001_228_74 * t * t * rh
+        + 0.000_852_82 * t * rh * rh
+        - 0.000_001_99 * t * t * rh * rh;
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        hi -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+    } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        hi += (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0);
+    }
+    f_to_c(hi)
+}
+
+/// Laying-hen THI (0.6 dry bulb + 0.4 wet bulb), on the °F scale the stress bands use.
+pub fn thi(temp_c: f32, humidity: f32) -> f32 {
+    c_to_f(0.6 * temp_c + 0.4 * wet_bulb_c(temp_c, humidity))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StressBand {
+    Comfortable,
+    Alert,
+    Danger,
+    Emergency,
+}
+
+impl fmt::Display for StressBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StressBand::Comfortable => "comfortable",
+            StressBand::Alert => "alert",
+            StressBand::Danger => "danger",
+            StressBand::Emergency => "emergency",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// THI values at which the alert, danger and emergency bands start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThiBands {
+    pub alert: f32,
+    pub danger: f32,
+    pub emergency: f32,
+}
+
+impl Default for ThiBands {
+    fn default() -> Self {
+        let [alert, danger, emergency] = THI_BANDS_DEFAULT;
+        ThiBands {
+            alert,
+            danger,
+            emergency,
+        }
+    }
+}
+
+impl ThiBands {
+    /// Reads `THI_BANDS` as three ascending values, e.g. `72,78,84`.
+    pub fn from_env() -> Result<ThiBands, String> {
+        let Ok(value) = env::var("THI_BANDS") else {
+            return Ok(ThiBands::default());
+        };
+        let edges: Vec<f32> = value
+            .split(',')
+            .map(|edge| edge.trim().parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid value for THI_BANDS: {value}"))?;
+        match edges[..] {
+            [alert, danger, emergency] if alert < danger && danger < emergency => Ok(ThiBands {
+                alert,
+                danger,
+                emergency,
+            }),
+            _ => Err(format!(
+                "THI_BANDS needs three ascending values (alert,danger,emergency), got {value}"
+            )),
+        }
+    }
+
+    pub fn band(&self, thi: f32) -> StressBand {
+        if thi >= self.emergency {
+            StressBand::Emergency
+        } else if thi >= self.danger {
+            StressBand::Danger
+        } else if thi >= self.alert {
+            StressBand::Alert
+        } else {
+            StressBand::Comfortable
+        }
+    }
+}
+
+/// Computes a derived `kind` from temperature and humidity readings, stamped with the older
+/// capture time of the two.
+pub fn derive(
+    kind: SensorKind,
+    temperature: &Reading,
+    humidity: &Reading,
+) -> Result<Reading, SensorError> {
+    let (SensorValue::Numeric(temp_c), SensorValue::Numeric(rh)) =
+        (temperature.value, humidity.value)
+    else {
+        return Err(SensorError::Source(format!(
+            "{} needs numeric temperature and humidity",
+            kind.as_str()
+        )));
+    };
+    let value = match kind {
+        SensorKind::Thi => thi(temp_c, rh),
+        SensorKind::HeatIndex => heat_index_c(temp_c, rh),
+        SensorKind::DewPoint => dew_point_c(temp_c, rh),
+        _ => return Err(SensorError::Unsupported(kind)),
+    };
+    Ok(Reading::new(
+        kind.as_str(),
+        kind,
+        SensorValue::Numeric(value),
+        temperature.captured_at.min(humidity.captured_at),
+    ))
+}
+
+/// [`derive`] from the results of reading temperature and humidity, passing on the first error.
+pub fn derive_reading(
+    kind: SensorKind,
+    temperature: &Result<Reading, SensorError>,
+    humidity: &Result<Reading, SensorError>,
+) -> Result<Reading, SensorError> {
+    match (temperature, humidity) {
+        (Ok(temperature), Ok(humidity)) => derive(kind, temperature, humidity),
+        (Err(err), _) | (_, Err(err)) => Err(err.clone()),
+    }
+}
+
+/// Samples `kind`, falling back to computing derived kinds from the source's temperature and
+/// humidity when it has no direct sensor for them.
+pub async fn sample_or_derive(
+    source: &dyn SensorSource,
+    kind: SensorKind,
+) -> Result<Reading, SensorError> {
+    match source.sample(kind).await {
+        Err(SensorError::Unsupported(_)) if kind.is_derived() => {
+            let (temperature, humidity) = tokio::join!(
+                source.sample(SensorKind::Temperature),
+                source.sample(SensorKind::Humidity)
+            );
+            derive(kind, &temperature?, &humidity?)
+        }
+        reading => reading,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derive, dew_point_c, heat_index_c, thi, StressBand, ThiBands};
+    use crate::sensors::{Reading, SensorKind, SensorValue};
+    use std::time::{Duration, SystemTime};
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn formulas_match_reference_values() {
+        assert_close(dew_point_c(25.0, 60.0), 16.7, 0.2);
+        assert_close(dew_point_c(10.0, 100.0), 10.0, 0.01);
+        // NWS table: 90°F at 60% feels like 100°F; mild weather is left near the air temperature.
+        assert_close(heat_index_c(32.22, 60.0), 37.8, 0.4);
+        assert_close(heat_index_c(20.0, 50.0), 19.7, 0.5);
+
+        let bands = ThiBands::default();
+        assert_eq!(bands.band(thi(18.0, 60.0)), StressBand::Comfortable);
+        assert_eq!(bands.band(thi(27.0, 60.0)), StressBand::Alert);
+        assert_eq!(bands.band(thi(30.0, 50.0)), StressBand::Danger);
+        assert_eq!(bands.band(thi(35.0, 60.0)), StressBand::Emergency);
+    }
+
+    #[test]
+    fn derived_readings_take_the_older_capture_time() {
+        let now = SystemTime::now();
+        let temperature = Reading::new(
+            "temperature",
+            SensorKind::Temperature,
+            SensorValue::Numeric(30.0),
+            now,
+        );
+        let humidity = Reading::new(
+            "humidity",
+            SensorKind::Humidity,
+            SensorValue::Numeric(50.0),
+            now - Duration::from_secs(60),
+        );
+        let derived = derive(SensorKind::DewPoint, &temperature, &humidity).expect("dew point");
+        assert_eq!(derived.captured_at, humidity.captured_at);
+        assert_eq!(derived.unit, SensorKind::DewPoint.unit());
+        assert!(derive(SensorKind::Motion, &temperature, &humidity).is_err());
+    }
+}
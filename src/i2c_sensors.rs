@@ -0,0 +1,491 @@
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+const BME280_CHIP_ID: u8 = 0x60;
+const BME280_REG_CHIP_ID: u8 = 0xD0;
+const BME280_REG_CALIB_TP: u8 = 0x88;
+const BME280_REG_CALIB_H: u8 = 0xE1;
+const BME280_REG_CTRL_HUM: u8 = 0xF2;
+const BME280_REG_CTRL_MEAS: u8 = 0xF4;
+const BME280_REG_DATA: u8 = 0xF7;
+/// Temperature and pressure oversampling x1, forced mode.
+const BME280_CTRL_MEAS_FORCED: u8 = 0x25;
+const BME280_MEASURE_MS: u64 = 10;
+
+/// Single-shot, high repeatability, no clock stretching.
+const SHT31_CMD_MEASURE: [u8; 2] = [0x24, 0x00];
+const SHT31_MEASURE_MS: u64 = 16;
+
+/// Minimal I2C access needed by the drivers, so they can run against a mock bus.
+pub trait I2cBus: Send {
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), String>;
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), String>;
+    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvMeasurement {
+    pub temperature_c: f32,
+    pub humidity_pct: f32,
+    pub pressure_hpa: Option<f32>,
+}
+
+pub trait EnvironmentalDriver: Send {
+    fn measure(&mut self) -> Result<EnvMeasurement, SensorError>;
+}
+
+fn bus_error(err: String) -> SensorError {
+    SensorError::Source(format!("i2c: {err}"))
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> f64 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]]) as f64
+}
+
+fn le_i16(bytes: &[u8], at: usize) -> f64 {
+    i16::from_le_bytes([bytes[at], bytes[at + 1]]) as f64
+}
+
+/// Factory trimming values from registers 0x88..0xA1 and 0xE1..0xE7.
+#[derive(Debug, Clone, PartialEq)]
+struct Bme280Calibration {
+    t: [f64; 3],
+    p: [f64; 9],
+    h: [f64; 6],
+}
+
+impl Bme280Calibration {
+    fn parse(tp: &[u8; 26], h: &[u8; 7]) -> Self {
+        let h4 = ((h[3] as i8 as i16) << 4) | (h[4] & 0x0F) as i16;
+        let h5 = ((h[5] as i8 as i16) << 4) | (h[4] >> 4) as i16;
+        Bme280Calibration {
+            t: [le_u16(tp, 0), le_i16(tp, 2), le_i16(tp, 4)],
+            p: [
+                le_u16(tp, 6),
+                le_i16(tp, 8),
+                le_i16(tp, 10),
+                le_i16(tp, 12),
+                le_i16(tp, 14),
+                le_i16(tp, 16),
+                le_i16(tp, 18),
+                le_i16(tp, 20),
+                le_i16(tp, 22),
+            ],
+            h: [
+                tp[25] as f64,
+                le_i16(h, 0),
+                h[2] as f64,
+                h4 as f64,
+                h5 as f64,
+                h[6] as i8 as f64,
+            ],
+        }
+    }
+
+    /// Floating-point compensation from the BME280 datasheet (section 8.1).
+    fn compensate(&self, adc_t: u32, adc_p: u32, adc_h: u32) -> EnvMeasurement {
+        let [t1, t2, t3] = self.t;
+        let (adc_t, adc_p, adc_h) = (adc_t as f64, adc_p as f64, adc_h as f64);
+
+        let var1 = (adc_t / 16384.0 - t1 / 1024.0) * t2;
+        let var2 = (adc_t / 131072.0 - t1 / 8192.0).powi(2) * t3;
+        let t_fine = var1 + var2;
+        let temperature = t_fine / 5120.0;
+
+        let [p1, p2, p3, p4, p5, p6, p7, p8, p9] = self.p;
+        let mut var1 = t_fine / 2.0 - 64000.0;
+        let mut var2 = var1 * var1 * p6 / 32768.0;
+        var2 += var1 * p5 * 2.0;
+        var2 = var2 / 4.0 + p4 * 65536.0;
+        var1 = (p3 * var1 * var1 / 524288.0 + p2 * var1) / 524288.0;
+        var1 = (1.0 + var1 / 32768.0) * p1;
+        let pressure_pa = if var1 == 0.0 {
+            0.0
+        } else {
+            let mut p = 1048576.0 - adc_p;
+            p = (p - var2 / 4096.0) * 6250.0 / var1;
+            let var1 = p9 * p * p / 2147483648.0;
+            let var2 = p * p8 / 32768.0;
+            p + (var1 + var2 + p7) / 16.0
+        };
+
+        let [h1, h2, h3, h4, h5, h6] = self.h;
+        let mut humidity = t_fine - 76800.0;
+        humidity = (adc_h - (h4 * 64.0 + h5 / 16384.0 * humidity))
+            * (h2 / 65536.0
+                * (1.0 + h6 / 67108864.0 * humidity * (1.0 + h3 / 67108864.0 * humidity)));
+        humidity *= 1.0 - h1 * humidity / 524288.0;
+
+        EnvMeasurement {
+            temperature_c: temperature as f32,
+            humidity_pct: humidity.clamp(0.0, 100.0) as f32,
+            pressure_hpa: Some((pressure_pa / 100.0) as f32),
+        }
+    }
+}
+
+/// Bosch BME280 temperature, humidity and pressure sensor in forced mode.
+pub struct Bme280<B: I2cBus> {
+    bus: B,
+    addr: u8,
+    calibration: Option<Bme280Calibration>,
+}
+
+impl<B: I2cBus> Bme280<B> {
+    pub fn new(bus: B, addr: u8) -> Self {
+        Bme280 {
+            bus,
+            addr,
+            calibration: None,
+        }
+    }
+
+    fn read_register(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), SensorError> {
+        self.bus
+            .write_read(self.addr, &[reg], buffer)
+            .map_err(bus_error)
+    }
+
+    fn calibration(&mut self) -> Result<Bme280Calibration, SensorError> {
+        if let Some(calibration) = &self.calibration {
+            return Ok(calibration.clone());
+        }
+        let mut chip_id = [0_u8; 1];
+        self.read_register(BME280_REG_CHIP_ID, &mut chip_id)?;
+        if chip_id[0] != BME280_CHIP_ID {
+            return Err(SensorError::Source(format!(
+                "unexpected BME280 chip id 0x{:02x}",
+                chip_id[0]
+            )));
+        }
+        let mut tp = [0_u8; 26];
+        self.read_register(BME280_REG_CALIB_TP, &mut tp)?;
+        let mut h = [0_u8; 7];
+        self.read_register(BME280_REG_CALIB_H, &mut h)?;
+        let calibration = Bme280Calibration::parse(&tp, &h);
+        self.calibration = Some(calibration.clone());
+        Ok(calibration)
+    }
+}
+
+impl<B: I2cBus> EnvironmentalDriver for Bme280<B> {
+    fn measure(&mut self) -> Result<EnvMeasurement, SensorError> {
+        let calibration = self.calibration()?;
+        self.bus
+            .write(self.addr, &[BME280_REG_CTRL_HUM, 0x01])
+            .map_err(bus_error)?;
+        self.bus
+            .write(self.addr, &[BME280_REG_CTRL_MEAS, BME280_CTRL_MEAS_FORCED])
+            .map_err(bus_error)?;
+        sleep(Duration::from_millis(BME280_MEASURE_MS));
+
+        let mut data = [0_u8; 8];
+        self.read_register(BME280_REG_DATA, &mut data)?;
+        let adc_p = ((data[0] as u32) << 12) | ((data[1] as u32) << 4) | ((data[2] as u32) >> 4);
+        let adc_t = ((data[3] as u32) << 12) | ((data[4] as u32) << 4) | ((data[5] as u32) >> 4);
+        let adc_h = ((data[6] as u32) << 8) | data[7] as u32;
+        Ok(calibration.compensate(adc_t, adc_p, adc_h))
+    }
+}
+
+/// Sensirion CRC-8: polynomial 0x31, initial value 0xFF.
+fn sht31_crc(bytes: &[u8]) -> u8 {
+    let mut crc = 0xFF_u8;
+    for byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Sensirion SHT31 temperature and humidity sensor.
+pub struct Sht31<B: I2cBus> {
+    bus: B,
+    addr: u8,
+}
+
+impl<B: I2cBus> Sht31<B> {
+    pub fn new(bus: B, addr: u8) -> Self {
+        Sht31 { bus, addr }
+    }
+}
+
+impl<B: I2cBus> EnvironmentalDriver for Sht31<B> {
+    fn measure(&mut self) -> Result<EnvMeasurement, SensorError> {
+        self.bus
+            .write(self.addr, &SHT31_CMD_MEASURE)
+            .map_err(bus_error)?;
+        sleep(Duration::from_millis(SHT31_MEASURE_MS));
+
+        let mut data = [0_u8; 6];
+        self.bus.read(self.addr, &mut data).map_err(bus_error)?;
+        if sht31_crc(&data[0..2]) != data[2] || sht31_crc(&data[3..5]) != data[5] {
+            return Err(SensorError::Source("sht31 crc check failed".to_string()));
+        }
+        let raw_t = u16::from_be_bytes([data[0], data[1]]) as f32;
+        let raw_h = u16::from_be_bytes([data[3], data[4]]) as f32;
+        Ok(EnvMeasurement {
+            temperature_c: -45.0 + 175.0 * raw_t / 65535.0,
+            humidity_pct: 100.0 * raw_h / 65535.0,
+            pressure_hpa: None,
+        })
+    }
+}
+
+/// Serves temperature, humidity and (when available) pressure from one I2C sensor.
+pub struct I2cEnvSource {
+    driver: Arc<Mutex<Box<dyn EnvironmentalDriver>>>,
+}
+
+impl I2cEnvSource {
+    pub fn new(driver: Box<dyn EnvironmentalDriver>) -> Self {
+        I2cEnvSource {
+            driver: Arc::new(Mutex::new(driver)),
+        }
+    }
+}
+
+#[async_trait]
+impl SensorSource for I2cEnvSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        if !matches!(
+            kind,
+            SensorKind::Temperature | SensorKind::Humidity | SensorKind::Pressure
+        ) {
+            return Err(SensorError::Unsupported(kind));
+        }
+        // A measurement sleeps through the sensor's conversion time, so it runs on the blocking
+        // pool instead of stalling the runtime.
+        let driver = Arc::clone(&self.driver);
+        let measurement = tokio::task::spawn_blocking(move || {
+            driver
+                .lock()
+                .map_err(|_| SensorError::Source("i2c driver lock poisoned".to_string()))?
+                .measure()
+        })
+        .await
+        .map_err(|e| SensorError::Source(format!("i2c driver task failed: {e}")))??;
+        let value = match kind {
+            SensorKind::Temperature => measurement.temperature_c,
+            SensorKind::Humidity => measurement.humidity_pct,
+            _ => measurement
+                .pressure_hpa
+                .ok_or(SensorError::Unsupported(kind))?,
+        };
+        Ok(Reading::new(
+            kind.as_str(),
+            kind,
+            SensorValue::Numeric(value),
+            SystemTime::now(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) mod rpi_i2c {
+    use super::I2cBus;
+    use rppal::i2c::I2c;
+
+    pub struct RppalI2cBus {
+        i2c: I2c,
+    }
+
+    impl RppalI2cBus {
+        pub fn new(bus: u8) -> Result<Self, String> {
+            let i2c = I2c::with_bus(bus).map_err(|e| format!("i2c bus {bus} unavailable: {e}"))?;
+            Ok(Self { i2c })
+        }
+
+        fn select(&mut self, addr: u8) -> Result<(), String> {
+            self.i2c
+                .set_slave_address(addr as u16)
+                .map_err(|e| format!("i2c address 0x{addr:02x} unavailable: {e}"))
+        }
+    }
+
+    impl I2cBus for RppalI2cBus {
+        fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), String> {
+            self.select(addr)?;
+            self.i2c.write(bytes).map(|_| ()).map_err(|e| e.to_string())
+        }
+
+        fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), String> {
+            self.select(addr)?;
+            self.i2c.read(buffer).map(|_| ()).map_err(|e| e.to_string())
+        }
+
+        fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), String> {
+            self.select(addr)?;
+            self.i2c
+                .write_read(bytes, buffer)
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn parse_addr_env(name: &str, default: u8) -> Result<u8, String> {
+    match std::env::var(name) {
+        Ok(value) => u8::from_str_radix(value.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("invalid value for {name}: {value}")),
+        Err(_) => Ok(default),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn i2c_bus_from_env() -> Result<rpi_i2c::RppalI2cBus, String> {
+    rpi_i2c::RppalI2cBus::new(crate::util::parse_env::<u8>("I2C_BUS", 1)?)
+}
+
+#[cfg(target_os = "linux")]
+pub fn bme280_source_from_env() -> Result<I2cEnvSource, String> {
+    let addr = parse_addr_env("BME280_ADDR", 0x76)?;
+    let driver = Bme280::new(i2c_bus_from_env()?, addr);
+    Ok(I2cEnvSource::new(Box::new(driver)))
+}
+
+#[cfg(target_os = "linux")]
+pub fn sht31_source_from_env() -> Result<I2cEnvSource, String> {
+    let addr = parse_addr_env("SHT31_ADDR", 0x44)?;
+    let driver = Sht31::new(i2c_bus_from_env()?, addr);
+    Ok(I2cEnvSource::new(Box::new(driver)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sht31_crc, Bme280, EnvironmentalDriver, I2cBus, I2cEnvSource, Sht31};
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+
+    /// Replays register contents for `write_read` and queued frames for plain reads.
+    #[derive(Default)]
+    struct MockBus {
+        registers: HashMap<u8, Vec<u8>>,
+        frames: VecDeque<Vec<u8>>,
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl I2cBus for MockBus {
+        fn write(&mut self, _addr: u8, bytes: &[u8]) -> Result<(), String> {
+            self.writes.lock().expect("writes").push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, _addr: u8, buffer: &mut [u8]) -> Result<(), String> {
+            let frame = self.frames.pop_front().ok_or("no frame queued")?;
+            buffer.copy_from_slice(&frame[..buffer.len()]);
+            Ok(())
+        }
+
+        fn write_read(&mut self, _addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), String> {
+            let data = self
+                .registers
+                .get(&bytes[0])
+                .ok_or_else(|| format!("unexpected register 0x{:02x}", bytes[0]))?;
+            buffer.copy_from_slice(&data[..buffer.len()]);
+            Ok(())
+        }
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).expect("hex"))
+            .collect()
+    }
+
+    /// Datasheet example trimming (T/P) with typical humidity trimming.
+    fn bme280_bus() -> MockBus {
+        MockBus {
+            registers: HashMap::from([
+                (0xD0, vec![0x60]),
+                (
+                    0x88,
+                    hex("706b436718fc7d8e43d6d00b270b8c00f9ff8c3cf8c67017004b"),
+                ),
+                (0xE1, hex("6a01001329031e")),
+                (0xF7, hex("655ac07eed007530")),
+            ]),
+            ..MockBus::default()
+        }
+    }
+
+    #[test]
+    fn bme280_applies_datasheet_compensation() {
+        let bus = bme280_bus();
+        let writes = Arc::clone(&bus.writes);
+        let mut bme280 = Bme280::new(bus, 0x76);
+
+        let measurement = bme280.measure().expect("measurement");
+        assert!((measurement.temperature_c - 25.08).abs() < 0.01);
+        assert!((measurement.pressure_hpa.expect("pressure") - 1006.53).abs() < 0.01);
+        assert!((measurement.humidity_pct - 55.0).abs() < 0.01);
+        assert_eq!(
+            *writes.lock().expect("writes"),
+            vec![vec![0xF2, 0x01], vec![0xF4, 0x25]]
+        );
+    }
+
+    #[test]
+    fn bme280_rejects_unknown_chip() {
+        let mut bus = bme280_bus();
+        bus.registers.insert(0xD0, vec![0x58]);
+        assert!(matches!(
+            Bme280::new(bus, 0x76).measure(),
+            Err(SensorError::Source(_))
+        ));
+    }
+
+    #[test]
+    fn sht31_validates_crc_and_converts() {
+        assert_eq!(sht31_crc(&[0xBE, 0xEF]), 0x92);
+
+        let bus = MockBus {
+            frames: VecDeque::from([
+                vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2],
+                vec![0x66, 0x66, 0x00, 0x80, 0x00, 0xA2],
+            ]),
+            ..MockBus::default()
+        };
+        let mut sht31 = Sht31::new(bus, 0x44);
+
+        let measurement = sht31.measure().expect("measurement");
+        assert!((measurement.temperature_c - 25.0).abs() < 0.01);
+        assert!((measurement.humidity_pct - 50.0).abs() < 0.01);
+        assert_eq!(measurement.pressure_hpa, None);
+        assert!(matches!(sht31.measure(), Err(SensorError::Source(_))));
+    }
+
+    #[tokio::test]
+    async fn i2c_source_exposes_pressure_when_available() {
+        let source = I2cEnvSource::new(Box::new(Bme280::new(bme280_bus(), 0x76)));
+        let pressure = source.sample(SensorKind::Pressure).await.expect("pressure");
+        assert!(matches!(pressure.value, SensorValue::Numeric(v) if (v - 1006.53).abs() < 0.01));
+        assert_eq!(
+            source.sample(SensorKind::Motion).await,
+            Err(SensorError::Unsupported(SensorKind::Motion))
+        );
+
+        let bus = MockBus {
+            frames: VecDeque::from([vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]]),
+            ..MockBus::default()
+        };
+        let source = I2cEnvSource::new(Box::new(Sht31::new(bus, 0x44)));
+        assert_eq!(
+            source.sample(SensorKind::Pressure).await,
+            Err(SensorError::Unsupported(SensorKind::Pressure))
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use crate::actuators::ActuatorDriver;
+use crate::door::{DoorDirection, DoorState};
+use crate::util::parse_env;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+const FEEDER_MAX_PULSE_MS_DEFAULT: u64 = 10_000;
+const ACTUATOR_MIN_INTERVAL_MS_DEFAULT: u64 = 1_000;
+const DOOR_REVERSAL_COOLDOWN_MS_DEFAULT: u64 = 10_000;
+const ACTUATOR_DAILY_BUDGET_DEFAULT: u32 = 50;
+const BUDGET_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterlockLimits {
+    pub max_feeder_pulse: Duration,
+    /// Least time between two runs of the same command, e.g. feeder after feeder.
+    pub min_interval: Duration,
+    /// Least time before the door may move the other way.
+    pub door_reversal_cooldown: Duration,
+    /// Runs allowed per actuator in any 24 hours; `None` is unlimited.
+    pub daily_budget: Option<u32>,
+}
+
+impl Default for InterlockLimits {
+    fn default() -> Self {
+        InterlockLimits {
+            max_feeder_pulse: Duration::from_millis(FEEDER_MAX_PULSE_MS_DEFAULT),
+            min_interval: Duration::from_millis(ACTUATOR_MIN_INTERVAL_MS_DEFAULT),
+            door_reversal_cooldown: Duration::from_millis(DOOR_REVERSAL_COOLDOWN_MS_DEFAULT),
+            daily_budget: Some(ACTUATOR_DAILY_BUDGET_DEFAULT),
+        }
+    }
+}
+
+impl InterlockLimits {
+    /// Reads `FEEDER_MAX_PULSE_MS`, `ACTUATOR_MIN_INTERVAL_MS`, `DOOR_REVERSAL_COOLDOWN_MS` and
+    /// `ACTUATOR_DAILY_BUDGET` (`0` disables the budget).
+    pub fn from_env() -> Result<InterlockLimits, String> {
+        let daily_budget = parse_env::<u64>(
+            "ACTUATOR_DAILY_BUDGET",
+            ACTUATOR_DAILY_BUDGET_DEFAULT.into(),
+        )?;
+        Ok(InterlockLimits {
+            max_feeder_pulse: Duration::from_millis(parse_env::<u64>(
+                "FEEDER_MAX_PULSE_MS",
+                FEEDER_MAX_PULSE_MS_DEFAULT,
+            )?),
+            min_interval: Duration::from_millis(parse_env::<u64>(
+                "ACTUATOR_MIN_INTERVAL_MS",
+                ACTUATOR_MIN_INTERVAL_MS_DEFAULT,
+            )?),
+            door_reversal_cooldown: Duration::from_millis(parse_env::<u64>(
+                "DOOR_REVERSAL_COOLDOWN_MS",
+                DOOR_REVERSAL_COOLDOWN_MS_DEFAULT,
+            )?),
+            daily_budget: match daily_budget {
+                0 => None,
+                budget => Some(budget.min(u32::MAX.into()) as u32),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actuator {
+    Feeder,
+    Door,
+}
+
+impl fmt::Display for Actuator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Actuator::Feeder => "feeder",
+            Actuator::Door => "door",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interlock {
+    MaxPulse { requested: Duration, max: Duration },
+    MinInterval { actuator: Actuator, wait: Duration },
+    ReversalCooldown { wait: Duration },
+    DailyBudget { actuator: Actuator, budget: u32 },
+}
+
+impl Interlock {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interlock::MaxPulse { .. } => "max_pulse",
+            Interlock::MinInterval { .. } => "min_interval",
+            Interlock::ReversalCooldown { .. } => "reversal_cooldown",
+            Interlock::DailyBudget { .. } => "daily_budget",
+        }
+    }
+
+    /// Whether the same command succeeds after waiting; otherwise the request itself is refused.
+    pub fn is_rate_limit(&self) -> bool {
+        !matches!(self, Interlock::MaxPulse { .. })
+    }
+}
+
+impl fmt::Display for Interlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "blocked by interlock `{}`: ", self.name())?;
+        match self {
+            Interlock::MaxPulse { requested, max } => write!(
+                f,
+                "feeder pulse of {}ms exceeds the {}ms limit",
+                requested.as_millis(),
+                max.as_millis()
+            ),
+            Interlock::MinInterval { actuator, wait } => write!(
+                f,
+                "{actuator} ran too recently; retry in {:.1}s",
+                wait.as_secs_f32()
+            ),
+            Interlock::ReversalCooldown { wait } => write!(
+                f,
+                "door cannot reverse yet; retry in {:.1}s",
+                wait.as_secs_f32()
+            ),
+            Interlock::DailyBudget { actuator, budget } => {
+                write!(f, "{actuator} used its {budget} runs for the last 24h")
+            }
+        }
+    }
+}
+
+/// Why a command through [`InterlockedDriver`] did not complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Blocked(Interlock),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Feed,
+    Door(DoorDirection),
+}
+
+impl Command {
+    fn actuator(self) -> Actuator {
+        match self {
+            Command::Feed => Actuator::Feeder,
+            Command::Door(_) => Actuator::Door,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    /// The last command and when it finished.
+    last: Option<(Command, Instant)>,
+    /// Start times within the budget window.
+    runs: VecDeque<Instant>,
+}
+
+/// Any [`ActuatorDriver`] behind the safety interlocks; the limits are kept in memory, so a
+/// restart resets the intervals and budgets.
+pub struct InterlockedDriver {
+    inner: Box<dyn ActuatorDriver>,
+    limits: InterlockLimits,
+    feeder: Usage,
+    door: Usage,
+}
+
+impl InterlockedDriver {
+    pub fn new(inner: Box<dyn ActuatorDriver>, limits: InterlockLimits) -> Self {
+        InterlockedDriver {
+            inner,
+            limits,
+            feeder: Usage::default(),
+            door: Usage::default(),
+        }
+    }
+
+    pub fn feeder_activate(
+        &mut self,
+        device_key: &str,
+        duration_ms: u64,
+    ) -> Result<(), CommandError> {
+        let requested = Duration::from_millis(duration_ms);
+        if requested > self.limits.max_feeder_pulse {
+            return Err(CommandError::Blocked(Interlock::MaxPulse {
+                requested,
+                max: self.limits.max_feeder_pulse,
+            }));
+        }
+        self.run(Command::Feed, |driver| {
+            driver.feeder_activate(device_key, duration_ms)
+        })
+    }
+
+    pub fn door_open(&mut self, device_key: &str) -> Result<(), CommandError> {
+        self.run(Command::Door(DoorDirection::Open), |driver| {
+            driver.door_open(device_key)
+        })
+    }
+
+    pub fn door_close(&mut self, device_key: &str) -> Result<(), CommandError> {
+        self.run(Command::Door(DoorDirection::Close), |driver| {
+            driver.door_close(device_key)
+        })
+    }
+
+    /// Whether a door move would pass the interlocks right now, without moving it.
+    pub fn check_door(&mut self, direction: DoorDirection) -> Result<(), Interlock> {
+        self.admit(Command::Door(direction), Instant::now())
+    }
+
+    pub fn door_state(&self) -> DoorState {
+        self.inner.door_state()
+    }
+
+    pub fn restore_door_state(&mut self, state: DoorState) {
+        self.inner.restore_door_state(state);
+    }
+
+    fn usage(&mut self, actuator: Actuator) -> &mut Usage {
+        match actuator {
+            Actuator::Feeder => &mut self.feeder,
+            Actuator::Door => &mut self.door,
+        }
+    }
+
+    fn run(
+        &mut self,
+        command: Command,
+        send: impl FnOnce(&mut dyn ActuatorDriver) -> Result<(), String>,
+    ) -> Result<(), CommandError> {
+        let started = Instant::now();
+        self.admit(command, started)
+            .map_err(CommandError::Blocked)?;
+        self.usage(command.actuator()).runs.push_back(started);
+        // A failed command may still have moved the motor, so it counts like a successful one.
+        let result = send(self.inner.as_mut());
+        self.usage(command.actuator()).last = Some((command, Instant::now()));
+        result.map_err(CommandError::Failed)
+    }
+
+    fn admit(&mut self, command: Command, now: Instant) -> Result<(), Interlock> {
+        let limits = self.limits;
+        let actuator = command.actuator();
+        let usage = self.usage(actuator);
+        while usage
+            .runs
+            .front()
+            .is_some_and(|run| now.duration_since(*run) >= BUDGET_WINDOW)
+        {
+            usage.runs.pop_front();
+        }
+        if let Some(budget) = limits.daily_budget {
+            if usage.runs.len() >= budget as usize {
+                return Err(Interlock::DailyBudget { actuator, budget });
+            }
+        }
+        let Some((last, finished)) = usage.last else {
+            return Ok(());
+        };
+        let since = now.saturating_duration_since(finished);
+        let wait = |cooldown: Duration| cooldown.checked_sub(since).filter(|wait| !wait.is_zero());
+        if last == command {
+            if let Some(wait) = wait(limits.min_interval) {
+                return Err(Interlock::MinInterval { actuator, wait });
+            }
+        } else if let Some(wait) = wait(limits.door_reversal_cooldown) {
+            // Only the door has two commands, so a different one on the same actuator reverses it.
+            return Err(Interlock::ReversalCooldown { wait });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Actuator, CommandError, Interlock, InterlockLimits, InterlockedDriver};
+    use crate::actuators::ActuatorDriver;
+    use crate::door::DoorState;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct RecordingDriver {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ActuatorDriver for RecordingDriver {
+        fn feeder_activate(&mut self, _device_key: &str, duration_ms: u64) -> Result<(), String> {
+            self.calls
+                .lock()
+                .expect("calls")
+                .push(format!("feed {duration_ms}"));
+            Ok(())
+        }
+
+        fn door_open(&mut self, _device_key: &str) -> Result<(), String> {
+            self.calls.lock().expect("calls").push("open".to_string());
+            Ok(())
+        }
+
+        fn door_close(&mut self, _device_key: &str) -> Result<(), String> {
+            self.calls.lock().expect("calls").push("close".to_string());
+            Err("door jammed".to_string())
+        }
+
+        fn door_state(&self) -> DoorState {
+            DoorState::Unknown
+        }
+
+        fn restore_door_state(&mut self, _state: DoorState) {}
+    }
+
+    fn driver(limits: InterlockLimits) -> (InterlockedDriver, Arc<Mutex<Vec<String>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let inner = RecordingDriver {
+            calls: Arc::clone(&calls),
+        };
+        (InterlockedDriver::new(Box::new(inner), limits), calls)
+    }
+
+    fn blocked(result: Result<(), CommandError>) -> Interlock {
+        match result {
+            Err(CommandError::Blocked(interlock)) => interlock,
+            other => panic!("expected an interlock, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn long_pulses_and_rapid_repeats_are_blocked() {
+        let (mut driver, calls) = driver(InterlockLimits {
+            min_interval: Duration::from_millis(30),
+            ..InterlockLimits::default()
+        });
+
+        let too_long = blocked(driver.feeder_activate("F", 600_000));
+        assert_eq!(
+            too_long.to_string(),
+            "blocked by interlock `max_pulse`: feeder pulse of 600000ms exceeds the 10000ms limit"
+        );
+        assert!(!too_long.is_rate_limit());
+
+        driver.feeder_activate("F", 2500).expect("first run");
+        let repeat = blocked(driver.feeder_activate("F", 2500));
+        assert_eq!(repeat.name(), "min_interval");
+        assert!(repeat.is_rate_limit());
+        // Other commands are not held back by the feeder's interval.
+        driver.door_open("D").expect("door");
+
+        std::thread::sleep(Duration::from_millis(40));
+        driver
+            .feeder_activate("F", 2500)
+            .expect("after the interval");
+        assert_eq!(
+            *calls.lock().expect("calls"),
+            vec!["feed 2500", "open", "feed 2500"]
+        );
+    }
+
+    #[test]
+    fn door_reversal_cooldown_and_daily_budget() {
+        let (mut driver, calls) = driver(InterlockLimits {
+            min_interval: Duration::ZERO,
+            door_reversal_cooldown: Duration::from_secs(60),
+            daily_budget: Some(2),
+            ..InterlockLimits::default()
+        });
+
+        driver.door_open("D").expect("open");
+        let reversal = blocked(driver.door_close("D"));
+        assert_eq!(reversal.name(), "reversal_cooldown");
+        assert!(reversal.is_rate_limit());
+        driver.door_open("D").expect("same direction again");
+        assert_eq!(
+            blocked(driver.door_open("D")),
+            Interlock::DailyBudget {
+                actuator: Actuator::Door,
+                budget: 2
+            }
+        );
+        assert_eq!(*calls.lock().expect("calls"), vec!["open", "open"]);
+    }
+
+    #[test]
+    fn driver_failures_are_not_interlocks() {
+        let (mut driver, _) = driver(InterlockLimits::default());
+        assert_eq!(
+            driver.door_close("D"),
+            Err(CommandError::Failed("door jammed".to_string()))
+        );
+        assert_eq!(blocked(driver.door_close("D")).name(), "min_interval");
+    }
+}
@@ -0,0 +1,360 @@
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Sound covers a centimetre and back in about 58µs.
+const HCSR04_US_PER_CM: f32 = 58.0;
+const HX711_READY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// One raw level measurement: distance in cm or gross weight in kg.
+pub trait LevelDriver: Send {
+    fn measure(&mut self) -> Result<f32, SensorError>;
+}
+
+pub fn echo_to_cm(echo: Duration) -> f32 {
+    echo.as_micros() as f32 / HCSR04_US_PER_CM
+}
+
+/// Bit-banged HX711 lines, so the protocol can run against mock pins.
+pub trait Hx711Pins: Send {
+    /// DOUT low means a conversion is waiting.
+    fn is_ready(&mut self) -> bool;
+    /// Pulses PD_SCK once and samples DOUT.
+    fn clock_bit(&mut self) -> bool;
+}
+
+/// HX711 load-cell amplifier on channel A at gain 128.
+pub struct Hx711<P: Hx711Pins> {
+    pub pins: P,
+    /// Raw counts with the scale empty.
+    pub zero_counts: i32,
+    pub counts_per_kg: f32,
+}
+
+impl<P: Hx711Pins> Hx711<P> {
+    fn read_counts(&mut self) -> Result<i32, SensorError> {
+        let deadline = Instant::now() + HX711_READY_TIMEOUT;
+        while !self.pins.is_ready() {
+            if Instant::now() > deadline {
+                return Err(SensorError::Source("hx711 not ready".to_string()));
+            }
+            sleep(Duration::from_millis(1));
+        }
+        let mut raw = 0u32;
+        for _ in 0..24 {
+            raw = (raw << 1) | self.pins.clock_bit() as u32;
+        }
+        // A 25th pulse keeps channel A at gain 128 for the next conversion.
+        self.pins.clock_bit();
+        // Sign-extend the 24-bit two's complement value.
+        Ok(((raw << 8) as i32) >> 8)
+    }
+}
+
+impl<P: Hx711Pins> LevelDriver for Hx711<P> {
+    fn measure(&mut self) -> Result<f32, SensorError> {
+        let counts = self.read_counts()?;
+        Ok((counts - self.zero_counts) as f32 / self.counts_per_kg)
+    }
+}
+
+fn measure(driver: &Mutex<Box<dyn LevelDriver>>) -> Result<f32, SensorError> {
+    driver
+        .lock()
+        .map_err(|_| SensorError::Source("level driver lock poisoned".to_string()))?
+        .measure()
+}
+
+/// Serves raw feed and water levels and nest box weights from whichever drivers are fitted.
+pub struct LevelSource {
+    feed: Option<Mutex<Box<dyn LevelDriver>>>,
+    water: Option<Mutex<Box<dyn LevelDriver>>>,
+    nest_boxes: HashMap<String, Mutex<Box<dyn LevelDriver>>>,
+}
+
+impl LevelSource {
+    pub fn new(feed: Option<Box<dyn LevelDriver>>, water: Option<Box<dyn LevelDriver>>) -> Self {
+        LevelSource {
+            feed: feed.map(Mutex::new),
+            water: water.map(Mutex::new),
+            nest_boxes: HashMap::new(),
+        }
+    }
+
+    /// Adds a scale under `nest_box`, read in kg for weight-based egg counting.
+    pub fn with_nest_box(mut self, nest_box: &str, scale: Box<dyn LevelDriver>) -> Self {
+        self.nest_boxes
+            .insert(nest_box.to_string(), Mutex::new(scale));
+        self
+    }
+}
+
+#[async_trait]
+impl SensorSource for LevelSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let driver = match kind {
+            SensorKind::FeedLevel => self.feed.as_ref(),
+            SensorKind::WaterLevel => self.water.as_ref(),
+            _ => None,
+        }
+        .ok_or(SensorError::Unsupported(kind))?;
+        Ok(Reading::new(
+            kind.as_str(),
+            kind,
+            SensorValue::Numeric(measure(driver)?),
+            SystemTime::now(),
+        ))
+    }
+
+    async fn nest_box(&self, nest_box: &str) -> Result<Reading, SensorError> {
+        let scale = self
+            .nest_boxes
+            .get(nest_box)
+            .ok_or(SensorError::Unsupported(SensorKind::EggCount))?;
+        Ok(Reading::new(
+            nest_box,
+            SensorKind::EggCount,
+            SensorValue::Numeric(measure(scale)?),
+            SystemTime::now(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod rpi_levels {
+    use super::{echo_to_cm, Hx711Pins, LevelDriver};
+    use crate::sensors::SensorError;
+    use rppal::gpio::{Gpio, InputPin, OutputPin};
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
+
+    const HCSR04_ECHO_TIMEOUT: Duration = Duration::from_millis(30);
+
+    fn spin(duration: Duration) {
+        let start = Instant::now();
+        while start.elapsed() < duration {}
+    }
+
+    fn output(gpio: &Gpio, pin: u8) -> Result<OutputPin, String> {
+        Ok(gpio
+            .get(pin)
+            .map_err(|e| format!("gpio pin {pin} unavailable: {e}"))?
+            .into_output_low())
+    }
+
+    fn input(gpio: &Gpio, pin: u8) -> Result<InputPin, String> {
+        Ok(gpio
+            .get(pin)
+            .map_err(|e| format!("gpio pin {pin} unavailable: {e}"))?
+            .into_input())
+    }
+
+    /// HC-SR04 style ultrasonic ranger (JSN-SR04T for wet water tanks works the same way).
+    pub struct Hcsr04 {
+        trigger: OutputPin,
+        echo: InputPin,
+    }
+
+    impl Hcsr04 {
+        pub fn new(trigger_pin: u8, echo_pin: u8) -> Result<Self, String> {
+            let gpio = Gpio::new().map_err(|e| format!("gpio init failed: {e}"))?;
+            Ok(Hcsr04 {
+                trigger: output(&gpio, trigger_pin)?,
+                echo: input(&gpio, echo_pin)?,
+            })
+        }
+
+        fn wait_for(&self, high: bool, deadline: Instant) -> Result<Instant, SensorError> {
+            while self.echo.is_high() != high {
+                if Instant::now() > deadline {
+                    return Err(SensorError::Source("hc-sr04 echo timed out".to_string()));
+                }
+            }
+            Ok(Instant::now())
+        }
+    }
+
+    impl LevelDriver for Hcsr04 {
+        fn measure(&mut self) -> Result<f32, SensorError> {
+            self.trigger.set_high();
+            spin(Duration::from_micros(10));
+            self.trigger.set_low();
+            let deadline = Instant::now() + HCSR04_ECHO_TIMEOUT;
+            let start = self.wait_for(true, deadline)?;
+            let end = self.wait_for(false, deadline)?;
+            // Let echoes from this ping die down before the next one.
+            sleep(Duration::from_millis(60));
+            Ok(echo_to_cm(end - start))
+        }
+    }
+
+    pub struct RppalHx711Pins {
+        data: InputPin,
+        clock: OutputPin,
+    }
+
+    impl RppalHx711Pins {
+        pub fn new(data_pin: u8, clock_pin: u8) -> Result<Self, String> {
+            let gpio = Gpio::new().map_err(|e| format!("gpio init failed: {e}"))?;
+            Ok(RppalHx711Pins {
+                data: input(&gpio, data_pin)?,
+                clock: output(&gpio, clock_pin)?,
+            })
+        }
+    }
+
+    impl Hx711Pins for RppalHx711Pins {
+        fn is_ready(&mut self) -> bool {
+            self.data.is_low()
+        }
+
+        fn clock_bit(&mut self) -> bool {
+            self.clock.set_high();
+            spin(Duration::from_micros(1));
+            let bit = self.data.is_high();
+            self.clock.set_low();
+            spin(Duration::from_micros(1));
+            bit
+        }
+    }
+}
+
+/// Driver for one container, or `None` when its pins are not configured.
+#[cfg(target_os = "linux")]
+fn level_driver_from_env(kind: SensorKind) -> Result<Option<Box<dyn LevelDriver>>, String> {
+    use crate::levels::{env_prefix, ContainerGeometry, LevelMethod};
+    use crate::util::parse_optional_env;
+
+    let prefix = env_prefix(kind).unwrap_or_default();
+    match ContainerGeometry::from_env(kind)?.method {
+        LevelMethod::Ultrasonic => {
+            let trigger = parse_optional_env::<u8>(&format!("{prefix}_TRIGGER_GPIO"))?;
+            let echo = parse_optional_env::<u8>(&format!("{prefix}_ECHO_GPIO"))?;
+            match (trigger, echo) {
+                (Some(trigger), Some(echo)) => {
+                    Ok(Some(Box::new(rpi_levels::Hcsr04::new(trigger, echo)?)))
+                }
+                _ => Ok(None),
+            }
+        }
+        LevelMethod::Scale => hx711_from_env(prefix),
+    }
+}
+
+/// HX711 scale from `<PREFIX>_HX711_*`, or `None` when its pins are not configured.
+#[cfg(target_os = "linux")]
+fn hx711_from_env(prefix: &str) -> Result<Option<Box<dyn LevelDriver>>, String> {
+    use crate::util::parse_optional_env;
+
+    let data = parse_optional_env::<u8>(&format!("{prefix}_HX711_DATA_GPIO"))?;
+    let clock = parse_optional_env::<u8>(&format!("{prefix}_HX711_CLOCK_GPIO"))?;
+    let (Some(data), Some(clock)) = (data, clock) else {
+        return Ok(None);
+    };
+    let counts_per_kg_var = format!("{prefix}_HX711_COUNTS_PER_KG");
+    let counts_per_kg = parse_optional_env::<f32>(&counts_per_kg_var)?
+        .ok_or_else(|| format!("{counts_per_kg_var} is required for an HX711 scale"))?;
+    Ok(Some(Box::new(Hx711 {
+        pins: rpi_levels::RppalHx711Pins::new(data, clock)?,
+        zero_counts: parse_optional_env(&format!("{prefix}_HX711_ZERO_COUNTS"))?.unwrap_or(0),
+        counts_per_kg,
+    })))
+}
+
+#[cfg(target_os = "linux")]
+pub fn level_source_from_env() -> Result<LevelSource, String> {
+    let feed = level_driver_from_env(SensorKind::FeedLevel)?;
+    let water = level_driver_from_env(SensorKind::WaterLevel)?;
+    let mut source = LevelSource::new(feed, water);
+    for nest_box in crate::eggs::nest_boxes_from_env() {
+        let prefix = format!("NEST_BOX_{}", crate::eggs::box_env_name(&nest_box));
+        if let Some(scale) = hx711_from_env(&prefix)? {
+            source = source.with_nest_box(&nest_box, scale);
+        }
+    }
+    if source.feed.is_none() && source.water.is_none() && source.nest_boxes.is_empty() {
+        return Err(
+            "SENSOR_BACKEND=levels needs FEED_*, WATER_* or NEST_BOX_* GPIO pins for a sensor"
+                .to_string(),
+        );
+    }
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{echo_to_cm, Hx711, Hx711Pins, LevelDriver, LevelSource};
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+    use std::time::Duration;
+
+    /// Shifts out a fixed 24-bit conversion, MSB first.
+    struct MockHx711 {
+        conversion: u32,
+        clocked: u32,
+    }
+
+    impl Hx711Pins for MockHx711 {
+        fn is_ready(&mut self) -> bool {
+            true
+        }
+
+        fn clock_bit(&mut self) -> bool {
+            let bit = self.clocked < 24 && (self.conversion >> (23 - self.clocked)) & 1 == 1;
+            self.clocked += 1;
+            bit
+        }
+    }
+
+    struct FixedDistance(f32);
+
+    impl LevelDriver for FixedDistance {
+        fn measure(&mut self) -> Result<f32, SensorError> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn hx711_decodes_signed_counts_into_kg() {
+        let mut scale = Hx711 {
+            pins: MockHx711 {
+                conversion: 0x01_86A0,
+                clocked: 0,
+            },
+            zero_counts: 20_000,
+            counts_per_kg: 10_000.0,
+        };
+        assert_eq!(scale.measure(), Ok(8.0));
+        assert_eq!(scale.pins.clocked, 25);
+
+        let mut negative = Hx711 {
+            pins: MockHx711 {
+                conversion: 0xFF_FFFF,
+                clocked: 0,
+            },
+            zero_counts: 0,
+            counts_per_kg: 1.0,
+        };
+        assert_eq!(negative.measure(), Ok(-1.0));
+    }
+
+    #[tokio::test]
+    async fn level_source_serves_fitted_containers() {
+        assert!((echo_to_cm(Duration::from_micros(2900)) - 50.0).abs() < 0.01);
+
+        let source = LevelSource::new(Some(Box::new(FixedDistance(35.0))), None)
+            .with_nest_box("left", Box::new(FixedDistance(0.52)));
+        let feed = source.sample(SensorKind::FeedLevel).await.expect("feed");
+        assert_eq!(feed.value, SensorValue::Numeric(35.0));
+        let left = source.nest_box("left").await.expect("nest box");
+        assert_eq!(left.value, SensorValue::Numeric(0.52));
+        assert_eq!(
+            source.sample(SensorKind::WaterLevel).await,
+            Err(SensorError::Unsupported(SensorKind::WaterLevel))
+        );
+    }
+}
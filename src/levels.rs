@@ -0,0 +1,427 @@
+use crate::sensors::{Reading, SensorKind, SensorValue};
+use crate::util::{millis, parse_env};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::Path;
+use std::time::SystemTime;
+
+const FEEDER_HISTORY_FILE_DEFAULT: &str = "feeder-history.json";
+const FEEDER_HISTORY_MAX_ENTRIES: usize = 200;
+/// At or below this the hopper counts as empty rather than jammed.
+const EMPTY_PERCENT: f32 = 2.0;
+/// A rise larger than this between checks is a refill, which hides any feeding drop.
+const REFILL_PERCENT: f32 = 10.0;
+
+/// How a level sensor measures its container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelMethod {
+    /// Ultrasonic distance from the lid down to the surface, in cm.
+    Ultrasonic,
+    /// Load cell under the container, in kg including the container itself.
+    Scale,
+}
+
+/// Container shape and sensor mounting, used to turn a raw distance or weight into a fill level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerGeometry {
+    pub method: LevelMethod,
+    /// Sensor to the container floor (ultrasonic).
+    pub empty_distance_cm: f32,
+    /// Sensor to the surface when full (ultrasonic).
+    pub full_distance_cm: f32,
+    /// Inner diameter at the full line (ultrasonic); differs from the floor for tapered hoppers.
+    pub top_diameter_cm: f32,
+    /// Inner diameter at the floor (ultrasonic).
+    pub bottom_diameter_cm: f32,
+    /// Weight of the empty container (scale).
+    pub tare_kg: f32,
+    /// Weight of the contents when full.
+    pub capacity_kg: f32,
+}
+
+/// Per-sensor changes to a [`ContainerGeometry`], e.g. from the sensor registry; unset fields
+/// keep the value from the environment.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeometryOverrides {
+    pub method: Option<LevelMethod>,
+    pub empty_distance_cm: Option<f32>,
+    pub full_distance_cm: Option<f32>,
+    pub top_diameter_cm: Option<f32>,
+    pub bottom_diameter_cm: Option<f32>,
+    pub tare_kg: Option<f32>,
+    pub capacity_kg: Option<f32>,
+}
+
+/// Env prefix for a container's settings, e.g. `FEED_CAPACITY_KG`.
+pub fn env_prefix(kind: SensorKind) -> Option<&'static str> {
+    match kind {
+        SensorKind::FeedLevel => Some("FEED"),
+        SensorKind::WaterLevel => Some("WATER"),
+        _ => None,
+    }
+}
+
+impl ContainerGeometry {
+    /// Reads `<PREFIX>_LEVEL_METHOD`, distances, diameters, tare and capacity for `kind`.
+    pub fn from_env(kind: SensorKind) -> Result<ContainerGeometry, String> {
+        let prefix = env_prefix(kind)
+            .ok_or_else(|| format!("{} is not a container level", kind.as_str()))?;
+        let (empty, full, capacity) = match kind {
+            SensorKind::FeedLevel => (60.0, 10.0, 10.0),
+            _ => (40.0, 5.0, 20.0),
+        };
+        let method_var = format!("{prefix}_LEVEL_METHOD");
+        let method = match env::var(&method_var).as_deref() {
+            Ok("ultrasonic") | Err(_) => LevelMethod::Ultrasonic,
+            Ok("scale") => LevelMethod::Scale,
+            Ok(other) => {
+                return Err(format!(
+                    "invalid value for {method_var}: {other} (expected `ultrasonic` or `scale`)"
+                ))
+            }
+        };
+        let top_diameter_cm = parse_env(&format!("{prefix}_TOP_DIAMETER_CM"), 30.0)?;
+        let geometry = ContainerGeometry {
+            method,
+            empty_distance_cm: parse_env(&format!("{prefix}_EMPTY_DISTANCE_CM"), empty)?,
+            full_distance_cm: parse_env(&format!("{prefix}_FULL_DISTANCE_CM"), full)?,
+            top_diameter_cm,
+            bottom_diameter_cm: parse_env(
+                &format!("{prefix}_BOTTOM_DIAMETER_CM"),
+                top_diameter_cm,
+            )?,
+            tare_kg: parse_env(&format!("{prefix}_TARE_KG"), 0.0)?,
+            capacity_kg: parse_env(&format!("{prefix}_CAPACITY_KG"), capacity)?,
+        };
+        geometry.validate(prefix)?;
+        Ok(geometry)
+    }
+
+    /// Applies `overrides`; `name` labels the container in the error when the result is invalid.
+    pub fn with_overrides(
+        self,
+        overrides: &GeometryOverrides,
+        name: &str,
+    ) -> Result<ContainerGeometry, String> {
+        let geometry = ContainerGeometry {
+            method: overrides.method.unwrap_or(self.method),
+            empty_distance_cm: overrides
+                .empty_distance_cm
+                .unwrap_or(self.empty_distance_cm),
+            full_distance_cm: overrides.full_distance_cm.unwrap_or(self.full_distance_cm),
+            top_diameter_cm: overrides.top_diameter_cm.unwrap_or(self.top_diameter_cm),
+            bottom_diameter_cm: overrides
+                .bottom_diameter_cm
+                .or(overrides.top_diameter_cm)
+                .unwrap_or(self.bottom_diameter_cm),
+            tare_kg: overrides.tare_kg.unwrap_or(self.tare_kg),
+            capacity_kg: overrides.capacity_kg.unwrap_or(self.capacity_kg),
+        };
+        geometry.validate(name)?;
+        Ok(geometry)
+    }
+
+    fn validate(&self, name: &str) -> Result<(), String> {
+        if self.capacity_kg <= 0.0
+            || (self.method == LevelMethod::Ultrasonic
+                && self.empty_distance_cm <= self.full_distance_cm)
+        {
+            return Err(format!(
+                "{name} container needs a positive capacity and an empty distance beyond the full distance"
+            ));
+        }
+        Ok(())
+    }
+
+    fn fill_height_cm(&self) -> f32 {
+        self.empty_distance_cm - self.full_distance_cm
+    }
+
+    /// Fraction of the full volume below `height` above the floor, for straight or tapered walls.
+    fn volume_fraction(&self, height: f32) -> f32 {
+        let volume_to = |h: f32| {
+            let (rb, rt) = (self.bottom_diameter_cm / 2.0, self.top_diameter_cm / 2.0);
+            let r = rb + (rt - rb) * h / self.fill_height_cm();
+            PI * h / 3.0 * (rb * rb + rb * r + r * r)
+        };
+        let full = volume_to(self.fill_height_cm());
+        if full <= 0.0 {
+            return 0.0;
+        }
+        volume_to(height.clamp(0.0, self.fill_height_cm())) / full
+    }
+
+    /// Fill level in percent for a raw distance (cm) or gross weight (kg).
+    pub fn percent(&self, raw: f32) -> f32 {
+        let fraction = match self.method {
+            LevelMethod::Ultrasonic => self.volume_fraction(self.empty_distance_cm - raw),
+            LevelMethod::Scale => (raw - self.tare_kg) / self.capacity_kg,
+        };
+        fraction.clamp(0.0, 1.0) * 100.0
+    }
+
+    /// Raw sensor value that corresponds to `percent`; the inverse of `percent`.
+    pub fn raw_at_percent(&self, percent: f32) -> f32 {
+        let fraction = (percent / 100.0).clamp(0.0, 1.0);
+        match self.method {
+            LevelMethod::Scale => self.tare_kg + fraction * self.capacity_kg,
+            LevelMethod::Ultrasonic => {
+                let (mut low, mut high) = (0.0, self.fill_height_cm());
+                for _ in 0..32 {
+                    let mid = (low + high) / 2.0;
+                    if self.volume_fraction(mid) < fraction {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                self.empty_distance_cm - (low + high) / 2.0
+            }
+        }
+    }
+
+    pub fn kg(&self, percent: f32) -> f32 {
+        percent / 100.0 * self.capacity_kg
+    }
+
+    /// Turns a raw distance or weight reading into a percent reading.
+    pub fn apply(&self, mut reading: Reading) -> Reading {
+        if let SensorValue::Numeric(raw) = reading.value {
+            reading.value = SensorValue::Numeric(self.percent(raw));
+        }
+        reading
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct LevelSample {
+    at_ms: u64,
+    percent: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeederIssue {
+    Empty,
+    Jammed {
+        activations: usize,
+        drop_percent: f32,
+    },
+}
+
+impl fmt::Display for FeederIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeederIssue::Empty => write!(f, "feeder is empty"),
+            FeederIssue::Jammed {
+                activations,
+                drop_percent,
+            } => write!(
+                f,
+                "feeder may be jammed: level dropped {drop_percent:.1}% over {activations} activation(s)"
+            ),
+        }
+    }
+}
+
+/// Feeder activations and feed level checks, kept across runs to spot a jammed or empty feeder.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeederLog {
+    /// Activation times per feeder device key.
+    activations: BTreeMap<String, Vec<u64>>,
+    /// Level checks per feed-level sensor id.
+    feed_levels: BTreeMap<String, Vec<LevelSample>>,
+}
+
+/// Appends `item`, dropping the oldest entries beyond the history limit.
+fn push_bounded<T>(entries: &mut Vec<T>, item: T) {
+    entries.push(item);
+    let excess = entries.len().saturating_sub(FEEDER_HISTORY_MAX_ENTRIES);
+    entries.drain(..excess);
+}
+
+impl FeederLog {
+    pub fn path_from_env() -> String {
+        env::var("FEEDER_HISTORY_FILE").unwrap_or_else(|_| FEEDER_HISTORY_FILE_DEFAULT.to_string())
+    }
+
+    /// Loads `path`; a missing file is an empty log.
+    pub fn load(path: &str) -> Result<FeederLog, String> {
+        if !Path::new(path).exists() {
+            return Ok(FeederLog::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read feeder history `{path}`: {e}"))?;
+        serde_json::from_str(&content).map_err(|e| format!("invalid feeder history `{path}`: {e}"))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to encode feeder history: {e}"))?;
+        std::fs::write(path, content + "\n")
+            .map_err(|e| format!("failed to write feeder history `{path}`: {e}"))
+    }
+
+    pub fn record_activation(&mut self, feeder: &str, at: SystemTime) {
+        push_bounded(
+            self.activations.entry(feeder.to_string()).or_default(),
+            millis(at),
+        );
+    }
+
+    pub fn record_level(&mut self, sensor_id: &str, percent: f32, at: SystemTime) {
+        let sample = LevelSample {
+            at_ms: millis(at),
+            percent,
+        };
+        push_bounded(
+            self.feed_levels.entry(sensor_id.to_string()).or_default(),
+            sample,
+        );
+    }
+
+    /// Compares `percent` from `sensor_id` with its previous check: each run of `feeder` (any
+    /// feeder when `None`) since then should have lowered the hopper by at least
+    /// `min_drop_percent`.
+    pub fn cross_check(
+        &self,
+        sensor_id: &str,
+        feeder: Option<&str>,
+        percent: f32,
+        at: SystemTime,
+        min_drop_percent: f32,
+    ) -> Option<FeederIssue> {
+        let previous = self.feed_levels.get(sensor_id)?.last()?;
+        let now_ms = millis(at);
+        let activations = self
+            .activations
+            .iter()
+            .filter(|(key, _)| feeder.is_none_or(|feeder| feeder == key.as_str()))
+            .flat_map(|(_, times)| times)
+            .filter(|t| **t > previous.at_ms && **t <= now_ms)
+            .count();
+        if activations == 0 {
+            return None;
+        }
+        if percent <= EMPTY_PERCENT {
+            return Some(FeederIssue::Empty);
+        }
+        let drop_percent = previous.percent - percent;
+        if drop_percent < -REFILL_PERCENT {
+            return None;
+        }
+        (drop_percent < min_drop_percent * activations as f32).then_some(FeederIssue::Jammed {
+            activations,
+            drop_percent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContainerGeometry, FeederIssue, FeederLog, GeometryOverrides, LevelMethod};
+    use std::time::{Duration, SystemTime};
+
+    fn hopper(top: f32, bottom: f32) -> ContainerGeometry {
+        ContainerGeometry {
+            method: LevelMethod::Ultrasonic,
+            empty_distance_cm: 60.0,
+            full_distance_cm: 10.0,
+            top_diameter_cm: top,
+            bottom_diameter_cm: bottom,
+            tare_kg: 0.0,
+            capacity_kg: 10.0,
+        }
+    }
+
+    #[test]
+    fn geometry_converts_distance_and_weight_to_fill() {
+        let straight = hopper(30.0, 30.0);
+        assert!((straight.percent(35.0) - 50.0).abs() < 0.01);
+        assert_eq!(straight.percent(5.0), 100.0);
+        assert_eq!(straight.percent(70.0), 0.0);
+        assert!((straight.kg(50.0) - 5.0).abs() < 0.01);
+
+        // Half the height of a hopper that narrows towards the floor holds well under half.
+        let tapered = hopper(40.0, 10.0);
+        assert!(tapered.percent(35.0) < 35.0);
+        assert!((tapered.percent(tapered.raw_at_percent(60.0)) - 60.0).abs() < 0.1);
+
+        let scale = ContainerGeometry {
+            method: LevelMethod::Scale,
+            tare_kg: 2.0,
+            capacity_kg: 20.0,
+            ..hopper(30.0, 30.0)
+        };
+        assert!((scale.percent(7.0) - 25.0).abs() < 0.01);
+        assert!((scale.raw_at_percent(25.0) - 7.0).abs() < 0.01);
+
+        let overrides: GeometryOverrides =
+            serde_json::from_str(r#"{"full_distance_cm": 20.0, "top_diameter_cm": 40.0}"#)
+                .expect("overrides");
+        let coop2 = straight
+            .clone()
+            .with_overrides(&overrides, "coop2-feed")
+            .expect("geometry");
+        assert_eq!(coop2.full_distance_cm, 20.0);
+        assert_eq!(coop2.bottom_diameter_cm, 40.0);
+        assert_eq!(coop2.empty_distance_cm, 60.0);
+        let inverted = GeometryOverrides {
+            full_distance_cm: Some(70.0),
+            ..GeometryOverrides::default()
+        };
+        assert!(straight.with_overrides(&inverted, "coop2-feed").is_err());
+    }
+
+    #[test]
+    fn feeding_without_a_level_drop_is_flagged() {
+        let t0 = SystemTime::now() - Duration::from_secs(3 * 3600);
+        let hour = Duration::from_secs(3600);
+        let mut log = FeederLog::default();
+        log.record_level("feed", 60.0, t0);
+        assert_eq!(log.cross_check("feed", None, 60.0, t0 + hour, 1.0), None);
+
+        log.record_activation("F1", t0 + hour / 2);
+        log.record_activation("F1", t0 + hour);
+        let check = |percent| log.cross_check("feed", Some("F1"), percent, t0 + hour * 2, 1.0);
+        assert_eq!(check(55.0), None);
+        assert_eq!(
+            check(59.5),
+            Some(FeederIssue::Jammed {
+                activations: 2,
+                drop_percent: 0.5
+            })
+        );
+        assert_eq!(check(1.0), Some(FeederIssue::Empty));
+        assert_eq!(check(95.0), None);
+    }
+
+    #[test]
+    fn each_hopper_is_checked_against_its_own_feeder() {
+        let t0 = SystemTime::now() - Duration::from_secs(3 * 3600);
+        let hour = Duration::from_secs(3600);
+        let mut log = FeederLog::default();
+        log.record_level("coop1-feed", 60.0, t0);
+        log.record_level("coop2-feed", 40.0, t0);
+        log.record_activation("COOP1_FEEDER", t0 + hour);
+
+        let later = t0 + hour * 2;
+        assert!(log
+            .cross_check("coop1-feed", Some("COOP1_FEEDER"), 60.0, later, 1.0)
+            .is_some());
+        assert_eq!(
+            log.cross_check("coop2-feed", Some("COOP2_FEEDER"), 40.0, later, 1.0),
+            None
+        );
+        // Without a known feeder every run counts.
+        assert!(log
+            .cross_check("coop2-feed", None, 40.0, later, 1.0)
+            .is_some());
+        assert_eq!(log.cross_check("coop3-feed", None, 40.0, later, 1.0), None);
+    }
+}
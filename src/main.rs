@@ -1,27 +1,440 @@
 mod actuator_server;
+mod actuator_status;
 mod actuators;
 mod ai;
+#[cfg(feature = "pi-hw")]
+mod air_quality;
 mod alerts;
 mod cache;
+mod calibration;
 mod camera;
 mod cli;
+mod door;
+#[cfg(feature = "pi-hw")]
+mod ds18b20;
+mod eggs;
+mod feeder;
+mod heat_stress;
+#[cfg(feature = "pi-hw")]
+mod i2c_sensors;
+mod interlocks;
+#[cfg(feature = "pi-hw")]
+mod level_sensors;
+mod levels;
+mod modbus;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(all(feature = "pi-hw", target_os = "linux"))]
+mod pir;
+mod retry;
 mod scheduler;
+mod sensor_filter;
+mod sensor_registry;
+mod sensor_server;
+mod sensor_sim;
+mod sensor_sources;
 mod sensors;
+mod util;
 
+use calibration::CalibrationConfig;
 use clap::Parser;
-use cli::{Cli, Commands, FeedCommands, RunCommands, ServeCommands};
+use cli::{Cli, Commands, DoorCommands, FeedCommands, RunCommands, SensorCommands, ServeCommands};
 use dotenvy::dotenv;
+use futures::future::join_all;
+use heat_stress::{derive_reading, StressBand, ThiBands};
+use levels::{ContainerGeometry, FeederLog};
+use sensor_filter::ReadingFilter;
+use sensor_registry::SensorRegistry;
 use sensors::{
-    EggPresenceSensor, HumiditySensor, MotionSensor, Sensor, SensorValue, TemperatureSensor,
+    EggCountSensor, EggPresenceSensor, FeedLevelSensor, HumiditySensor, MotionSensor,
+    NumericSensor, Reading, Sensor, SensorBackend, SensorError, SensorKind, SensorValue,
+    TemperatureSensor, WaterLevelSensor,
 };
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::Duration;
 
-fn format_sensor_value(value: SensorValue) -> String {
-    match value {
-        SensorValue::Numeric(v) => format!("{v:.1}"),
-        SensorValue::Binary(v) => v.to_string(),
+/// Gateway readings older than this are flagged rather than trusted.
+const STALE_READING_AFTER: Duration = Duration::from_secs(300);
+/// Ammonia above this irritates birds' airways; overridable with `AMMONIA_ALERT_PPM`.
+const AMMONIA_ALERT_PPM_DEFAULT: f32 = 25.0;
+/// CO2 above this points at poor ventilation; overridable with `CO2_ALERT_PPM`.
+const CO2_ALERT_PPM_DEFAULT: f32 = 2500.0;
+/// Fill levels below these prompt a refill; overridable with `FEED_LOW_PCT` / `WATER_LOW_PCT`.
+const FEED_LOW_PCT_DEFAULT: f32 = 20.0;
+const WATER_LOW_PCT_DEFAULT: f32 = 25.0;
+/// Each feeder activation should lower the hopper by at least this; `FEEDER_MIN_DROP_PCT`.
+const FEEDER_MIN_DROP_PCT_DEFAULT: f32 = 1.0;
+
+fn format_sensor_value(
+    reading: &Result<Reading, SensorError>,
+    calibration: &CalibrationConfig,
+) -> String {
+    match reading {
+        Ok(reading) => calibration.display(reading).to_string(),
+        Err(_) => "unavailable".to_string(),
+    }
+}
+
+fn cache_reading(cache: &mut cache::DataCache, k: &str, reading: &Result<Reading, SensorError>) {
+    match reading {
+        Ok(reading) => cache.store_reading(k, reading),
+        Err(_) => cache.store(k, "unavailable"),
+    }
+}
+
+fn alert_on_reading(name: &str, reading: &Result<Reading, SensorError>) {
+    let message = match reading {
+        Err(err) => format!("{name} sensor unavailable: {err}"),
+        Ok(reading) if reading.age() > STALE_READING_AFTER => format!(
+            "{name} reading from {} is stale ({}s old)",
+            reading.sensor_id,
+            reading.age().as_secs()
+        ),
+        Ok(_) => return,
+    };
+    let alert = alerts::Alert::new(&message);
+    alert.send();
+}
+
+fn alert_above(name: &str, reading: &Result<Reading, SensorError>, limit: f32) {
+    if let Ok(
+        reading @ Reading {
+            value: SensorValue::Numeric(value),
+            ..
+        },
+    ) = reading
+    {
+        if *value > limit {
+            let alert = alerts::Alert::new(&format!("{name} at {reading} exceeds {limit}"));
+            alert.send();
+        }
+    }
+}
+
+fn alert_below(name: &str, reading: &Result<Reading, SensorError>, limit: f32) {
+    if let Ok(
+        reading @ Reading {
+            value: SensorValue::Numeric(value),
+            ..
+        },
+    ) = reading
+    {
+        if *value < limit {
+            let alert = alerts::Alert::new(&format!("{name} at {reading} is below {limit}%"));
+            alert.send();
+        }
+    }
+}
+
+/// An alert limit from the environment; an unparsable value stops the run like other settings.
+fn env_f32(name: &str, default: f32) -> f32 {
+    util::parse_env(name, default).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    })
+}
+
+/// Alerts on the configured limit for gas and level kinds; other kinds have none.
+fn alert_thresholds(name: &str, kind: SensorKind, reading: &Result<Reading, SensorError>) {
+    match kind {
+        SensorKind::Ammonia => alert_above(
+            name,
+            reading,
+            env_f32("AMMONIA_ALERT_PPM", AMMONIA_ALERT_PPM_DEFAULT),
+        ),
+        SensorKind::Co2 => alert_above(
+            name,
+            reading,
+            env_f32("CO2_ALERT_PPM", CO2_ALERT_PPM_DEFAULT),
+        ),
+        SensorKind::FeedLevel => {
+            alert_below(name, reading, env_f32("FEED_LOW_PCT", FEED_LOW_PCT_DEFAULT))
+        }
+        SensorKind::WaterLevel => alert_below(
+            name,
+            reading,
+            env_f32("WATER_LOW_PCT", WATER_LOW_PCT_DEFAULT),
+        ),
+        SensorKind::Thi => {
+            if let Some(band) = thi_band(reading).filter(|band| *band >= StressBand::Danger) {
+                let alert = alerts::Alert::new(&format!(
+                    "{name} heat stress {band}: THI {}",
+                    reading.as_ref().map(|r| r.to_string()).unwrap_or_default()
+                ));
+                alert.send();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn thi_band(reading: &Result<Reading, SensorError>) -> Option<StressBand> {
+    let Ok(Reading {
+        value: SensorValue::Numeric(thi),
+        ..
+    }) = reading
+    else {
+        return None;
+    };
+    let bands = ThiBands::from_env().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        ThiBands::default()
+    });
+    Some(bands.band(*thi))
+}
+
+/// A feed level by sensor id, with the device key of the feeder filling that hopper.
+type FeedLevel<'a> = (&'a str, Option<&'a str>, &'a Result<Reading, SensorError>);
+
+/// Cross-checks each feed level against recent runs of its feeder and records it.
+fn check_feeders(levels: &[FeedLevel]) {
+    if levels.is_empty() {
+        return;
+    }
+    let path = FeederLog::path_from_env();
+    let mut log = match FeederLog::load(&path) {
+        Ok(log) => log,
+        Err(err) => {
+            eprintln!("{err}");
+            return;
+        }
+    };
+    let min_drop = env_f32("FEEDER_MIN_DROP_PCT", FEEDER_MIN_DROP_PCT_DEFAULT);
+    let now = SystemTime::now();
+    for (sensor_id, feeder, reading) in levels {
+        let Ok(Reading {
+            value: SensorValue::Numeric(percent),
+            ..
+        }) = reading
+        else {
+            continue;
+        };
+        if let Some(issue) = log.cross_check(sensor_id, *feeder, *percent, now, min_drop) {
+            let message = if levels.len() > 1 {
+                format!("{sensor_id}: {issue}")
+            } else {
+                issue.to_string()
+            };
+            alerts::Alert::new(&message).send();
+        }
+        log.record_level(sensor_id, *percent, now);
+    }
+    if let Err(err) = log.save(&path) {
+        eprintln!("{err}");
+    }
+}
+
+/// Fill level with the matching weight, e.g. `42.0% (4.2 kg)`.
+fn format_level(reading: &Result<Reading, SensorError>, geometry: &ContainerGeometry) -> String {
+    match reading {
+        Ok(Reading {
+            value: SensorValue::Numeric(percent),
+            ..
+        }) => format!("{percent:.1}% ({:.1} kg)", geometry.kg(*percent)),
+        _ => "unavailable".to_string(),
+    }
+}
+
+fn container_geometry(kind: SensorKind) -> ContainerGeometry {
+    ContainerGeometry::from_env(kind).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    })
+}
+
+async fn read_optional<S: Sensor>(sensor: &Option<S>) -> Option<Result<Reading, SensorError>> {
+    match sensor {
+        Some(sensor) => Some(sensor.read().await),
+        None => None,
+    }
+}
+
+fn load_calibration(path: &str) -> CalibrationConfig {
+    CalibrationConfig::load(path).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    })
+}
+
+/// Reads `sensor_id` without calibration so reference points pair with the raw value.
+async fn read_uncalibrated(
+    backend: &SensorBackend,
+    sensor_id: &str,
+) -> Result<Reading, SensorError> {
+    match load_registry().raw_sensor(sensor_id) {
+        Ok(Some(sensor)) => return sensor.read().await,
+        Ok(None) => {}
+        Err(err) => return Err(SensorError::Source(err)),
+    }
+    match SensorKind::from_name(sensor_id) {
+        Some(SensorKind::Temperature) => {
+            TemperatureSensor::new(&required_env("TEMP_SENSOR_KEY"))
+                .with_backend(backend.clone())
+                .read()
+                .await
+        }
+        Some(SensorKind::Humidity) => {
+            HumiditySensor::new(&required_env("HUMIDITY_SENSOR_KEY"))
+                .with_backend(backend.clone())
+                .read()
+                .await
+        }
+        Some(SensorKind::Pressure) => {
+            NumericSensor::pressure(&required_env("PRESSURE_SENSOR_KEY"))
+                .with_backend(backend.clone())
+                .read()
+                .await
+        }
+        Some(SensorKind::Ammonia) => {
+            NumericSensor::ammonia(&required_env("AMMONIA_SENSOR_KEY"))
+                .with_backend(backend.clone())
+                .read()
+                .await
+        }
+        Some(SensorKind::Co2) => {
+            NumericSensor::co2(&required_env("CO2_SENSOR_KEY"))
+                .with_backend(backend.clone())
+                .read()
+                .await
+        }
+        _ => {
+            let auxiliary = match backend {
+                SensorBackend::Local(source) => source.auxiliary().await,
+                SensorBackend::Http | SensorBackend::Gateway(_) => Vec::new(),
+            };
+            auxiliary
+                .into_iter()
+                .find(|(id, _)| id == sensor_id)
+                .map(|(_, reading)| reading)
+                .unwrap_or_else(|| {
+                    Err(SensorError::Source(format!(
+                        "no numeric sensor named `{sensor_id}`"
+                    )))
+                })
+        }
+    }
+}
+
+fn load_registry() -> SensorRegistry {
+    SensorRegistry::load(&SensorRegistry::path_from_env()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    })
+}
+
+/// Prints the server's message for an actuator command, or its status when it sent none.
+fn print_actuator_reply(label: &str, reply: &actuators::ActuatorReply) {
+    if reply.message.is_empty() {
+        println!("{label}: {} (HTTP {})", reply.status, reply.http_status);
+    } else {
+        println!("{label}: {} (HTTP {})", reply.message, reply.http_status);
+    }
+}
+
+/// `FEED_PORTION_GRAMS` for `feed now`; unset leaves the portion to the actuator server.
+fn portion_grams_from_env() -> Option<f32> {
+    let grams = util::parse_optional_env::<f32>("FEED_PORTION_GRAMS").unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    })?;
+    if !(grams.is_finite() && grams > 0.0) {
+        eprintln!("invalid value for FEED_PORTION_GRAMS: {grams}");
+        std::process::exit(2);
     }
+    Some(grams)
+}
+
+fn prompt_grams() -> f32 {
+    print!("Weigh the feed and enter grams: ");
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    let mut line = String::new();
+    if let Err(err) = std::io::stdin().read_line(&mut line) {
+        eprintln!("failed to read grams: {err}");
+        std::process::exit(1);
+    }
+    match line.trim().parse::<f32>() {
+        Ok(grams) if grams.is_finite() && grams > 0.0 => grams,
+        _ => {
+            eprintln!("invalid grams: {}", line.trim());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints retry metrics for endpoints that needed a retry, failed or refused calls.
+fn report_http_retries() {
+    for (endpoint, stats) in retry::stats() {
+        if stats.retries == 0 && stats.failures == 0 && stats.rejected == 0 {
+            continue;
+        }
+        println!(
+            "HTTP {endpoint}: {} attempts, {} retries, {} failures, circuit opened {} times, {} calls refused",
+            stats.attempts, stats.retries, stats.failures, stats.opened, stats.rejected
+        );
+    }
+}
+
+/// `status` for sensors declared in the registry file, grouped by zone.
+async fn registry_status(registry: &SensorRegistry) {
+    let cache_key = required_env("CACHE_KEY");
+    let calibration = load_calibration(&CalibrationConfig::path_from_env());
+    let filter = Arc::new(load_filter());
+    let sensors = registry
+        .build(&calibration, Some(Arc::clone(&filter)))
+        .unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(2);
+        });
+    let readings = sensor_registry::read_all(sensors).await;
+    if let Err(err) = filter.save(&ReadingFilter::path_from_env()) {
+        eprintln!("{err}");
+    }
+
+    let mut data_cache = cache::DataCache::new(&cache_key);
+    let feed_levels: Vec<FeedLevel> = readings
+        .iter()
+        .filter(|(sensor, _)| sensor.kind == SensorKind::FeedLevel)
+        .map(|(sensor, reading)| (sensor.id.as_str(), sensor.feeder.as_deref(), reading))
+        .collect();
+    check_feeders(&feed_levels);
+    for (sensor, reading) in &readings {
+        alert_on_reading(&sensor.id, reading);
+        alert_thresholds(&sensor.id, sensor.kind, reading);
+        if let Some(unchanged) = filter.flatlined(&sensor.id) {
+            let alert = alerts::Alert::new(&format!(
+                "{} value hasn't changed in {} hours",
+                sensor.id,
+                unchanged.as_secs() / 3600
+            ));
+            alert.send();
+        }
+        cache_reading(&mut data_cache, &format!("last_{}", sensor.id), reading);
+    }
+
+    println!("Chicken Coop Status");
+    for (zone, entries) in sensor_registry::by_zone(&readings) {
+        println!("[{zone}]");
+        for (sensor, reading) in entries {
+            let value = match (&sensor.geometry, thi_band(reading)) {
+                (Some(geometry), _) => format_level(reading, geometry),
+                (None, Some(band)) if sensor.kind == SensorKind::Thi => {
+                    format!("{} ({band})", format_sensor_value(reading, &calibration))
+                }
+                _ => format_sensor_value(reading, &calibration),
+            };
+            println!("  {}: {value}", sensor.id);
+        }
+    }
+}
+
+fn load_filter() -> ReadingFilter {
+    ReadingFilter::from_env().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    })
 }
 
 fn required_env(name: &str) -> String {
@@ -31,6 +444,14 @@ fn required_env(name: &str) -> String {
     })
 }
 
+/// A sensor key, required only when `backend` reads from a gateway; local sources take none.
+fn sensor_key(name: &str, backend: &SensorBackend) -> String {
+    match backend {
+        SensorBackend::Local(_) => env::var(name).unwrap_or_default(),
+        SensorBackend::Http | SensorBackend::Gateway(_) => required_env(name),
+    }
+}
+
 fn env_or_default(name: &str, default: &str) -> String {
     env::var(name).unwrap_or_else(|_| default.to_string())
 }
@@ -43,49 +464,390 @@ async fn main() {
 
     match args.command {
         Some(Commands::Status) => {
-            let temp_sensor_key = required_env("TEMP_SENSOR_KEY");
-            let humidity_sensor_key = required_env("HUMIDITY_SENSOR_KEY");
-            let motion_sensor_key = required_env("MOTION_SENSOR_KEY");
-            let egg_sensor_key = required_env("EGG_SENSOR_KEY");
+            let registry = load_registry();
+            if !registry.is_empty() {
+                registry_status(&registry).await;
+                report_http_retries();
+                return;
+            }
+
+            let backend = SensorBackend::from_env().unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(2);
+            });
+
+            let temp_sensor_key = sensor_key("TEMP_SENSOR_KEY", &backend);
+            let humidity_sensor_key = sensor_key("HUMIDITY_SENSOR_KEY", &backend);
+            let motion_sensor_key = sensor_key("MOTION_SENSOR_KEY", &backend);
+            let egg_sensor_key = sensor_key("EGG_SENSOR_KEY", &backend);
             let cache_key = required_env("CACHE_KEY");
 
-            let temp_sensor = TemperatureSensor::new(&temp_sensor_key);
-            let humidity_sensor = HumiditySensor::new(&humidity_sensor_key);
-            let motion_sensor = MotionSensor::new(&motion_sensor_key);
-            let egg_sensor = EggPresenceSensor::new(&egg_sensor_key);
+            let calibration = load_calibration(&CalibrationConfig::path_from_env());
+            let filter = Arc::new(load_filter());
+
+            let temp_sensor = TemperatureSensor::new(&temp_sensor_key)
+                .with_backend(backend.clone())
+                .with_calibration(calibration.for_sensor("temperature"))
+                .with_filter(Arc::clone(&filter));
+            let humidity_sensor = HumiditySensor::new(&humidity_sensor_key)
+                .with_backend(backend.clone())
+                .with_calibration(calibration.for_sensor("humidity"))
+                .with_filter(Arc::clone(&filter));
+            let motion_sensor = MotionSensor::new(&motion_sensor_key).with_backend(backend.clone());
+            let egg_sensor = EggPresenceSensor::new(&egg_sensor_key).with_backend(backend.clone());
+            let pressure_sensor = env::var("PRESSURE_SENSOR_KEY").ok().map(|key| {
+                NumericSensor::pressure(&key)
+                    .with_backend(backend.clone())
+                    .with_calibration(calibration.for_sensor("pressure"))
+                    .with_filter(Arc::clone(&filter))
+            });
+            let ammonia_sensor = env::var("AMMONIA_SENSOR_KEY").ok().map(|key| {
+                NumericSensor::ammonia(&key)
+                    .with_backend(backend.clone())
+                    .with_calibration(calibration.for_sensor("ammonia"))
+                    .with_filter(Arc::clone(&filter))
+            });
+            let co2_sensor = env::var("CO2_SENSOR_KEY").ok().map(|key| {
+                NumericSensor::co2(&key)
+                    .with_backend(backend.clone())
+                    .with_calibration(calibration.for_sensor("co2"))
+                    .with_filter(Arc::clone(&filter))
+            });
+            let feed_sensor = env::var("FEED_SENSOR_KEY").ok().map(|key| {
+                FeedLevelSensor::new(&key, container_geometry(SensorKind::FeedLevel))
+                    .with_backend(backend.clone())
+                    .with_calibration(calibration.for_sensor("feed"))
+            });
+            let water_sensor = env::var("WATER_SENSOR_KEY").ok().map(|key| {
+                WaterLevelSensor::new(&key, container_geometry(SensorKind::WaterLevel))
+                    .with_backend(backend.clone())
+                    .with_calibration(calibration.for_sensor("water"))
+            });
             let mut data_cache = cache::DataCache::new(&cache_key);
 
-            let temp = format_sensor_value(temp_sensor.read());
-            let humidity = format_sensor_value(humidity_sensor.read());
-            let motion = format_sensor_value(motion_sensor.read());
-            let eggs = format_sensor_value(egg_sensor.read());
+            let (
+                temp_reading,
+                humidity_reading,
+                motion_reading,
+                egg_reading,
+                pressure_reading,
+                ammonia_reading,
+                co2_reading,
+                feed_reading,
+                water_reading,
+            ) = tokio::join!(
+                temp_sensor.read(),
+                humidity_sensor.read(),
+                motion_sensor.read(),
+                egg_sensor.read(),
+                read_optional(&pressure_sensor),
+                read_optional(&ammonia_sensor),
+                read_optional(&co2_sensor),
+                read_optional(&feed_sensor),
+                read_optional(&water_sensor)
+            );
+
+            let nest_box_sensors: Vec<_> = eggs::nest_boxes_from_env()
+                .into_iter()
+                .map(|nest_box| {
+                    let counting = eggs::EggCounting::from_env(&nest_box).unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        std::process::exit(2);
+                    });
+                    let sensor = EggCountSensor::new(&egg_sensor_key, &nest_box)
+                        .with_backend(backend.clone())
+                        .with_counting(counting);
+                    (nest_box, sensor)
+                })
+                .collect();
+            let nest_box_readings: Vec<_> = join_all(
+                nest_box_sensors
+                    .iter()
+                    .map(|(nest_box, sensor)| async move { (nest_box, sensor.read().await) }),
+            )
+            .await;
+
+            let auxiliary_readings: Vec<_> = match &backend {
+                SensorBackend::Local(source) => source.auxiliary().await,
+                SensorBackend::Http | SensorBackend::Gateway(_) => Vec::new(),
+            }
+            .into_iter()
+            .map(|(id, reading)| {
+                let reading = reading.and_then(|r| filter.check(calibration.apply(r)));
+                (id, reading)
+            })
+            .collect();
+            if let Err(err) = filter.save(&ReadingFilter::path_from_env()) {
+                eprintln!("{err}");
+            }
+
+            alert_on_reading("Temperature", &temp_reading);
+            alert_on_reading("Humidity", &humidity_reading);
+            alert_on_reading("Motion", &motion_reading);
+            alert_on_reading("Egg presence", &egg_reading);
+            if let Some(reading) = &pressure_reading {
+                alert_on_reading("Pressure", reading);
+            }
+            if let Some(reading) = &ammonia_reading {
+                alert_on_reading("Ammonia", reading);
+                alert_thresholds("Ammonia", SensorKind::Ammonia, reading);
+            }
+            if let Some(reading) = &co2_reading {
+                alert_on_reading("CO2", reading);
+                alert_thresholds("CO2", SensorKind::Co2, reading);
+            }
+            if let Some(reading) = &feed_reading {
+                alert_on_reading("Feed level", reading);
+                alert_thresholds("Feed level", SensorKind::FeedLevel, reading);
+            }
+            if let Some(reading) = &water_reading {
+                alert_on_reading("Water level", reading);
+                alert_thresholds("Water level", SensorKind::WaterLevel, reading);
+            }
+            if let Some(reading) = &feed_reading {
+                let feeder = env::var("FEEDER_KEY").ok();
+                check_feeders(&[("feed", feeder.as_deref(), reading)]);
+            }
+            let thi_reading = derive_reading(SensorKind::Thi, &temp_reading, &humidity_reading);
+            let heat_index_reading =
+                derive_reading(SensorKind::HeatIndex, &temp_reading, &humidity_reading);
+            let dew_point_reading =
+                derive_reading(SensorKind::DewPoint, &temp_reading, &humidity_reading);
+            alert_thresholds("Coop", SensorKind::Thi, &thi_reading);
+
+            let temp = format_sensor_value(&temp_reading, &calibration);
+            let humidity = format_sensor_value(&humidity_reading, &calibration);
+            let motion = format_sensor_value(&motion_reading, &calibration);
+            let eggs = format_sensor_value(&egg_reading, &calibration);
 
-            data_cache.store("last_temp", &temp);
-            data_cache.store("last_humidity", &humidity);
-            data_cache.store("last_motion", &motion);
-            data_cache.store("last_egg_presence", &eggs);
+            cache_reading(&mut data_cache, "last_temp", &temp_reading);
+            cache_reading(&mut data_cache, "last_humidity", &humidity_reading);
+            cache_reading(&mut data_cache, "last_motion", &motion_reading);
+            cache_reading(&mut data_cache, "last_egg_presence", &egg_reading);
+            cache_reading(&mut data_cache, "last_thi", &thi_reading);
+            for (nest_box, reading) in &nest_box_readings {
+                alert_on_reading(&format!("Nest box {nest_box}"), reading);
+                cache_reading(&mut data_cache, &format!("last_eggs_{nest_box}"), reading);
+            }
 
             scheduler::run_scheduled_tasks(1, Duration::from_millis(5)).await;
 
             println!("Chicken Coop Status");
             println!("Egg presence: {eggs}");
-            println!("Temperature: {temp}C");
-            println!("Humidity: {humidity}%");
+            for (nest_box, reading) in &nest_box_readings {
+                println!(
+                    "Eggs in {nest_box}: {}",
+                    format_sensor_value(reading, &calibration)
+                );
+            }
+            println!("Temperature: {temp}");
+            println!("Humidity: {humidity}");
+            match thi_band(&thi_reading) {
+                Some(band) => println!(
+                    "Heat stress (THI): {} ({band})",
+                    format_sensor_value(&thi_reading, &calibration)
+                ),
+                None => println!("Heat stress (THI): unavailable"),
+            }
+            println!(
+                "Heat index: {}",
+                format_sensor_value(&heat_index_reading, &calibration)
+            );
+            println!(
+                "Dew point: {}",
+                format_sensor_value(&dew_point_reading, &calibration)
+            );
             println!("Motion detected: {motion}");
-            println!("Cached temp: {:?}", data_cache.retrieve("last_temp"));
+            if let Some(reading) = &pressure_reading {
+                println!("Pressure: {}", format_sensor_value(reading, &calibration));
+            }
+            if let Some(reading) = &ammonia_reading {
+                println!("Ammonia: {}", format_sensor_value(reading, &calibration));
+            }
+            if let Some(reading) = &co2_reading {
+                println!("CO2: {}", format_sensor_value(reading, &calibration));
+            }
+            if let (Some(reading), Some(sensor)) = (&feed_reading, &feed_sensor) {
+                println!("Feed: {}", format_level(reading, &sensor.geometry));
+            }
+            if let (Some(reading), Some(sensor)) = (&water_reading, &water_sensor) {
+                println!("Water: {}", format_level(reading, &sensor.geometry));
+            }
+            let numeric_ids = ["temperature", "humidity", "pressure", "ammonia", "co2"]
+                .into_iter()
+                .map(str::to_string)
+                .chain(auxiliary_readings.iter().map(|(id, _)| id.clone()));
+            for id in numeric_ids {
+                if let Some(unchanged) = filter.flatlined(&id) {
+                    let alert = alerts::Alert::new(&format!(
+                        "{id} value hasn't changed in {} hours",
+                        unchanged.as_secs() / 3600
+                    ));
+                    alert.send();
+                }
+            }
+            for (id, reading) in &auxiliary_readings {
+                alert_on_reading(id, reading);
+                println!("{id}: {}", format_sensor_value(reading, &calibration));
+            }
+            match data_cache.age("last_temp") {
+                Some(age) => println!(
+                    "Cached temp: {:?} ({}s old)",
+                    data_cache.retrieve("last_temp"),
+                    age.as_secs()
+                ),
+                None => println!("Cached temp: {:?}", data_cache.retrieve("last_temp")),
+            }
+            report_http_retries();
+        }
+        Some(Commands::Sensors {
+            action:
+                SensorCommands::Calibrate {
+                    sensor,
+                    reference,
+                    clear,
+                },
+        }) => {
+            let path = CalibrationConfig::path_from_env();
+            let mut calibration = load_calibration(&path);
+
+            if let Some(sensor_id) = &sensor {
+                if clear {
+                    calibration.sensors.remove(sensor_id);
+                } else if let Some(reference) = reference {
+                    let backend = SensorBackend::from_env().unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        std::process::exit(2);
+                    });
+                    let raw = match read_uncalibrated(&backend, sensor_id).await {
+                        Ok(Reading {
+                            kind,
+                            value: SensorValue::Numeric(raw),
+                            ..
+                        }) => {
+                            calibration.record_point(sensor_id, kind, raw, reference);
+                            raw
+                        }
+                        Ok(reading) => {
+                            eprintln!("{sensor_id} is not a numeric sensor ({reading})");
+                            std::process::exit(1);
+                        }
+                        Err(err) => {
+                            eprintln!("Failed to read {sensor_id}: {err}");
+                            std::process::exit(1);
+                        }
+                    };
+                    println!("Recorded {sensor_id}: raw {raw} -> reference {reference}");
+                } else {
+                    eprintln!("Pass --reference <value> to record a point, or --clear.");
+                    std::process::exit(2);
+                }
+                if let Err(err) = calibration.save(&path) {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
+
+            println!("Calibration file: {path}");
+            println!("Temperature unit: {:?}", calibration.temperature_unit);
+            if calibration.sensors.is_empty() {
+                println!("No sensors calibrated");
+            }
+            for (id, sensor_calibration) in &calibration.sensors {
+                println!("{id}: {sensor_calibration}");
+            }
+        }
+        Some(Commands::Sensors {
+            action: SensorCommands::Diagnostics,
+        }) => {
+            let filter = load_filter();
+            let diagnostics = filter.diagnostics();
+            if diagnostics.is_empty() {
+                println!("No rejected readings");
+            }
+            for diagnostic in diagnostics {
+                let age = UNIX_EPOCH
+                    .elapsed()
+                    .unwrap_or_default()
+                    .as_secs()
+                    .saturating_sub(diagnostic.at_ms / 1000);
+                println!(
+                    "{} ({}s ago): rejected {} - {}",
+                    diagnostic.sensor_id, age, diagnostic.value, diagnostic.reason
+                );
+            }
         }
         Some(Commands::Feed {
             action: FeedCommands::Now,
         }) => {
             let actuator_api_key = required_env("ACTUATOR_API_KEY");
             let feeder_key = required_env("FEEDER_KEY");
+            let mut feeder = actuators::FeederMotor::new(&feeder_key, &actuator_api_key);
+            feeder.portion_grams = portion_grams_from_env();
+            // The actuator client is blocking; keep it off the async runtime's worker.
+            let failed = tokio::task::block_in_place(|| {
+                println!("Activating feeder now...");
+                match feeder.activate() {
+                    Ok(reply) => {
+                        print_actuator_reply("Feeder", &reply);
+                        false
+                    }
+                    Err(err) => {
+                        eprintln!("Feeder command failed: {err}");
+                        true
+                    }
+                }
+            });
+            report_http_retries();
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Door { action }) => {
+            let actuator_api_key = required_env("ACTUATOR_API_KEY");
             let door_key = required_env("DOOR_KEY");
-            let feeder = actuators::FeederMotor::new(&feeder_key, &actuator_api_key);
             let door = actuators::CoopDoor::new(&door_key, &actuator_api_key);
-            println!("Activating feeder now...");
-            feeder.activate();
-            door.open();
-            door.close();
+            let (label, result) = tokio::task::block_in_place(|| match action {
+                DoorCommands::Open => ("Door open", door.open()),
+                DoorCommands::Close => ("Door close", door.close()),
+            });
+            report_http_retries();
+            match result {
+                Ok(reply) => print_actuator_reply(label, &reply),
+                Err(err) => {
+                    eprintln!("{label} command failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Feed {
+            action: FeedCommands::Calibrate { duration_ms, grams },
+        }) => {
+            let actuator_api_key = required_env("ACTUATOR_API_KEY");
+            let feeder_key = required_env("FEEDER_KEY");
+            let feeder = actuators::FeederMotor::new(&feeder_key, &actuator_api_key);
+            let saved = tokio::task::block_in_place(|| {
+                let grams = match grams {
+                    Some(grams) => grams,
+                    None => {
+                        println!("Running feeder for {duration_ms}ms; catch what it dispenses.");
+                        if let Err(err) = feeder.calibration_run(duration_ms) {
+                            eprintln!("Feeder calibration run failed: {err}");
+                            std::process::exit(1);
+                        }
+                        prompt_grams()
+                    }
+                };
+                feeder.save_calibration(duration_ms, grams)
+            });
+            report_http_retries();
+            match saved {
+                Ok(reply) => print_actuator_reply("Feeder", &reply),
+                Err(err) => {
+                    eprintln!("Saving feeder calibration failed: {err}");
+                    std::process::exit(1);
+                }
+            }
         }
         Some(Commands::Run {
             action:
@@ -93,6 +855,7 @@ async fn main() {
                     image,
                     camera_index,
                     frames,
+                    on_motion,
                 },
         }) => {
             let ai_key = required_env("AI_KEY");
@@ -141,7 +904,40 @@ async fn main() {
                         std::process::exit(1);
                     }
 
+                    #[cfg(all(feature = "pi-hw", target_os = "linux"))]
+                    let mut motion = if on_motion {
+                        match pir::PirMotionSource::from_env() {
+                            Ok(source) => {
+                                let events = source.subscribe();
+                                Some((source, events))
+                            }
+                            Err(err) => {
+                                eprintln!("{err}");
+                                std::process::exit(1);
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    #[cfg(not(all(feature = "pi-hw", target_os = "linux")))]
+                    if on_motion {
+                        eprintln!("--on-motion requires Linux and cargo feature `pi-hw`.");
+                        std::process::exit(1);
+                    }
+
                     for _ in 0..frames {
+                        #[cfg(all(feature = "pi-hw", target_os = "linux"))]
+                        if let Some((_, events)) = motion.as_mut() {
+                            match pir::wait_for_motion_start(events).await {
+                                Ok(event) => {
+                                    println!("Motion on GPIO {}; capturing frame", event.pin)
+                                }
+                                Err(err) => {
+                                    eprintln!("{err}");
+                                    std::process::exit(1);
+                                }
+                            }
+                        }
                         match session.capture_frame() {
                             Ok(frame) => match vision.classify_dynamic_image(frame.clone()) {
                                 Ok(result) => {
@@ -185,6 +981,7 @@ async fn main() {
                 {
                     let _ = frames;
                     let _ = camera_index;
+                    let _ = on_motion;
                     eprintln!("Webcam mode requires cargo feature `camera`.");
                     eprintln!("Use --image <path> or run with --features camera.");
                     std::process::exit(1);
@@ -204,10 +1001,45 @@ async fn main() {
                 std::process::exit(1);
             }
         }
+        Some(Commands::Serve {
+            action: ServeCommands::Sensors,
+        }) => {
+            let mut keys = HashMap::from([
+                (SensorKind::Temperature, required_env("TEMP_SENSOR_KEY")),
+                (SensorKind::Humidity, required_env("HUMIDITY_SENSOR_KEY")),
+                (SensorKind::Motion, required_env("MOTION_SENSOR_KEY")),
+                (SensorKind::EggPresence, required_env("EGG_SENSOR_KEY")),
+                (SensorKind::EggCount, required_env("EGG_SENSOR_KEY")),
+            ]);
+            // Derived heat-stress readings share the temperature key.
+            for kind in [SensorKind::Thi, SensorKind::HeatIndex, SensorKind::DewPoint] {
+                keys.insert(kind, keys[&SensorKind::Temperature].clone());
+            }
+            for (kind, name) in [
+                (SensorKind::Pressure, "PRESSURE_SENSOR_KEY"),
+                (SensorKind::Ammonia, "AMMONIA_SENSOR_KEY"),
+                (SensorKind::Co2, "CO2_SENSOR_KEY"),
+                (SensorKind::FeedLevel, "FEED_SENSOR_KEY"),
+                (SensorKind::WaterLevel, "WATER_SENSOR_KEY"),
+            ] {
+                if let Ok(key) = env::var(name) {
+                    keys.insert(kind, key);
+                }
+            }
+            let bind_addr = env_or_default("SENSOR_BIND_ADDR", "0.0.0.0:8080");
+            println!("Starting sensor gateway on {bind_addr}");
+            let batch_key = env::var("SENSOR_BATCH_KEY")
+                .ok()
+                .filter(|key| !key.is_empty());
+            if let Err(err) = sensor_server::run_sensor_server(&bind_addr, keys, batch_key).await {
+                eprintln!("Sensor gateway exited with error: {err}");
+                std::process::exit(1);
+            }
+        }
         None => {
             println!("Welcome to AI Chicken Coop! Use --help for commands.");
             println!(
-                "Try: `coop status`, `coop feed now`, `coop run ai-vision`, `coop serve actuators`"
+                "Try: `coop status`, `coop feed now`, `coop run ai-vision`, `coop serve actuators`, `coop serve sensors`, `coop sensors calibrate`"
             );
         }
     }
@@ -0,0 +1,873 @@
+use crate::actuators::ActuatorDriver;
+use crate::door::{DoorConfig, DoorController, DoorDirection, DoorHardware, DoorState};
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue, Unit};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+const MODBUS_MAP_FILE_DEFAULT: &str = "modbus.json";
+const MODBUS_TCP_ADDR_DEFAULT: &str = "127.0.0.1:502";
+const MODBUS_TIMEOUT: Duration = Duration::from_secs(1);
+
+const FN_READ_COILS: u8 = 0x01;
+const FN_READ_DISCRETE_INPUTS: u8 = 0x02;
+const FN_READ_HOLDING_REGISTERS: u8 = 0x03;
+const FN_READ_INPUT_REGISTERS: u8 = 0x04;
+const FN_WRITE_SINGLE_COIL: u8 = 0x05;
+const COIL_ON: u16 = 0xFF00;
+
+/// One request/response exchange with a Modbus device. PDUs start at the function code; the
+/// transport adds the unit id and its own framing.
+pub trait ModbusTransport: Send {
+    fn transact(&mut self, unit_id: u8, request: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// Modbus TCP, reconnecting on the next request after any I/O error.
+pub struct TcpTransport {
+    addr: String,
+    stream: Option<TcpStream>,
+    transaction_id: u16,
+}
+
+impl TcpTransport {
+    pub fn new(addr: &str) -> Self {
+        TcpTransport {
+            addr: addr.to_string(),
+            stream: None,
+            transaction_id: 0,
+        }
+    }
+
+    fn connect(&self) -> Result<TcpStream, String> {
+        let addr = self
+            .addr
+            .to_socket_addrs()
+            .map_err(|e| format!("invalid modbus address {}: {e}", self.addr))?
+            .next()
+            .ok_or_else(|| format!("invalid modbus address {}", self.addr))?;
+        let stream = TcpStream::connect_timeout(&addr, MODBUS_TIMEOUT)
+            .map_err(|e| format!("modbus device {} unreachable: {e}", self.addr))?;
+        stream
+            .set_read_timeout(Some(MODBUS_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(MODBUS_TIMEOUT)))
+            .map_err(|e| e.to_string())?;
+        Ok(stream)
+    }
+
+    fn exchange(&mut self, unit_id: u8, request: &[u8]) -> Result<Vec<u8>, String> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect()?);
+        }
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let transaction_id = self.transaction_id.to_be_bytes();
+        let stream = self.stream.as_mut().expect("modbus stream connected");
+
+        // MBAP header: transaction id, protocol 0, length of unit id + PDU, unit id.
+        let mut frame = Vec::with_capacity(7 + request.len());
+        frame.extend_from_slice(&transaction_id);
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&(request.len() as u16 + 1).to_be_bytes());
+        frame.push(unit_id);
+        frame.extend_from_slice(request);
+        stream.write_all(&frame).map_err(|e| e.to_string())?;
+
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).map_err(|e| e.to_string())?;
+        if header[..2] != transaction_id || header[6] != unit_id {
+            return Err("modbus response does not match the request".to_string());
+        }
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if !(2..=254).contains(&length) {
+            return Err(format!("invalid modbus response length {length}"));
+        }
+        let mut response = vec![0u8; length - 1];
+        stream
+            .read_exact(&mut response)
+            .map_err(|e| e.to_string())?;
+        Ok(response)
+    }
+}
+
+impl ModbusTransport for TcpTransport {
+    fn transact(&mut self, unit_id: u8, request: &[u8]) -> Result<Vec<u8>, String> {
+        let result = self.exchange(unit_id, request);
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+#[cfg(feature = "pi-hw")]
+mod rtu {
+    use super::ModbusTransport;
+    use crate::air_quality::SerialPort;
+
+    /// Modbus CRC-16 (polynomial 0xA001, initial 0xFFFF), sent low byte first.
+    pub fn crc16(bytes: &[u8]) -> u16 {
+        let mut crc = 0xFFFF_u16;
+        for byte in bytes {
+            crc ^= u16::from(*byte);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xA001
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        crc
+    }
+
+    /// Modbus RTU over a serial port, e.g. an RS-485 adapter on the Pi's UART.
+    pub struct RtuTransport<P: SerialPort> {
+        pub port: P,
+    }
+
+    impl<P: SerialPort> ModbusTransport for RtuTransport<P> {
+        fn transact(&mut self, unit_id: u8, request: &[u8]) -> Result<Vec<u8>, String> {
+            let mut frame = vec![unit_id];
+            frame.extend_from_slice(request);
+            frame.extend_from_slice(&crc16(&frame).to_le_bytes());
+            self.port.write(&frame)?;
+
+            // Unit id, function code and one more byte tell how long the rest of the frame is.
+            let mut response = vec![0u8; 3];
+            self.port.read_exact(&mut response)?;
+            let remaining = match response[1] {
+                function if function & 0x80 != 0 => 2,
+                1..=4 => response[2] as usize + 2,
+                _ => 5,
+            };
+            response.resize(3 + remaining, 0);
+            self.port.read_exact(&mut response[3..])?;
+
+            let (body, crc) = response.split_at(response.len() - 2);
+            if crc16(body).to_le_bytes() != crc {
+                return Err("modbus crc mismatch".to_string());
+            }
+            if body[0] != unit_id {
+                return Err(format!("modbus response from unit {}", body[0]));
+            }
+            Ok(body[1..].to_vec())
+        }
+    }
+}
+
+fn exception_message(code: u8) -> String {
+    let name = match code {
+        1 => "illegal function",
+        2 => "illegal data address",
+        3 => "illegal data value",
+        4 => "device failure",
+        6 => "device busy",
+        _ => "exception",
+    };
+    format!("modbus exception {code} ({name})")
+}
+
+/// The data bytes of a read response, checked against the expected byte count.
+fn payload(response: &[u8], expected: usize) -> Result<&[u8], String> {
+    match response.get(1) {
+        Some(&count) if count as usize == expected && response.len() == expected + 2 => {
+            Ok(&response[2..])
+        }
+        _ => Err("modbus response has an unexpected length".to_string()),
+    }
+}
+
+pub struct ModbusClient {
+    transport: Box<dyn ModbusTransport>,
+    unit_id: u8,
+}
+
+impl ModbusClient {
+    pub fn new(transport: Box<dyn ModbusTransport>, unit_id: u8) -> Self {
+        ModbusClient { transport, unit_id }
+    }
+
+    fn request(&mut self, request: &[u8]) -> Result<Vec<u8>, String> {
+        let response = self.transport.transact(self.unit_id, request)?;
+        match response.first() {
+            Some(&function) if function == request[0] => Ok(response),
+            Some(&function) if function == request[0] | 0x80 => {
+                Err(exception_message(response.get(1).copied().unwrap_or(0)))
+            }
+            _ => Err("unexpected modbus response".to_string()),
+        }
+    }
+
+    fn read(&mut self, function: u8, address: u16, count: u16) -> Result<Vec<u8>, String> {
+        let [address_high, address_low] = address.to_be_bytes();
+        let [count_high, count_low] = count.to_be_bytes();
+        self.request(&[function, address_high, address_low, count_high, count_low])
+    }
+
+    /// Coils or discrete inputs, packed least significant bit first.
+    pub fn read_bits(
+        &mut self,
+        function: u8,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<bool>, String> {
+        let response = self.read(function, address, count)?;
+        let bytes = payload(&response, (count as usize).div_ceil(8))?;
+        Ok((0..count as usize)
+            .map(|bit| bytes[bit / 8] >> (bit % 8) & 1 == 1)
+            .collect())
+    }
+
+    pub fn read_registers(
+        &mut self,
+        function: u8,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, String> {
+        let response = self.read(function, address, count)?;
+        let bytes = payload(&response, count as usize * 2)?;
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect())
+    }
+
+    pub fn write_coil(&mut self, address: u16, on: bool) -> Result<(), String> {
+        let [address_high, address_low] = address.to_be_bytes();
+        let [value_high, value_low] = if on { COIL_ON } else { 0 }.to_be_bytes();
+        let request = [
+            FN_WRITE_SINGLE_COIL,
+            address_high,
+            address_low,
+            value_high,
+            value_low,
+        ];
+        if self.request(&request)? != request {
+            return Err(format!("modbus device did not confirm coil {address}"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Table {
+    Coil,
+    Discrete,
+    Input,
+    Holding,
+}
+
+impl Table {
+    fn read_function(self) -> u8 {
+        match self {
+            Table::Coil => FN_READ_COILS,
+            Table::Discrete => FN_READ_DISCRETE_INPUTS,
+            Table::Input => FN_READ_INPUT_REGISTERS,
+            Table::Holding => FN_READ_HOLDING_REGISTERS,
+        }
+    }
+
+    fn is_bits(self) -> bool {
+        matches!(self, Table::Coil | Table::Discrete)
+    }
+}
+
+/// Register encoding; 32-bit types span two registers, high word first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+impl DataType {
+    fn registers(self) -> u16 {
+        match self {
+            DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+        }
+    }
+
+    fn decode(self, words: &[u16]) -> f32 {
+        let wide = || (u32::from(words[0]) << 16) | u32::from(words[1]);
+        match self {
+            DataType::U16 => f32::from(words[0]),
+            DataType::I16 => f32::from(words[0] as i16),
+            DataType::U32 => wide() as f32,
+            DataType::I32 => wide() as i32 as f32,
+            DataType::F32 => f32::from_bits(wide()),
+        }
+    }
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// One sensor in the register map: `value = raw * scale + offset`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterSpec {
+    /// Gateway name of the kind, e.g. `temperature`.
+    pub kind: String,
+    /// Names an extra reading, e.g. a second temperature input; registry sensors select it
+    /// with `probe`.
+    #[serde(default)]
+    pub probe: Option<String>,
+    pub table: Table,
+    pub address: u16,
+    #[serde(default)]
+    pub data_type: DataType,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub offset: f32,
+}
+
+/// Relay coils driven by the actuator backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoilMap {
+    pub feeder: Option<u16>,
+    pub door_open: Option<u16>,
+    pub door_close: Option<u16>,
+}
+
+/// Discrete inputs wired to the door's limit and obstruction switches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputMap {
+    pub door_top_limit: Option<u16>,
+    pub door_bottom_limit: Option<u16>,
+    pub door_obstruction: Option<u16>,
+}
+
+fn default_unit_id() -> u8 {
+    1
+}
+
+/// Register map of the I/O module, from `MODBUS_MAP_FILE`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModbusMap {
+    #[serde(default = "default_unit_id")]
+    pub unit_id: u8,
+    #[serde(default)]
+    pub sensors: Vec<RegisterSpec>,
+    #[serde(default)]
+    pub coils: CoilMap,
+    #[serde(default)]
+    pub inputs: InputMap,
+}
+
+impl ModbusMap {
+    /// Path from `MODBUS_MAP_FILE`, defaulting to `modbus.json` in the working directory.
+    pub fn path_from_env() -> String {
+        env::var("MODBUS_MAP_FILE").unwrap_or_else(|_| MODBUS_MAP_FILE_DEFAULT.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<ModbusMap, String> {
+        if !Path::new(path).exists() {
+            return Err(format!("modbus register map `{path}` not found"));
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read modbus register map `{path}`: {e}"))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("invalid modbus register map `{path}`: {e}"))
+    }
+
+    /// Sensors with their kinds resolved; numeric kinds must come from registers.
+    fn resolved_sensors(&self) -> Result<Vec<(SensorKind, RegisterSpec)>, String> {
+        self.sensors
+            .iter()
+            .map(|spec| {
+                let kind = SensorKind::from_name(&spec.kind)
+                    .ok_or_else(|| format!("modbus map has unknown kind `{}`", spec.kind))?;
+                if spec.table.is_bits() && kind.unit() != Unit::Bool {
+                    return Err(format!(
+                        "modbus {} at {} must be a register, not a {:?}",
+                        spec.kind, spec.address, spec.table
+                    ));
+                }
+                Ok((kind, spec.clone()))
+            })
+            .collect()
+    }
+}
+
+fn read_value(
+    client: &mut ModbusClient,
+    kind: SensorKind,
+    spec: &RegisterSpec,
+) -> Result<SensorValue, String> {
+    let function = spec.table.read_function();
+    if spec.table.is_bits() {
+        return Ok(SensorValue::Binary(
+            client.read_bits(function, spec.address, 1)?[0],
+        ));
+    }
+    let words = client.read_registers(function, spec.address, spec.data_type.registers())?;
+    let value = spec.data_type.decode(&words) * spec.scale + spec.offset;
+    Ok(if kind.unit() == Unit::Bool {
+        SensorValue::Binary(value != 0.0)
+    } else {
+        SensorValue::Numeric(value)
+    })
+}
+
+/// Serves the sensors in a Modbus register map from one I/O module.
+pub struct ModbusSensorSource {
+    client: Arc<Mutex<ModbusClient>>,
+    sensors: Vec<(SensorKind, RegisterSpec)>,
+}
+
+impl ModbusSensorSource {
+    pub fn new(client: ModbusClient, map: &ModbusMap) -> Result<Self, String> {
+        Ok(ModbusSensorSource {
+            client: Arc::new(Mutex::new(client)),
+            sensors: map.resolved_sensors()?,
+        })
+    }
+
+    /// Reads one register on the blocking pool, since a transaction waits on the socket or
+    /// serial line.
+    async fn read(
+        &self,
+        id: &str,
+        kind: SensorKind,
+        spec: &RegisterSpec,
+    ) -> Result<Reading, SensorError> {
+        let client = Arc::clone(&self.client);
+        let (id, spec) = (id.to_string(), spec.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut client = client
+                .lock()
+                .map_err(|_| SensorError::Source("modbus client lock poisoned".to_string()))?;
+            let value = read_value(&mut client, kind, &spec)
+                .map_err(|e| SensorError::Source(format!("modbus: {e}")))?;
+            Ok(Reading::new(&id, kind, value, SystemTime::now()))
+        })
+        .await
+        .map_err(|e| SensorError::Source(format!("modbus task failed: {e}")))?
+    }
+}
+
+#[async_trait]
+impl SensorSource for ModbusSensorSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let (_, spec) = self
+            .sensors
+            .iter()
+            .find(|(spec_kind, spec)| *spec_kind == kind && spec.probe.is_none())
+            .ok_or(SensorError::Unsupported(kind))?;
+        self.read(kind.as_str(), kind, spec).await
+    }
+
+    async fn auxiliary(&self) -> Vec<(String, Result<Reading, SensorError>)> {
+        let mut readings = Vec::new();
+        for (kind, spec) in &self.sensors {
+            if let Some(probe) = &spec.probe {
+                readings.push((probe.clone(), self.read(probe, *kind, spec).await));
+            }
+        }
+        readings
+    }
+}
+
+/// Drives feeder and door relays through coils on the I/O module.
+pub struct ModbusActuatorDriver {
+    client: ModbusClient,
+    coils: CoilMap,
+    inputs: InputMap,
+    door: DoorController,
+}
+
+impl ModbusActuatorDriver {
+    pub fn new(client: ModbusClient, map: &ModbusMap, door: DoorConfig) -> Self {
+        ModbusActuatorDriver {
+            client,
+            coils: map.coils,
+            inputs: map.inputs,
+            door: DoorController::new(door),
+        }
+    }
+}
+
+/// Door relays and switch inputs on the module, borrowed for one move.
+struct ModbusDoor<'a> {
+    client: &'a mut ModbusClient,
+    coils: CoilMap,
+    inputs: InputMap,
+    running: Option<(&'static str, u16)>,
+}
+
+impl<'a> ModbusDoor<'a> {
+    fn new(client: &'a mut ModbusClient, coils: CoilMap, inputs: InputMap) -> Self {
+        ModbusDoor {
+            client,
+            coils,
+            inputs,
+            running: None,
+        }
+    }
+
+    fn input(&mut self, address: Option<u16>) -> Result<Option<bool>, String> {
+        address
+            .map(|address| {
+                self.client
+                    .read_bits(FN_READ_DISCRETE_INPUTS, address, 1)
+                    .map(|bits| bits[0])
+            })
+            .transpose()
+    }
+}
+
+impl DoorHardware for ModbusDoor<'_> {
+    fn start(&mut self, direction: DoorDirection) -> Result<(), String> {
+        let (name, coil) = match direction {
+            DoorDirection::Open => ("door_open", self.coils.door_open),
+            DoorDirection::Close => ("door_close", self.coils.door_close),
+        };
+        let coil = coil.ok_or_else(|| format!("modbus map has no `{name}` coil"))?;
+        self.running = Some((name, coil));
+        self.client.write_coil(coil, true)
+    }
+
+    fn stop(&mut self) -> Result<(), String> {
+        match self.running.take() {
+            Some((name, coil)) => self
+                .client
+                .write_coil(coil, false)
+                .map_err(|e| format!("{name} coil {coil} may still be on: {e}")),
+            None => Ok(()),
+        }
+    }
+
+    fn limit_reached(&mut self, direction: DoorDirection) -> Result<Option<bool>, String> {
+        self.input(match direction {
+            DoorDirection::Open => self.inputs.door_top_limit,
+            DoorDirection::Close => self.inputs.door_bottom_limit,
+        })
+    }
+
+    fn obstructed(&mut self) -> Result<bool, String> {
+        Ok(self.input(self.inputs.door_obstruction)? == Some(true))
+    }
+}
+
+impl ActuatorDriver for ModbusActuatorDriver {
+    fn feeder_activate(&mut self, _device_key: &str, duration_ms: u64) -> Result<(), String> {
+        let coil = self
+            .coils
+            .feeder
+            .ok_or_else(|| "modbus map has no `feeder` coil".to_string())?;
+        self.client.write_coil(coil, true)?;
+        sleep(Duration::from_millis(duration_ms));
+        self.client
+            .write_coil(coil, false)
+            .map_err(|e| format!("feeder coil {coil} may still be on: {e}"))
+    }
+
+    fn door_open(&mut self, _device_key: &str) -> Result<(), String> {
+        let mut hardware = ModbusDoor::new(&mut self.client, self.coils, self.inputs);
+        self.door.open(&mut hardware).map(|_| ())
+    }
+
+    fn door_close(&mut self, _device_key: &str) -> Result<(), String> {
+        let mut hardware = ModbusDoor::new(&mut self.client, self.coils, self.inputs);
+        self.door.close(&mut hardware).map(|_| ())
+    }
+
+    fn door_state(&self) -> DoorState {
+        self.door.state()
+    }
+
+    fn restore_door_state(&mut self, state: DoorState) {
+        self.door.restore(state);
+    }
+}
+
+#[cfg(all(feature = "pi-hw", target_os = "linux"))]
+fn rtu_transport(path: &str) -> Result<Box<dyn ModbusTransport>, String> {
+    use crate::air_quality::rpi_uart::RppalSerialPort;
+    use crate::util::parse_env;
+
+    let baud = parse_env("MODBUS_RTU_BAUD", 9600)?;
+    Ok(Box::new(rtu::RtuTransport {
+        port: RppalSerialPort::with_baud(path, baud)?,
+    }))
+}
+
+#[cfg(not(all(feature = "pi-hw", target_os = "linux")))]
+fn rtu_transport(_path: &str) -> Result<Box<dyn ModbusTransport>, String> {
+    Err("MODBUS_RTU_UART requires Linux and cargo feature `pi-hw`".to_string())
+}
+
+/// RTU on `MODBUS_RTU_UART` when set, otherwise TCP to `MODBUS_TCP_ADDR`.
+fn client_from_env(map: &ModbusMap) -> Result<ModbusClient, String> {
+    let transport = match env::var("MODBUS_RTU_UART") {
+        Ok(path) => rtu_transport(&path)?,
+        Err(_) => {
+            let addr =
+                env::var("MODBUS_TCP_ADDR").unwrap_or_else(|_| MODBUS_TCP_ADDR_DEFAULT.to_string());
+            Box::new(TcpTransport::new(&addr))
+        }
+    };
+    Ok(ModbusClient::new(transport, map.unit_id))
+}
+
+pub fn modbus_source_from_env() -> Result<ModbusSensorSource, String> {
+    let map = ModbusMap::load(&ModbusMap::path_from_env())?;
+    ModbusSensorSource::new(client_from_env(&map)?, &map)
+}
+
+pub fn modbus_driver_from_env() -> Result<ModbusActuatorDriver, String> {
+    let map = ModbusMap::load(&ModbusMap::path_from_env())?;
+    Ok(ModbusActuatorDriver::new(
+        client_from_env(&map)?,
+        &map,
+        DoorConfig::from_env()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DoorConfig, ModbusActuatorDriver, ModbusClient, ModbusMap, ModbusSensorSource,
+        TcpTransport, FN_READ_HOLDING_REGISTERS,
+    };
+    use crate::actuators::ActuatorDriver;
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorKind, SensorValue};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    /// A Modbus TCP slave with function codes 1-5, for tests.
+    #[derive(Default)]
+    struct Simulator {
+        coils: Vec<bool>,
+        discrete: Vec<bool>,
+        input: Vec<u16>,
+        holding: Vec<u16>,
+        coil_writes: Vec<(u16, bool)>,
+    }
+
+    impl Simulator {
+        fn handle(&mut self, pdu: &[u8]) -> Vec<u8> {
+            let function = pdu[0];
+            let address = u16::from_be_bytes([pdu[1], pdu[2]]) as usize;
+            let value = u16::from_be_bytes([pdu[3], pdu[4]]);
+            let illegal_address = vec![function | 0x80, 2];
+            match function {
+                1 | 2 => {
+                    let bits = if function == 1 {
+                        &self.coils
+                    } else {
+                        &self.discrete
+                    };
+                    let Some(bits) = bits.get(address..address + value as usize) else {
+                        return illegal_address;
+                    };
+                    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+                    for (bit, on) in bits.iter().enumerate() {
+                        bytes[bit / 8] |= u8::from(*on) << (bit % 8);
+                    }
+                    let mut response = vec![function, bytes.len() as u8];
+                    response.extend(bytes);
+                    response
+                }
+                3 | 4 => {
+                    let registers = if function == 3 {
+                        &self.holding
+                    } else {
+                        &self.input
+                    };
+                    let Some(words) = registers.get(address..address + value as usize) else {
+                        return illegal_address;
+                    };
+                    let mut response = vec![function, (words.len() * 2) as u8];
+                    response.extend(words.iter().flat_map(|word| word.to_be_bytes()));
+                    response
+                }
+                5 if address < self.coils.len() => {
+                    self.coils[address] = value == 0xFF00;
+                    self.coil_writes.push((address as u16, value == 0xFF00));
+                    pdu.to_vec()
+                }
+                5 => illegal_address,
+                _ => vec![function | 0x80, 1],
+            }
+        }
+    }
+
+    fn spawn_simulator(simulator: Arc<Mutex<Simulator>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind simulator");
+        let addr = listener.local_addr().expect("local addr");
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.expect("accept");
+                let simulator = Arc::clone(&simulator);
+                thread::spawn(move || {
+                    let mut header = [0u8; 7];
+                    while stream.read_exact(&mut header).is_ok() {
+                        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+                        let mut pdu = vec![0u8; length - 1];
+                        stream.read_exact(&mut pdu).expect("read pdu");
+                        let response = simulator.lock().expect("simulator").handle(&pdu);
+                        let mut frame = header[..4].to_vec();
+                        frame.extend((response.len() as u16 + 1).to_be_bytes());
+                        frame.push(header[6]);
+                        frame.extend(response);
+                        stream.write_all(&frame).expect("write frame");
+                    }
+                });
+            }
+        });
+        addr.to_string()
+    }
+
+    fn client(addr: &str) -> ModbusClient {
+        ModbusClient::new(Box::new(TcpTransport::new(addr)), 1)
+    }
+
+    #[tokio::test]
+    async fn registers_map_to_scaled_readings() {
+        let outside = 18.25_f32.to_bits();
+        let simulator = Arc::new(Mutex::new(Simulator {
+            discrete: vec![false, true],
+            input: vec![(-35_i16) as u16],
+            holding: vec![612, (outside >> 16) as u16, outside as u16],
+            ..Simulator::default()
+        }));
+        let addr = spawn_simulator(simulator);
+        let map: ModbusMap = serde_json::from_str(
+            r#"{
+                "sensors": [
+                    { "kind": "temperature", "table": "input", "address": 0,
+                      "data_type": "i16", "scale": 0.1 },
+                    { "kind": "humidity", "table": "holding", "address": 0, "scale": 0.1 },
+                    { "kind": "motion", "table": "discrete", "address": 1 },
+                    { "kind": "temperature", "probe": "outside", "table": "holding",
+                      "address": 1, "data_type": "f32" }
+                ]
+            }"#,
+        )
+        .expect("register map");
+        let source = ModbusSensorSource::new(client(&addr), &map).expect("source");
+
+        let temperature = source.sample(SensorKind::Temperature).await;
+        assert_eq!(temperature.map(|r| r.value), Ok(SensorValue::Numeric(-3.5)));
+        let humidity = source.sample(SensorKind::Humidity).await.expect("humidity");
+        assert_eq!(humidity.value, SensorValue::Numeric(61.2));
+        let motion = source.sample(SensorKind::Motion).await;
+        assert_eq!(motion.map(|r| r.value), Ok(SensorValue::Binary(true)));
+        let auxiliary = source.auxiliary().await;
+        assert_eq!(auxiliary[0].0, "outside");
+        assert_eq!(
+            auxiliary[0].1.as_ref().map(|r| r.value),
+            Ok(SensorValue::Numeric(18.25))
+        );
+
+        let bad_address = client(&addr).read_registers(FN_READ_HOLDING_REGISTERS, 40, 1);
+        assert_eq!(
+            bad_address,
+            Err("modbus exception 2 (illegal data address)".to_string())
+        );
+        let numeric_coil: Result<ModbusMap, _> = serde_json::from_str(
+            r#"{ "sensors": [ { "kind": "co2", "table": "coil", "address": 0 } ] }"#,
+        );
+        assert!(ModbusSensorSource::new(client(&addr), &numeric_coil.expect("map")).is_err());
+    }
+
+    #[test]
+    fn coils_pulse_feeder_and_door_relays() {
+        let simulator = Arc::new(Mutex::new(Simulator {
+            coils: vec![false; 3],
+            discrete: vec![false],
+            ..Simulator::default()
+        }));
+        let addr = spawn_simulator(Arc::clone(&simulator));
+        let map: ModbusMap = serde_json::from_str(
+            r#"{ "coils": { "feeder": 0, "door_open": 1 }, "inputs": { "door_top_limit": 0 } }"#,
+        )
+        .expect("map");
+        let door = DoorConfig {
+            run_time: Duration::from_millis(5),
+            travel_timeout: Duration::from_millis(40),
+            poll_interval: Duration::from_millis(1),
+            retries: 0,
+            reverse_on_obstruction: false,
+        };
+        let mut driver = ModbusActuatorDriver::new(client(&addr), &map, door);
+
+        driver.feeder_activate("FEEDER", 5).expect("feeder");
+        let err = driver
+            .door_open("DOOR")
+            .expect_err("top limit never reached");
+        assert!(err.contains("did not reach open"), "{err}");
+        assert!(driver.door_close("DOOR").is_err());
+
+        simulator.lock().expect("simulator").discrete[0] = true;
+        driver.door_open("DOOR").expect("already open");
+
+        let simulator = simulator.lock().expect("simulator");
+        assert_eq!(
+            simulator.coil_writes,
+            vec![(0, true), (0, false), (1, true), (1, false)]
+        );
+        assert_eq!(simulator.coils, vec![false; 3]);
+    }
+
+    #[cfg(feature = "pi-hw")]
+    #[test]
+    fn rtu_frames_carry_a_crc() {
+        use super::rtu::{crc16, RtuTransport};
+        use crate::air_quality::SerialPort;
+
+        struct MockPort {
+            response: Vec<u8>,
+        }
+
+        impl SerialPort for MockPort {
+            fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+                // Read one holding register at 0 from unit 1, the canonical example frame.
+                assert_eq!(bytes, [0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]);
+                Ok(())
+            }
+
+            fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), String> {
+                let rest = self.response.split_off(buffer.len());
+                buffer.copy_from_slice(&self.response);
+                self.response = rest;
+                Ok(())
+            }
+        }
+
+        let mut response = vec![0x01, 0x03, 0x02, 0x00, 0xD7];
+        response.extend(crc16(&response).to_le_bytes());
+        let mut client = ModbusClient::new(
+            Box::new(RtuTransport {
+                port: MockPort { response },
+            }),
+            1,
+        );
+        assert_eq!(
+            client.read_registers(FN_READ_HOLDING_REGISTERS, 0, 1),
+            Ok(vec![215])
+        );
+    }
+}
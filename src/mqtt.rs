@@ -0,0 +1,322 @@
+use crate::sensor_sources::{parse_value, SensorSource};
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use crate::util::parse_env;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+
+const MQTT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+const MQTT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// How long `sample` waits for the first message on a topic before reporting a timeout.
+const FIRST_MESSAGE_WAIT: Duration = Duration::from_secs(2);
+
+fn topic_env(kind: SensorKind) -> &'static str {
+    match kind {
+        SensorKind::Temperature => "MQTT_TEMPERATURE_TOPIC",
+        SensorKind::Humidity => "MQTT_HUMIDITY_TOPIC",
+        SensorKind::Motion => "MQTT_MOTION_TOPIC",
+        SensorKind::EggPresence => "MQTT_EGGS_TOPIC",
+        SensorKind::Pressure => "MQTT_PRESSURE_TOPIC",
+        SensorKind::Ammonia => "MQTT_AMMONIA_TOPIC",
+        SensorKind::Co2 => "MQTT_CO2_TOPIC",
+        SensorKind::FeedLevel => "MQTT_FEED_TOPIC",
+        SensorKind::WaterLevel => "MQTT_WATER_TOPIC",
+        SensorKind::EggCount => "MQTT_EGG_COUNT_TOPIC",
+        SensorKind::Thi => "MQTT_THI_TOPIC",
+        SensorKind::HeatIndex => "MQTT_HEAT_INDEX_TOPIC",
+        SensorKind::DewPoint => "MQTT_DEW_POINT_TOPIC",
+    }
+}
+
+fn path_env(kind: SensorKind) -> &'static str {
+    match kind {
+        SensorKind::Temperature => "MQTT_TEMPERATURE_PATH",
+        SensorKind::Humidity => "MQTT_HUMIDITY_PATH",
+        SensorKind::Motion => "MQTT_MOTION_PATH",
+        SensorKind::EggPresence => "MQTT_EGGS_PATH",
+        SensorKind::Pressure => "MQTT_PRESSURE_PATH",
+        SensorKind::Ammonia => "MQTT_AMMONIA_PATH",
+        SensorKind::Co2 => "MQTT_CO2_PATH",
+        SensorKind::FeedLevel => "MQTT_FEED_PATH",
+        SensorKind::WaterLevel => "MQTT_WATER_PATH",
+        SensorKind::EggCount => "MQTT_EGG_COUNT_PATH",
+        SensorKind::Thi => "MQTT_THI_PATH",
+        SensorKind::HeatIndex => "MQTT_HEAT_INDEX_PATH",
+        SensorKind::DewPoint => "MQTT_DEW_POINT_PATH",
+    }
+}
+
+/// Topic filter for one sensor kind; without `path` the whole payload is the value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttBinding {
+    pub topic: String,
+    pub path: Option<String>,
+}
+
+/// Follows a dotted path such as `$.sensors.0.temp` into a JSON document.
+fn select_json<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let path = path.strip_prefix("$.").unwrap_or(path);
+    path.split('.')
+        .filter(|segment| !segment.is_empty() && *segment != "$")
+        .try_fold(value, |current, segment| match current {
+            serde_json::Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => current.get(segment),
+        })
+}
+
+fn decode_payload(
+    kind: SensorKind,
+    path: Option<&str>,
+    payload: &[u8],
+) -> Result<SensorValue, SensorError> {
+    let text = std::str::from_utf8(payload)
+        .map_err(|_| SensorError::Source(format!("non-utf8 payload for {}", kind.as_str())))?;
+    let Some(path) = path else {
+        return parse_value(kind, text);
+    };
+
+    let document: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| SensorError::MalformedJson(e.to_string()))?;
+    match select_json(&document, path) {
+        Some(serde_json::Value::String(raw)) => parse_value(kind, raw),
+        Some(serde_json::Value::Number(raw)) => parse_value(kind, &raw.to_string()),
+        Some(serde_json::Value::Bool(raw)) => Ok(SensorValue::Binary(*raw)),
+        Some(other) => Err(SensorError::MalformedJson(format!(
+            "`{path}` is not a scalar: {other}"
+        ))),
+        None => Err(SensorError::MalformedJson(format!(
+            "`{path}` missing from payload"
+        ))),
+    }
+}
+
+type LatestValues = Mutex<HashMap<SensorKind, (SensorValue, SystemTime)>>;
+
+/// Subscribes to one topic per sensor kind and keeps the last value with its receive time.
+pub struct MqttSensorSource {
+    bindings: HashMap<SensorKind, MqttBinding>,
+    latest: Arc<LatestValues>,
+    updated: Arc<Notify>,
+}
+
+impl MqttSensorSource {
+    fn new(bindings: HashMap<SensorKind, MqttBinding>) -> Self {
+        MqttSensorSource {
+            bindings,
+            latest: Arc::new(Mutex::new(HashMap::new())),
+            updated: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Connects to the broker and starts the subscription loop; needs a running tokio runtime.
+    pub fn from_env() -> Result<Self, String> {
+        let bindings: HashMap<SensorKind, MqttBinding> = SensorKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let topic = env::var(topic_env(kind)).ok().filter(|t| !t.is_empty())?;
+                let path = env::var(path_env(kind)).ok().filter(|p| !p.is_empty());
+                Some((kind, MqttBinding { topic, path }))
+            })
+            .collect();
+        if bindings.is_empty() {
+            return Err("SENSOR_BACKEND=mqtt needs at least one MQTT_<SENSOR>_TOPIC".to_string());
+        }
+
+        let host = env::var("MQTT_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+        let port = parse_env::<u16>("MQTT_PORT", 1883)?;
+        let client_id = env::var("MQTT_CLIENT_ID").unwrap_or_else(|_| "coop-sensors".to_string());
+        let mut options = MqttOptions::new(client_id, host, port);
+        options.set_keep_alive(MQTT_KEEP_ALIVE);
+        if let Ok(username) = env::var("MQTT_USERNAME") {
+            options.set_credentials(username, env::var("MQTT_PASSWORD").unwrap_or_default());
+        }
+
+        let source = MqttSensorSource::new(bindings);
+        let (client, eventloop) = AsyncClient::new(options, 16);
+        tokio::spawn(run_subscriptions(
+            client,
+            eventloop,
+            source.bindings.clone(),
+            Arc::clone(&source.latest),
+            Arc::clone(&source.updated),
+        ));
+        Ok(source)
+    }
+
+    fn latest(&self, kind: SensorKind) -> Result<Option<(SensorValue, SystemTime)>, SensorError> {
+        self.latest
+            .lock()
+            .map(|latest| latest.get(&kind).copied())
+            .map_err(|_| SensorError::Source("mqtt state lock poisoned".to_string()))
+    }
+}
+
+fn ingest(
+    bindings: &HashMap<SensorKind, MqttBinding>,
+    latest: &LatestValues,
+    updated: &Notify,
+    publish: &Publish,
+) {
+    let received_at = SystemTime::now();
+    for (kind, binding) in bindings {
+        if !rumqttc::matches(&publish.topic, &binding.topic) {
+            continue;
+        }
+        match decode_payload(*kind, binding.path.as_deref(), &publish.payload) {
+            Ok(value) => {
+                if let Ok(mut latest) = latest.lock() {
+                    latest.insert(*kind, (value, received_at));
+                }
+            }
+            Err(err) => eprintln!("Ignoring MQTT message on {}: {err}", publish.topic),
+        }
+    }
+    updated.notify_waiters();
+}
+
+async fn run_subscriptions(
+    client: AsyncClient,
+    mut eventloop: EventLoop,
+    bindings: HashMap<SensorKind, MqttBinding>,
+    latest: Arc<LatestValues>,
+    updated: Arc<Notify>,
+) {
+    loop {
+        match eventloop.poll().await {
+            // Subscriptions do not survive a clean-session reconnect, so renew them on every ConnAck.
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                for binding in bindings.values() {
+                    if let Err(err) = client.subscribe(&binding.topic, QoS::AtLeastOnce).await {
+                        eprintln!("MQTT subscribe to {} failed: {err}", binding.topic);
+                    }
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                ingest(&bindings, &latest, &updated, &publish);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("MQTT connection error: {err}");
+                tokio::time::sleep(MQTT_RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SensorSource for MqttSensorSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        if !self.bindings.contains_key(&kind) {
+            return Err(SensorError::Unsupported(kind));
+        }
+        let deadline = tokio::time::Instant::now() + FIRST_MESSAGE_WAIT;
+        loop {
+            // Register interest before checking so a message landing in between is not missed.
+            let updated = self.updated.notified();
+            if let Some((value, received_at)) = self.latest(kind)? {
+                return Ok(Reading::new(kind.as_str(), kind, value, received_at));
+            }
+            if tokio::time::timeout_at(deadline, updated).await.is_err() {
+                return Err(SensorError::Timeout);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_payload, ingest, MqttBinding, MqttSensorSource};
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+    use rumqttc::{Publish, QoS};
+    use std::collections::HashMap;
+
+    fn binding(topic: &str, path: Option<&str>) -> MqttBinding {
+        MqttBinding {
+            topic: topic.to_string(),
+            path: path.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn decodes_plain_and_json_payloads() {
+        assert_eq!(
+            decode_payload(SensorKind::Temperature, None, b" 21.5\n"),
+            Ok(SensorValue::Numeric(21.5))
+        );
+        assert_eq!(
+            decode_payload(SensorKind::Motion, None, b"ON"),
+            Ok(SensorValue::Binary(true))
+        );
+        let esp32 = br#"{"sensors":[{"temp":19.25,"hum":"61"}],"pir":false}"#;
+        assert_eq!(
+            decode_payload(SensorKind::Temperature, Some("$.sensors.0.temp"), esp32),
+            Ok(SensorValue::Numeric(19.25))
+        );
+        assert_eq!(
+            decode_payload(SensorKind::Humidity, Some("sensors.0.hum"), esp32),
+            Ok(SensorValue::Numeric(61.0))
+        );
+        assert_eq!(
+            decode_payload(SensorKind::Motion, Some("pir"), esp32),
+            Ok(SensorValue::Binary(false))
+        );
+        assert!(matches!(
+            decode_payload(SensorKind::Temperature, Some("sensors.1.temp"), esp32),
+            Err(SensorError::MalformedJson(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn keeps_last_value_per_matching_topic() {
+        let source = MqttSensorSource::new(HashMap::from([
+            (
+                SensorKind::Temperature,
+                binding("coop/+/climate", Some("t")),
+            ),
+            (SensorKind::EggPresence, binding("coop/nest/eggs", None)),
+        ]));
+
+        for (topic, payload) in [
+            ("coop/run/climate", r#"{"t":18}"#),
+            ("coop/nest/eggs", "yes"),
+            ("coop/nest/eggs", "garbage"),
+        ] {
+            let publish = Publish::new(topic, QoS::AtMostOnce, payload);
+            ingest(&source.bindings, &source.latest, &source.updated, &publish);
+        }
+
+        let temp = source.sample(SensorKind::Temperature).await.expect("temp");
+        assert_eq!(temp.value, SensorValue::Numeric(18.0));
+        let eggs = source.sample(SensorKind::EggPresence).await.expect("eggs");
+        assert_eq!(eggs.value, SensorValue::Binary(true));
+        assert_eq!(
+            source.sample(SensorKind::Motion).await,
+            Err(SensorError::Unsupported(SensorKind::Motion))
+        );
+    }
+
+    /// Needs a broker: `mosquitto -p 1883`, then `cargo test --features mqtt -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn reads_from_local_mosquitto() {
+        std::env::set_var("MQTT_CLIENT_ID", "coop-sensors-test");
+        std::env::set_var("MQTT_TEMPERATURE_TOPIC", "coop/test/temperature");
+        let source = MqttSensorSource::from_env().expect("source");
+
+        let options = rumqttc::MqttOptions::new("coop-sensors-test-pub", "127.0.0.1", 1883);
+        let (client, mut eventloop) = rumqttc::AsyncClient::new(options, 4);
+        client
+            .publish("coop/test/temperature", QoS::AtLeastOnce, true, "22.75")
+            .await
+            .expect("publish");
+        tokio::spawn(async move { while eventloop.poll().await.is_ok() {} });
+
+        let temp = source.sample(SensorKind::Temperature).await.expect("temp");
+        assert_eq!(temp.value, SensorValue::Numeric(22.75));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use crate::util::parse_env;
+use async_trait::async_trait;
+use rppal::gpio::{Gpio, InputPin, Level, Trigger};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+const MOTION_EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionEventKind {
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionEvent {
+    pub pin: u8,
+    pub kind: MotionEventKind,
+    pub at: SystemTime,
+}
+
+fn elapsed(from: SystemTime, to: SystemTime) -> Duration {
+    to.duration_since(from).unwrap_or(Duration::ZERO)
+}
+
+/// Debounced edge state for one PIR output.
+#[derive(Debug, Clone)]
+struct MotionTracker {
+    debounce: Duration,
+    /// Debounced state.
+    active: bool,
+    /// Raw pin level from the latest edge, and when it changed.
+    level: bool,
+    level_at: Option<SystemTime>,
+    last_edge: Option<SystemTime>,
+    last_seen: Option<SystemTime>,
+}
+
+impl MotionTracker {
+    fn new(debounce: Duration) -> Self {
+        MotionTracker {
+            debounce,
+            active: false,
+            level: false,
+            level_at: None,
+            last_edge: None,
+            last_seen: None,
+        }
+    }
+
+    /// Returns an event for accepted edges; repeats and bounces inside `debounce` are ignored.
+    fn on_level(&mut self, pin: u8, high: bool, at: SystemTime) -> Option<MotionEvent> {
+        if high != self.level {
+            self.level = high;
+            self.level_at = Some(at);
+        }
+        if high == self.active {
+            return None;
+        }
+        if let Some(last) = self.last_edge {
+            if elapsed(last, at) < self.debounce {
+                return None;
+            }
+        }
+        Some(self.accept(pin, high, at))
+    }
+
+    /// Accepts a raw level that arrived inside the debounce window but has held since, so an
+    /// edge that ends within `debounce` of the previous one is not lost.
+    fn settle(&mut self, pin: u8, now: SystemTime) -> Option<MotionEvent> {
+        let changed = self.level_at?;
+        if self.level == self.active || elapsed(changed, now) < self.debounce {
+            return None;
+        }
+        Some(self.accept(pin, self.level, changed))
+    }
+
+    fn accept(&mut self, pin: u8, high: bool, at: SystemTime) -> MotionEvent {
+        self.active = high;
+        self.last_edge = Some(at);
+        self.last_seen = Some(at);
+        let kind = if high {
+            MotionEventKind::Start
+        } else {
+            MotionEventKind::End
+        };
+        MotionEvent { pin, kind, at }
+    }
+
+    fn motion_within(&self, window: Duration, now: SystemTime) -> bool {
+        self.active
+            || self
+                .last_seen
+                .map(|seen| elapsed(seen, now) <= window)
+                .unwrap_or(false)
+    }
+}
+
+/// Interrupt-driven PIR motion: edges become events, `sample` reports recent motion.
+pub struct PirMotionSource {
+    trackers: Arc<Mutex<HashMap<u8, MotionTracker>>>,
+    events: broadcast::Sender<MotionEvent>,
+    window: Duration,
+    debounce: Duration,
+    _pins: Vec<InputPin>,
+}
+
+impl PirMotionSource {
+    fn new(window: Duration, debounce: Duration) -> Self {
+        let (events, _) = broadcast::channel(MOTION_EVENT_CAPACITY);
+        PirMotionSource {
+            trackers: Arc::new(Mutex::new(HashMap::new())),
+            events,
+            window,
+            debounce,
+            _pins: Vec::new(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let pins = env::var("PIR_GPIO_PINS").unwrap_or_else(|_| "4".to_string());
+        let debounce_ms = parse_env::<u64>("PIR_DEBOUNCE_MS", 50)?;
+        let window_secs = parse_env::<u64>("PIR_MOTION_WINDOW_SECS", 30)?;
+        let mut source = PirMotionSource::new(
+            Duration::from_secs(window_secs),
+            Duration::from_millis(debounce_ms),
+        );
+
+        let gpio = Gpio::new().map_err(|e| format!("gpio init failed: {e}"))?;
+        for pin in pins.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let pin = pin
+                .parse::<u8>()
+                .map_err(|_| format!("invalid value for PIR_GPIO_PINS: {pins}"))?;
+            let mut input = gpio
+                .get(pin)
+                .map_err(|e| format!("gpio pin {pin} unavailable: {e}"))?
+                .into_input_pulldown();
+            let trackers = Arc::clone(&source.trackers);
+            let events = source.events.clone();
+            let debounce = source.debounce;
+            input
+                .set_async_interrupt(Trigger::Both, move |level| {
+                    record_level(&trackers, &events, debounce, pin, level == Level::High);
+                })
+                .map_err(|e| format!("gpio pin {pin} interrupt setup failed: {e}"))?;
+            source._pins.push(input);
+        }
+        Ok(source)
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn subscribe(&self) -> broadcast::Receiver<MotionEvent> {
+        self.events.subscribe()
+    }
+}
+
+fn record_level(
+    trackers: &Mutex<HashMap<u8, MotionTracker>>,
+    events: &broadcast::Sender<MotionEvent>,
+    debounce: Duration,
+    pin: u8,
+    high: bool,
+) {
+    let accepted = match trackers.lock() {
+        Ok(mut trackers) => {
+            let tracker = trackers
+                .entry(pin)
+                .or_insert_with(|| MotionTracker::new(debounce));
+            let now = SystemTime::now();
+            [tracker.settle(pin, now), tracker.on_level(pin, high, now)]
+        }
+        Err(_) => [None, None],
+    };
+    // No subscribers is fine; the polled state is already updated.
+    for event in accepted.into_iter().flatten() {
+        let _ = events.send(event);
+    }
+}
+
+/// Waits for the next motion start, skipping end events and lag on a slow consumer.
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub async fn wait_for_motion_start(
+    events: &mut broadcast::Receiver<MotionEvent>,
+) -> Result<MotionEvent, String> {
+    loop {
+        match events.recv().await {
+            Ok(event) if event.kind == MotionEventKind::Start => return Ok(event),
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => {
+                return Err("motion event stream closed".to_string())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SensorSource for PirMotionSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        if kind != SensorKind::Motion {
+            return Err(SensorError::Unsupported(kind));
+        }
+        let now = SystemTime::now();
+        let mut settled = Vec::new();
+        let motion = {
+            let mut trackers = self
+                .trackers
+                .lock()
+                .map_err(|_| SensorError::Source("motion state lock poisoned".to_string()))?;
+            let mut motion = false;
+            for (pin, tracker) in trackers.iter_mut() {
+                settled.extend(tracker.settle(*pin, now));
+                motion |= tracker.motion_within(self.window, now);
+            }
+            motion
+        };
+        for event in settled {
+            let _ = self.events.send(event);
+        }
+        Ok(Reading::new(
+            kind.as_str(),
+            kind,
+            SensorValue::Binary(motion),
+            now,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        record_level, wait_for_motion_start, MotionEventKind, MotionTracker, PirMotionSource,
+    };
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{SensorKind, SensorValue};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn tracker_debounces_and_reports_recent_motion() {
+        let t0 = SystemTime::now() - Duration::from_secs(60);
+        let ms = Duration::from_millis;
+        let mut tracker = MotionTracker::new(ms(50));
+
+        let start = tracker.on_level(4, true, t0).expect("start");
+        assert_eq!(start.kind, MotionEventKind::Start);
+        assert_eq!(tracker.on_level(4, false, t0 + ms(10)), None);
+        assert_eq!(tracker.on_level(4, true, t0 + ms(20)), None);
+        let end = tracker.on_level(4, false, t0 + ms(2000)).expect("end");
+        assert_eq!(end.kind, MotionEventKind::End);
+        assert_eq!(end.at, t0 + ms(2000));
+
+        let now = t0 + Duration::from_secs(20);
+        assert!(tracker.motion_within(Duration::from_secs(30), now));
+        assert!(!tracker.motion_within(Duration::from_secs(10), now));
+    }
+
+    #[test]
+    fn short_pulse_inside_debounce_settles_low() {
+        let t0 = SystemTime::now() - Duration::from_secs(60);
+        let ms = Duration::from_millis;
+        let mut tracker = MotionTracker::new(ms(50));
+
+        assert!(tracker.on_level(4, true, t0).is_some());
+        assert_eq!(tracker.on_level(4, false, t0 + ms(10)), None);
+        assert_eq!(tracker.settle(4, t0 + ms(40)), None);
+        let end = tracker.settle(4, t0 + ms(100)).expect("settled end");
+        assert_eq!(end.kind, MotionEventKind::End);
+        assert_eq!(end.at, t0 + ms(10));
+        assert!(!tracker.motion_within(Duration::from_secs(1), t0 + Duration::from_secs(5)));
+
+        let start = tracker
+            .on_level(4, true, t0 + Duration::from_secs(5))
+            .expect("next start");
+        assert_eq!(start.kind, MotionEventKind::Start);
+    }
+
+    #[tokio::test]
+    async fn edges_are_streamed_and_polled() {
+        let source = PirMotionSource::new(Duration::from_secs(30), Duration::from_millis(50));
+        let mut events = source.subscribe();
+
+        let motion = source.sample(SensorKind::Motion).await.expect("motion");
+        assert_eq!(motion.value, SensorValue::Binary(false));
+
+        record_level(&source.trackers, &source.events, source.debounce, 23, true);
+        let event = wait_for_motion_start(&mut events).await.expect("event");
+        assert_eq!(event.pin, 23);
+
+        let motion = source.sample(SensorKind::Motion).await.expect("motion");
+        assert_eq!(motion.value, SensorValue::Binary(true));
+    }
+}
@@ -0,0 +1,391 @@
+use crate::util::parse_env;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const RETRY_ATTEMPTS_DEFAULT: u32 = 3;
+const RETRY_BASE_MS_DEFAULT: u64 = 200;
+const RETRY_MAX_MS_DEFAULT: u64 = 2000;
+const BREAKER_THRESHOLD_DEFAULT: u32 = 5;
+const BREAKER_COOLDOWN_SECS_DEFAULT: u64 = 30;
+
+/// Errors from an HTTP attempt that the retry loop knows how to classify.
+pub trait Retryable {
+    /// Whether another attempt could succeed, e.g. a timeout but not a rejected key.
+    fn is_retryable(&self) -> bool;
+
+    /// The error returned without trying while the endpoint's circuit is open.
+    fn circuit_open(endpoint: &str) -> Self;
+}
+
+/// Attempts, backoff and circuit breaker settings shared by the sensor and actuator clients.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: RETRY_ATTEMPTS_DEFAULT,
+            base_delay: Duration::from_millis(RETRY_BASE_MS_DEFAULT),
+            max_delay: Duration::from_millis(RETRY_MAX_MS_DEFAULT),
+            breaker_threshold: BREAKER_THRESHOLD_DEFAULT,
+            breaker_cooldown: Duration::from_secs(BREAKER_COOLDOWN_SECS_DEFAULT),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads `HTTP_RETRY_ATTEMPTS`, `HTTP_RETRY_BASE_MS`, `HTTP_RETRY_MAX_MS`,
+    /// `HTTP_BREAKER_THRESHOLD` and `HTTP_BREAKER_COOLDOWN_SECS`.
+    pub fn from_env() -> Result<RetryPolicy, String> {
+        let attempts = parse_env::<u64>("HTTP_RETRY_ATTEMPTS", RETRY_ATTEMPTS_DEFAULT.into())?;
+        let breaker_threshold =
+            parse_env::<u64>("HTTP_BREAKER_THRESHOLD", BREAKER_THRESHOLD_DEFAULT.into())?;
+        if attempts == 0 || breaker_threshold == 0 {
+            return Err(
+                "HTTP_RETRY_ATTEMPTS and HTTP_BREAKER_THRESHOLD must be at least 1".to_string(),
+            );
+        }
+        Ok(RetryPolicy {
+            attempts: attempts.min(u32::MAX.into()) as u32,
+            base_delay: Duration::from_millis(parse_env::<u64>(
+                "HTTP_RETRY_BASE_MS",
+                RETRY_BASE_MS_DEFAULT,
+            )?),
+            max_delay: Duration::from_millis(parse_env::<u64>(
+                "HTTP_RETRY_MAX_MS",
+                RETRY_MAX_MS_DEFAULT,
+            )?),
+            breaker_threshold: breaker_threshold.min(u32::MAX.into()) as u32,
+            breaker_cooldown: Duration::from_secs(parse_env::<u64>(
+                "HTTP_BREAKER_COOLDOWN_SECS",
+                BREAKER_COOLDOWN_SECS_DEFAULT,
+            )?),
+        })
+    }
+
+    /// The policy from the environment, read once; invalid settings fall back to the defaults.
+    pub fn shared() -> RetryPolicy {
+        static POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+        *POLICY.get_or_init(|| {
+            RetryPolicy::from_env().unwrap_or_else(|err| {
+                eprintln!("{err}");
+                RetryPolicy::default()
+            })
+        })
+    }
+
+    /// Exponential backoff with equal jitter: half the capped delay is fixed, half random, so
+    /// clients that failed together do not retry in lockstep.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(jitter_fraction())
+    }
+
+    /// Runs `attempt` with retries. Errors matching `expected` are answers the caller
+    /// anticipates, such as a 404 from probing an optional endpoint: they are returned at once
+    /// and not counted as failures.
+    pub async fn run_expecting<T, E, F, Fut>(
+        &self,
+        endpoint: &str,
+        expected: impl Fn(&E) -> bool,
+        mut attempt: F,
+    ) -> Result<T, E>
+    where
+        E: Retryable,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut retry = 0;
+        loop {
+            let probe = self.admit::<E>(endpoint)?;
+            let result = attempt().await;
+            let expected = matches!(&result, Err(err) if expected(err));
+            match self.settle(endpoint, retry, probe, result, expected) {
+                Settled::Done(result) => return result,
+                Settled::RetryAfter(delay) => tokio::time::sleep(delay).await,
+            }
+            retry += 1;
+        }
+    }
+
+    /// Blocking counterpart of [`RetryPolicy::run_expecting`] for the actuator client.
+    pub fn run_blocking<T, E, F>(&self, endpoint: &str, mut attempt: F) -> Result<T, E>
+    where
+        E: Retryable,
+        F: FnMut() -> Result<T, E>,
+    {
+        let mut retry = 0;
+        loop {
+            let probe = self.admit::<E>(endpoint)?;
+            match self.settle(endpoint, retry, probe, attempt(), false) {
+                Settled::Done(result) => return result,
+                Settled::RetryAfter(delay) => std::thread::sleep(delay),
+            }
+            retry += 1;
+        }
+    }
+
+    /// Fails fast while the endpoint's circuit is open. Once the cooldown passes the circuit is
+    /// half-open: one probe attempt is let through and everyone else is still refused until it
+    /// settles. Returns whether the admitted attempt is that probe.
+    fn admit<E: Retryable>(&self, endpoint: &str) -> Result<bool, E> {
+        let mut endpoints = endpoints().lock().expect("retry state poisoned");
+        let state = endpoints.entry(endpoint.to_string()).or_default();
+        let now = Instant::now();
+        let probe = match state.open_until {
+            None => false,
+            Some(until) if now < until => {
+                state.stats.rejected += 1;
+                return Err(E::circuit_open(endpoint));
+            }
+            // A probe that never settled, e.g. a dropped future, is given up after a cooldown.
+            Some(_)
+                if state
+                    .probe_started
+                    .is_some_and(|started| now.duration_since(started) < self.breaker_cooldown) =>
+            {
+                state.stats.rejected += 1;
+                return Err(E::circuit_open(endpoint));
+            }
+            Some(_) => {
+                state.probe_started = Some(now);
+                true
+            }
+        };
+        state.stats.attempts += 1;
+        Ok(probe)
+    }
+
+    fn settle<T, E: Retryable>(
+        &self,
+        endpoint: &str,
+        retry: u32,
+        probe: bool,
+        result: Result<T, E>,
+        expected: bool,
+    ) -> Settled<T, E> {
+        let mut endpoints = endpoints().lock().expect("retry state poisoned");
+        let state = endpoints.entry(endpoint.to_string()).or_default();
+        if probe {
+            state.probe_started = None;
+            // Any answer shows the endpoint is back; a transient failure reopens the circuit below.
+            if expected || !matches!(&result, Err(err) if err.is_retryable()) {
+                state.open_until = None;
+            }
+        }
+        let err = match result {
+            Ok(value) => {
+                state.consecutive_failures = 0;
+                return Settled::Done(Ok(value));
+            }
+            Err(err) if expected => {
+                state.consecutive_failures = 0;
+                return Settled::Done(Err(err));
+            }
+            Err(err) => err,
+        };
+        state.stats.failures += 1;
+        // An endpoint that answers with a permanent error is up, so it does not trip the breaker.
+        if !err.is_retryable() {
+            return Settled::Done(Err(err));
+        }
+        state.consecutive_failures += 1;
+        if probe || state.consecutive_failures >= self.breaker_threshold {
+            state.open_until = Some(Instant::now() + self.breaker_cooldown);
+            state.stats.opened += 1;
+            return Settled::Done(Err(err));
+        }
+        if retry + 1 >= self.attempts {
+            return Settled::Done(Err(err));
+        }
+        state.stats.retries += 1;
+        Settled::RetryAfter(self.backoff(retry))
+    }
+}
+
+enum Settled<T, E> {
+    Done(Result<T, E>),
+    RetryAfter(Duration),
+}
+
+fn jitter_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    hasher.write_u128(now.as_nanos());
+    hasher.finish() as f64 / u64::MAX as f64
+}
+
+/// Request counters for one endpoint since the process started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EndpointStats {
+    pub attempts: u64,
+    pub retries: u64,
+    pub failures: u64,
+    /// Calls refused without trying because the circuit was open.
+    pub rejected: u64,
+    /// Times the circuit opened.
+    pub opened: u64,
+}
+
+#[derive(Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    /// Set when the circuit opens; once passed, the circuit is half-open until a probe settles.
+    open_until: Option<Instant>,
+    probe_started: Option<Instant>,
+    stats: EndpointStats,
+}
+
+fn endpoints() -> &'static Mutex<HashMap<String, EndpointState>> {
+    static ENDPOINTS: OnceLock<Mutex<HashMap<String, EndpointState>>> = OnceLock::new();
+    ENDPOINTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Retry metrics per endpoint, keyed by URL.
+pub fn stats() -> BTreeMap<String, EndpointStats> {
+    endpoints()
+        .lock()
+        .expect("retry state poisoned")
+        .iter()
+        .map(|(endpoint, state)| (endpoint.clone(), state.stats))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stats, RetryPolicy, Retryable};
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq)]
+    enum TestError {
+        Flaky,
+        Rejected,
+        CircuitOpen,
+    }
+
+    impl Retryable for TestError {
+        fn is_retryable(&self) -> bool {
+            *self == TestError::Flaky
+        }
+
+        fn circuit_open(_endpoint: &str) -> Self {
+            TestError::CircuitOpen
+        }
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+            breaker_threshold: 4,
+            breaker_cooldown: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn retries_transient_errors_until_success() {
+        let policy = fast_policy();
+        let calls = Cell::new(0);
+        let result = policy.run_blocking("test://flaky", || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(TestError::Flaky)
+            } else {
+                Ok(calls.get())
+            }
+        });
+        assert_eq!(result, Ok(3));
+
+        let rejected = policy.run_blocking::<(), _, _>("test://rejected", || {
+            calls.set(calls.get() + 1);
+            Err(TestError::Rejected)
+        });
+        assert_eq!(rejected, Err(TestError::Rejected));
+        assert_eq!(calls.get(), 4);
+
+        let flaky = stats()["test://flaky"];
+        assert_eq!((flaky.attempts, flaky.retries, flaky.failures), (3, 2, 2));
+        assert!(policy.backoff(10) <= policy.max_delay);
+        assert!(policy.backoff(0) >= policy.base_delay / 2);
+    }
+
+    #[test]
+    fn breaker_opens_after_repeated_failures_and_recovers() {
+        let policy = fast_policy();
+        let endpoint = "test://down";
+        for _ in 0..2 {
+            let _ = policy.run_blocking::<(), _, _>(endpoint, || Err(TestError::Flaky));
+        }
+        assert_eq!(
+            policy.run_blocking(endpoint, || Ok::<_, TestError>(())),
+            Err(TestError::CircuitOpen)
+        );
+        assert_eq!(stats()[endpoint].opened, 1);
+        assert_eq!(stats()[endpoint].rejected, 1);
+
+        // A failed probe opens the circuit again straight away.
+        std::thread::sleep(Duration::from_millis(60));
+        let calls = Cell::new(0);
+        let probe = policy.run_blocking::<(), _, _>(endpoint, || {
+            calls.set(calls.get() + 1);
+            Err(TestError::Flaky)
+        });
+        assert_eq!((probe, calls.get()), (Err(TestError::Flaky), 1));
+        assert_eq!(stats()[endpoint].opened, 2);
+        assert_eq!(
+            policy.run_blocking(endpoint, || Ok::<_, TestError>(())),
+            Err(TestError::CircuitOpen)
+        );
+
+        // While the probe is in flight, other callers are still refused.
+        std::thread::sleep(Duration::from_millis(60));
+        let probe = policy.run_blocking(endpoint, || {
+            assert_eq!(
+                policy.run_blocking(endpoint, || Ok::<_, TestError>(())),
+                Err(TestError::CircuitOpen)
+            );
+            Ok::<_, TestError>(())
+        });
+        assert_eq!(probe, Ok(()));
+        assert_eq!(
+            policy.run_blocking(endpoint, || Ok::<_, TestError>(())),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn expected_answers_are_not_failures() {
+        let policy = fast_policy();
+        let endpoint = "test://probe";
+        let probe = policy
+            .run_expecting::<(), _, _, _>(
+                endpoint,
+                |err| *err == TestError::Rejected,
+                || async { Err(TestError::Rejected) },
+            )
+            .await;
+        assert_eq!(probe, Err(TestError::Rejected));
+        assert_eq!(
+            (stats()[endpoint].attempts, stats()[endpoint].failures),
+            (1, 0)
+        );
+    }
+}
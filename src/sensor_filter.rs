@@ -0,0 +1,344 @@
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use crate::util::{millis, parse_env};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const SENSOR_HISTORY_FILE_DEFAULT: &str = "sensor-history.json";
+const MEDIAN_WINDOW_DEFAULT: usize = 3;
+const FLATLINE_HOURS_DEFAULT: u64 = 6;
+/// Samples older than this are not smoothed into a new reading.
+const MEDIAN_MAX_AGE: Duration = Duration::from_secs(600);
+/// Changes smaller than this count as "unchanged" for flatline detection.
+const FLATLINE_EPSILON: f32 = 0.01;
+const MAX_DIAGNOSTICS: usize = 100;
+
+/// Physical limits for one sensor kind; anything outside is a wiring or probe fault.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlausibilityLimits {
+    pub min: f32,
+    pub max: f32,
+    pub max_change_per_min: f32,
+}
+
+impl PlausibilityLimits {
+    /// Coop-realistic ranges. 85C, the DS18B20 power-on value, is deliberately out of range.
+    pub fn for_kind(kind: SensorKind) -> Option<PlausibilityLimits> {
+        match kind {
+            SensorKind::Temperature => Some(PlausibilityLimits {
+                min: -40.0,
+                max: 60.0,
+                max_change_per_min: 5.0,
+            }),
+            SensorKind::Humidity => Some(PlausibilityLimits {
+                min: 0.0,
+                max: 100.0,
+                max_change_per_min: 20.0,
+            }),
+            SensorKind::Pressure => Some(PlausibilityLimits {
+                min: 870.0,
+                max: 1085.0,
+                max_change_per_min: 5.0,
+            }),
+            SensorKind::Ammonia => Some(PlausibilityLimits {
+                min: 0.0,
+                max: 500.0,
+                max_change_per_min: 50.0,
+            }),
+            SensorKind::Co2 => Some(PlausibilityLimits {
+                min: 300.0,
+                max: 10_000.0,
+                max_change_per_min: 1000.0,
+            }),
+            // Refills jump straight to full, so levels skip range and rate checks.
+            SensorKind::Motion
+            | SensorKind::EggPresence
+            | SensorKind::FeedLevel
+            | SensorKind::WaterLevel
+            | SensorKind::EggCount => None,
+            // Derived from already-filtered temperature and humidity.
+            SensorKind::Thi | SensorKind::HeatIndex | SensorKind::DewPoint => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Sample {
+    value: f32,
+    at_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct SensorTrack {
+    /// Accepted raw values, newest last, at most the median window long.
+    recent: Vec<Sample>,
+    /// The value and time the sensor last moved by more than `FLATLINE_EPSILON`.
+    last_change: Option<Sample>,
+}
+
+/// A rejected reading, kept so a flaky probe can be diagnosed later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub sensor_id: String,
+    pub value: f32,
+    pub at_ms: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct FilterHistory {
+    tracks: BTreeMap<String, SensorTrack>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_by(f32::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Plausibility stage for numeric readings: range and rate-of-change rejection, median-of-N
+/// smoothing and flatline tracking. History is kept across `coop status` runs in a JSON file.
+pub struct ReadingFilter {
+    median_window: usize,
+    flatline_after: Duration,
+    history: Mutex<FilterHistory>,
+}
+
+impl ReadingFilter {
+    pub fn new(median_window: usize, flatline_after: Duration) -> Self {
+        ReadingFilter {
+            median_window: median_window.max(1),
+            flatline_after,
+            history: Mutex::new(FilterHistory::default()),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let median_window = parse_env("SENSOR_MEDIAN_WINDOW", MEDIAN_WINDOW_DEFAULT)?;
+        let flatline_hours = parse_env("SENSOR_FLATLINE_HOURS", FLATLINE_HOURS_DEFAULT)?;
+        let filter = ReadingFilter::new(median_window, Duration::from_secs(flatline_hours * 3600));
+        filter.load(&Self::path_from_env())?;
+        Ok(filter)
+    }
+
+    pub fn path_from_env() -> String {
+        env::var("SENSOR_HISTORY_FILE").unwrap_or_else(|_| SENSOR_HISTORY_FILE_DEFAULT.to_string())
+    }
+
+    /// Replaces the in-memory history with `path`; a missing file leaves it empty.
+    pub fn load(&self, path: &str) -> Result<(), String> {
+        if !Path::new(path).exists() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read sensor history `{path}`: {e}"))?;
+        let history: FilterHistory = serde_json::from_str(&content)
+            .map_err(|e| format!("invalid sensor history `{path}`: {e}"))?;
+        if let Ok(mut current) = self.history.lock() {
+            *current = history;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let history = self
+            .history
+            .lock()
+            .map_err(|_| "sensor history lock poisoned".to_string())?;
+        let content = serde_json::to_string_pretty(&*history)
+            .map_err(|e| format!("failed to encode sensor history: {e}"))?;
+        std::fs::write(path, content + "\n")
+            .map_err(|e| format!("failed to write sensor history `{path}`: {e}"))
+    }
+
+    /// Passes plausible readings on (smoothed), rejecting and recording implausible ones.
+    pub fn check(&self, mut reading: Reading) -> Result<Reading, SensorError> {
+        let (SensorValue::Numeric(value), Some(limits)) =
+            (reading.value, PlausibilityLimits::for_kind(reading.kind))
+        else {
+            return Ok(reading);
+        };
+        let mut history = self
+            .history
+            .lock()
+            .map_err(|_| SensorError::Source("sensor history lock poisoned".to_string()))?;
+        let sample = Sample {
+            value,
+            at_ms: millis(reading.captured_at),
+        };
+        let track = history.tracks.entry(reading.sensor_id.clone()).or_default();
+
+        let rejection = if !value.is_finite() || value < limits.min || value > limits.max {
+            Some(format!(
+                "{value} outside {}..{} for {}",
+                limits.min,
+                limits.max,
+                reading.kind.as_str()
+            ))
+        } else {
+            track.recent.last().and_then(|previous| {
+                let minutes = sample.at_ms.checked_sub(previous.at_ms)? as f32 / 60_000.0;
+                if minutes <= 0.0 {
+                    return None;
+                }
+                let rate = (value - previous.value).abs() / minutes;
+                (rate > limits.max_change_per_min).then(|| {
+                    format!(
+                        "jumped from {} to {value} ({rate:.1}/min, limit {}/min)",
+                        previous.value, limits.max_change_per_min
+                    )
+                })
+            })
+        };
+        if let Some(reason) = rejection {
+            history.diagnostics.push(Diagnostic {
+                sensor_id: reading.sensor_id.clone(),
+                value,
+                at_ms: sample.at_ms,
+                reason: reason.clone(),
+            });
+            let excess = history.diagnostics.len().saturating_sub(MAX_DIAGNOSTICS);
+            history.diagnostics.drain(..excess);
+            return Err(SensorError::Implausible(reason));
+        }
+
+        match track.last_change {
+            Some(last) if (value - last.value).abs() < FLATLINE_EPSILON => {}
+            _ => track.last_change = Some(sample),
+        }
+        track.recent.push(sample);
+        let excess = track.recent.len().saturating_sub(self.median_window);
+        track.recent.drain(..excess);
+
+        let oldest = sample
+            .at_ms
+            .saturating_sub(MEDIAN_MAX_AGE.as_millis() as u64);
+        let mut window: Vec<f32> = track
+            .recent
+            .iter()
+            .filter(|s| s.at_ms >= oldest)
+            .map(|s| s.value)
+            .collect();
+        reading.value = SensorValue::Numeric(median(&mut window));
+        Ok(reading)
+    }
+
+    /// How long `sensor_id` has reported the same value, once that exceeds the flatline limit.
+    pub fn flatlined(&self, sensor_id: &str) -> Option<Duration> {
+        let history = self.history.lock().ok()?;
+        let track = history.tracks.get(sensor_id)?;
+        let (last_change, latest) = (track.last_change?, track.recent.last()?);
+        let unchanged = Duration::from_millis(latest.at_ms.saturating_sub(last_change.at_ms));
+        (unchanged >= self.flatline_after).then_some(unchanged)
+    }
+
+    /// Recorded rejections, oldest first.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.history
+            .lock()
+            .map(|history| history.diagnostics.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadingFilter;
+    use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+    use std::time::{Duration, SystemTime};
+
+    fn temperature(id: &str, value: f32, at: SystemTime) -> Reading {
+        Reading::new(id, SensorKind::Temperature, SensorValue::Numeric(value), at)
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_jumps_as_diagnostics() {
+        let filter = ReadingFilter::new(1, Duration::from_secs(6 * 3600));
+        let t0 = SystemTime::now() - Duration::from_secs(3600);
+        let minute = Duration::from_secs(60);
+
+        assert!(filter.check(temperature("nest_box", 21.0, t0)).is_ok());
+        assert!(matches!(
+            filter.check(temperature("nest_box", 85.0, t0 + minute)),
+            Err(SensorError::Implausible(_))
+        ));
+        assert!(matches!(
+            filter.check(temperature("nest_box", 35.0, t0 + minute)),
+            Err(SensorError::Implausible(_))
+        ));
+        assert!(filter
+            .check(temperature("nest_box", 35.0, t0 + minute * 10))
+            .is_ok());
+
+        let diagnostics = filter.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].value, 85.0);
+        assert!(diagnostics[1].reason.contains("jumped"));
+
+        let motion = Reading::new("motion", SensorKind::Motion, SensorValue::Binary(true), t0);
+        assert_eq!(filter.check(motion.clone()), Ok(motion));
+    }
+
+    #[test]
+    fn smooths_with_median_and_detects_flatline() {
+        let filter = ReadingFilter::new(3, Duration::from_secs(6 * 3600));
+        let t0 = SystemTime::now() - Duration::from_secs(86_400);
+        let secs = Duration::from_secs;
+
+        let values = [20.0, 20.4, 20.2];
+        let smoothed: Vec<SensorValue> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                filter
+                    .check(temperature("temperature", *v, t0 + secs(i as u64 * 60)))
+                    .expect("accepted")
+                    .value
+            })
+            .collect();
+        assert_eq!(smoothed[2], SensorValue::Numeric(20.2));
+
+        assert_eq!(filter.flatlined("temperature"), None);
+        for hour in 1..=7 {
+            filter
+                .check(temperature("temperature", 20.2, t0 + secs(hour * 3600)))
+                .expect("accepted");
+        }
+        assert!(filter.flatlined("temperature").expect("flatline") >= secs(6 * 3600));
+    }
+
+    #[test]
+    fn history_survives_a_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("coop-sensor-history-{}.json", std::process::id()));
+        let path = path.to_str().expect("utf8 temp path");
+        let t0 = SystemTime::now() - Duration::from_secs(120);
+
+        let first = ReadingFilter::new(3, Duration::from_secs(3600));
+        first
+            .check(temperature("temperature", 20.0, t0))
+            .expect("accepted");
+        first.save(path).expect("save");
+
+        let second = ReadingFilter::new(3, Duration::from_secs(3600));
+        second.load(path).expect("load");
+        let _ = std::fs::remove_file(path);
+        assert!(second
+            .check(temperature(
+                "temperature",
+                40.0,
+                t0 + Duration::from_secs(60)
+            ))
+            .is_err());
+    }
+}
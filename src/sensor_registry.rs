@@ -0,0 +1,516 @@
+use crate::calibration::{Calibration, CalibrationConfig};
+use crate::eggs::EggCounting;
+use crate::heat_stress::derive_reading;
+use crate::levels::{ContainerGeometry, GeometryOverrides};
+use crate::sensor_filter::ReadingFilter;
+use crate::sensors::{read_path, Reading, Sensor, SensorBackend, SensorError, SensorKind};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+
+const SENSOR_REGISTRY_FILE_DEFAULT: &str = "sensors.json";
+const DEFAULT_ZONE: &str = "coop";
+
+/// One declared sensor in the registry file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SensorSpec {
+    pub id: String,
+    /// Gateway name of the kind, e.g. `temperature` or `egg-count`.
+    pub kind: String,
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// `http`, a gateway URL such as `http://coop2.local:8080`, or a local backend like
+    /// `ds18b20`; defaults to `SENSOR_BACKEND`.
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Env var holding the key, so secrets can stay out of the registry file.
+    #[serde(default)]
+    pub key_env: Option<String>,
+    /// Gateway path; defaults to `sensors/<kind>` (`sensors/eggs/<probe>` for egg counts).
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Named reading on a local source: a DS18B20 probe name or a nest box.
+    #[serde(default)]
+    pub probe: Option<String>,
+    /// Container shape of a feed or water sensor, over the `FEED_*`/`WATER_*` variables.
+    #[serde(default)]
+    pub geometry: Option<GeometryOverrides>,
+    /// Empty weight of an egg-count scale, over `NEST_BOX_<BOX>_TARE_KG`.
+    #[serde(default)]
+    pub tare_kg: Option<f32>,
+    /// Egg weight of an egg-count scale, over `EGG_WEIGHT_G`.
+    #[serde(default)]
+    pub egg_weight_g: Option<f32>,
+    /// Device key of the feeder filling a feed sensor's hopper, whose runs the level is checked
+    /// against; defaults to `FEEDER_KEY`.
+    #[serde(default)]
+    pub feeder: Option<String>,
+}
+
+impl SensorSpec {
+    pub fn kind(&self) -> Result<SensorKind, String> {
+        SensorKind::from_name(&self.kind)
+            .ok_or_else(|| format!("sensor `{}` has unknown kind `{}`", self.id, self.kind))
+    }
+
+    pub fn zone(&self) -> &str {
+        self.zone.as_deref().unwrap_or(DEFAULT_ZONE)
+    }
+
+    fn key(&self) -> Result<String, String> {
+        match (&self.key, &self.key_env) {
+            (Some(key), _) => Ok(key.clone()),
+            (None, Some(name)) => env::var(name)
+                .map_err(|_| format!("sensor `{}` needs environment variable {name}", self.id)),
+            (None, None) => Ok(String::new()),
+        }
+    }
+
+    fn path(&self, kind: SensorKind) -> String {
+        match (&self.path, &self.probe) {
+            (Some(path), _) => path.trim_start_matches('/').to_string(),
+            (None, Some(probe)) if kind == SensorKind::EggCount => {
+                format!("sensors/eggs/{probe}")
+            }
+            _ => format!("sensors/{}", kind.as_str()),
+        }
+    }
+}
+
+/// Sensors declared in `SENSOR_REGISTRY_FILE`, so any number of instances can be read by id.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorRegistry {
+    pub sensors: Vec<SensorSpec>,
+}
+
+impl SensorRegistry {
+    /// Path from `SENSOR_REGISTRY_FILE`, defaulting to `sensors.json` in the working directory.
+    pub fn path_from_env() -> String {
+        env::var("SENSOR_REGISTRY_FILE")
+            .unwrap_or_else(|_| SENSOR_REGISTRY_FILE_DEFAULT.to_string())
+    }
+
+    /// Loads and validates `path`; a missing file is an empty registry.
+    pub fn load(path: &str) -> Result<SensorRegistry, String> {
+        if !Path::new(path).exists() {
+            return Ok(SensorRegistry::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read sensor registry `{path}`: {e}"))?;
+        let registry: SensorRegistry = serde_json::from_str(&content)
+            .map_err(|e| format!("invalid sensor registry `{path}`: {e}"))?;
+        registry.validate()?;
+        Ok(registry)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut seen = HashSet::new();
+        for spec in &self.sensors {
+            if spec.id.is_empty() {
+                return Err("sensor registry has a sensor without an id".to_string());
+            }
+            if !seen.insert(spec.id.as_str()) {
+                return Err(format!("sensor id `{}` is declared twice", spec.id));
+            }
+            let kind = spec.kind()?;
+            if spec.geometry.is_some()
+                && !matches!(kind, SensorKind::FeedLevel | SensorKind::WaterLevel)
+            {
+                return Err(format!(
+                    "sensor `{}` has a `geometry` but is not a feed or water level",
+                    spec.id
+                ));
+            }
+            if spec.feeder.is_some() && kind != SensorKind::FeedLevel {
+                return Err(format!(
+                    "sensor `{}` has a `feeder` but is not a feed level",
+                    spec.id
+                ));
+            }
+            if (spec.tare_kg.is_some() || spec.egg_weight_g.is_some())
+                && kind != SensorKind::EggCount
+            {
+                return Err(format!(
+                    "sensor `{}` has `tare_kg` or `egg_weight_g` but is not an egg count",
+                    spec.id
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty()
+    }
+
+    pub fn spec(&self, id: &str) -> Option<&SensorSpec> {
+        self.sensors.iter().find(|spec| spec.id == id)
+    }
+
+    /// Builds every declared sensor; sensors naming the same local backend share one source.
+    pub fn build(
+        &self,
+        calibration: &CalibrationConfig,
+        filter: Option<Arc<ReadingFilter>>,
+    ) -> Result<Vec<RegisteredSensor>, String> {
+        let default_source = env::var("SENSOR_BACKEND").unwrap_or_else(|_| "http".to_string());
+        let mut backends: HashMap<String, SensorBackend> = HashMap::new();
+        self.sensors
+            .iter()
+            .map(|spec| {
+                let source = spec
+                    .source
+                    .clone()
+                    .unwrap_or_else(|| default_source.clone());
+                let backend = match backends.get(&source) {
+                    Some(backend) => backend.clone(),
+                    None => {
+                        let backend = SensorBackend::from_name(&source)?;
+                        backends.insert(source, backend.clone());
+                        backend
+                    }
+                };
+                RegisteredSensor::new(
+                    spec,
+                    backend,
+                    calibration.for_sensor(&spec.id),
+                    filter.clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Builds the sensor declared as `id` without calibration, filtering or level conversion,
+    /// so calibration points pair with the raw value.
+    pub fn raw_sensor(&self, id: &str) -> Result<Option<RegisteredSensor>, String> {
+        let Some(spec) = self.spec(id) else {
+            return Ok(None);
+        };
+        let backend = match &spec.source {
+            Some(source) => SensorBackend::from_name(source)?,
+            None => SensorBackend::from_env()?,
+        };
+        let mut sensor = RegisteredSensor::new(spec, backend, Calibration::default(), None)?;
+        sensor.geometry = None;
+        Ok(Some(sensor))
+    }
+}
+
+/// A registry sensor: reads its kind from its source and runs the kind's conversions.
+pub struct RegisteredSensor {
+    pub id: String,
+    pub kind: SensorKind,
+    pub zone: String,
+    key: String,
+    path: String,
+    probe: Option<String>,
+    backend: SensorBackend,
+    calibration: Calibration,
+    filter: Option<Arc<ReadingFilter>>,
+    /// Container shape for feed and water levels.
+    pub geometry: Option<ContainerGeometry>,
+    /// Feeder filling a feed level's hopper.
+    pub feeder: Option<String>,
+    counting: EggCounting,
+}
+
+impl RegisteredSensor {
+    fn new(
+        spec: &SensorSpec,
+        backend: SensorBackend,
+        calibration: Calibration,
+        filter: Option<Arc<ReadingFilter>>,
+    ) -> Result<RegisteredSensor, String> {
+        let kind = spec.kind()?;
+        let key = spec.key()?;
+        if key.is_empty() && !matches!(backend, SensorBackend::Local(_)) {
+            return Err(format!(
+                "sensor `{}` reads from a gateway and needs `key` or `key_env`",
+                spec.id
+            ));
+        }
+        let geometry = match (kind, &spec.geometry) {
+            (SensorKind::FeedLevel | SensorKind::WaterLevel, None) => {
+                Some(ContainerGeometry::from_env(kind)?)
+            }
+            (SensorKind::FeedLevel | SensorKind::WaterLevel, Some(overrides)) => {
+                Some(ContainerGeometry::from_env(kind)?.with_overrides(overrides, &spec.id)?)
+            }
+            _ => None,
+        };
+        let counting = match kind {
+            SensorKind::EggCount => {
+                EggCounting::from_env(spec.probe.as_deref().unwrap_or(&spec.id))?
+                    .with_overrides(spec.tare_kg, spec.egg_weight_g)?
+            }
+            _ => EggCounting::Count,
+        };
+        Ok(RegisteredSensor {
+            id: spec.id.clone(),
+            kind,
+            zone: spec.zone().to_string(),
+            path: spec.path(kind),
+            probe: spec.probe.clone(),
+            key,
+            backend,
+            calibration,
+            filter,
+            geometry,
+            feeder: match kind {
+                SensorKind::FeedLevel => {
+                    spec.feeder.clone().or_else(|| env::var("FEEDER_KEY").ok())
+                }
+                _ => None,
+            },
+            counting,
+        })
+    }
+
+    async fn read_raw(&self) -> Result<Reading, SensorError> {
+        match (&self.backend, &self.probe) {
+            (SensorBackend::Local(source), Some(probe)) if self.kind == SensorKind::EggCount => {
+                source.nest_box(probe).await
+            }
+            (SensorBackend::Local(source), Some(probe)) => source
+                .auxiliary()
+                .await
+                .into_iter()
+                .find(|(name, _)| name == probe)
+                .map(|(_, reading)| reading)
+                .unwrap_or_else(|| Err(SensorError::Unsupported(self.kind))),
+            (backend, _) => read_path(backend, &self.id, self.kind, &self.key, &self.path).await,
+        }
+    }
+}
+
+#[async_trait]
+impl Sensor for RegisteredSensor {
+    async fn read(&self) -> Result<Reading, SensorError> {
+        let mut reading = self.read_raw().await?;
+        reading.sensor_id = self.id.clone();
+        let mut reading = self.calibration.apply(reading);
+        if let Some(geometry) = &self.geometry {
+            reading = geometry.apply(reading);
+        }
+        if self.kind == SensorKind::EggCount {
+            reading = self.counting.apply(reading)?;
+        }
+        match &self.filter {
+            Some(filter) => filter.check(reading),
+            None => Ok(reading),
+        }
+    }
+}
+
+/// A registry sensor with the result of reading it.
+pub type SensorReading = (Arc<RegisteredSensor>, Result<Reading, SensorError>);
+
+/// Reads `sensors` concurrently, keeping their order. Derived kinds in a zone that declares
+/// temperature and humidity are computed from those calibrated readings, as plain `status`
+/// does, rather than read from the source.
+pub async fn read_all(sensors: Vec<RegisteredSensor>) -> Vec<SensorReading> {
+    let zone_has = |zone: &str, kind: SensorKind| {
+        sensors
+            .iter()
+            .any(|sensor| sensor.zone == zone && sensor.kind == kind)
+    };
+    let derive: Vec<bool> = sensors
+        .iter()
+        .map(|sensor| {
+            sensor.kind.is_derived()
+                && zone_has(&sensor.zone, SensorKind::Temperature)
+                && zone_has(&sensor.zone, SensorKind::Humidity)
+        })
+        .collect();
+    let handles: Vec<_> = sensors
+        .into_iter()
+        .zip(&derive)
+        .map(|(sensor, derive)| {
+            let sensor = Arc::new(sensor);
+            let task = (!derive).then(|| {
+                tokio::spawn({
+                    let sensor = Arc::clone(&sensor);
+                    async move { sensor.read().await }
+                })
+            });
+            (sensor, task)
+        })
+        .collect();
+    let mut readings = Vec::with_capacity(handles.len());
+    for (sensor, task) in handles {
+        let reading = match task {
+            Some(task) => task
+                .await
+                .unwrap_or_else(|e| Err(SensorError::Source(format!("sensor task failed: {e}")))),
+            // Filled in below once the zone's temperature and humidity are read.
+            None => Err(SensorError::Unsupported(sensor.kind)),
+        };
+        readings.push((sensor, reading));
+    }
+    for index in (0..readings.len()).filter(|index| derive[*index]) {
+        let sensor = Arc::clone(&readings[index].0);
+        let zone_reading = |kind: SensorKind| {
+            readings
+                .iter()
+                .find(|(other, _)| other.zone == sensor.zone && other.kind == kind)
+                .map(|(_, reading)| reading)
+                .expect("zone declares the kind")
+        };
+        let reading = derive_reading(
+            sensor.kind,
+            zone_reading(SensorKind::Temperature),
+            zone_reading(SensorKind::Humidity),
+        )
+        .map(|mut reading| {
+            reading.sensor_id = sensor.id.clone();
+            reading
+        });
+        readings[index].1 = reading;
+    }
+    readings
+}
+
+/// Readings grouped by zone, zones in name order.
+pub fn by_zone(readings: &[SensorReading]) -> BTreeMap<&str, Vec<&SensorReading>> {
+    let mut zones: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+    for entry in readings {
+        zones.entry(entry.0.zone.as_str()).or_default().push(entry);
+    }
+    zones
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{by_zone, read_all, SensorRegistry};
+    use crate::calibration::CalibrationConfig;
+    use crate::eggs::EggCounting;
+    use crate::heat_stress::derive;
+    use crate::levels::LevelMethod;
+    use crate::sensors::{SensorKind, SensorValue};
+
+    #[test]
+    fn registry_validates_ids_and_kinds() {
+        let registry: SensorRegistry = serde_json::from_str(
+            r#"{"sensors": [
+                {"id": "coop1-temp", "kind": "temperature", "zone": "coop1", "key": "K1"},
+                {"id": "coop2-temp", "kind": "temperature", "zone": "coop2",
+                 "source": "http://coop2.local:8080", "key": "K2", "path": "/v2/temp"},
+                {"id": "left", "kind": "egg-count", "probe": "left", "key": "K3"}
+            ]}"#,
+        )
+        .expect("registry");
+        assert!(registry.validate().is_ok());
+        let spec = registry.spec("coop2-temp").expect("coop2");
+        assert_eq!(spec.kind(), Ok(SensorKind::Temperature));
+        assert_eq!(spec.path(SensorKind::Temperature), "v2/temp");
+        let eggs = registry.spec("left").expect("nest box");
+        assert_eq!(eggs.zone(), "coop");
+        assert_eq!(eggs.path(SensorKind::EggCount), "sensors/eggs/left");
+
+        let duplicate: SensorRegistry = serde_json::from_str(
+            r#"{"sensors": [{"id": "t", "kind": "temperature"}, {"id": "t", "kind": "humidity"}]}"#,
+        )
+        .expect("registry");
+        assert!(duplicate.validate().is_err());
+        let unknown: SensorRegistry =
+            serde_json::from_str(r#"{"sensors": [{"id": "t", "kind": "radon"}]}"#)
+                .expect("registry");
+        assert!(unknown.validate().is_err());
+        let misplaced: SensorRegistry = serde_json::from_str(
+            r#"{"sensors": [{"id": "t", "kind": "temperature", "tare_kg": 0.4}]}"#,
+        )
+        .expect("registry");
+        assert!(misplaced.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn per_sensor_geometry_and_tare() {
+        let registry: SensorRegistry = serde_json::from_str(
+            r#"{"sensors": [
+                {"id": "coop1-feed", "kind": "feed", "source": "sim"},
+                {"id": "coop2-feed", "kind": "feed", "source": "sim",
+                 "geometry": {"method": "scale", "tare_kg": 1.5, "capacity_kg": 25.0}},
+                {"id": "coop2-left", "kind": "egg-count", "source": "sim", "probe": "left",
+                 "tare_kg": 0.4, "egg_weight_g": 62.0}
+            ]}"#,
+        )
+        .expect("registry");
+        assert!(registry.validate().is_ok());
+        let sensors = registry
+            .build(&CalibrationConfig::default(), None)
+            .expect("build");
+        let coop1 = sensors[0].geometry.as_ref().expect("coop1 geometry");
+        let coop2 = sensors[1].geometry.as_ref().expect("coop2 geometry");
+        assert_eq!(coop1.method, LevelMethod::Ultrasonic);
+        assert_eq!(coop2.method, LevelMethod::Scale);
+        assert_eq!(coop2.capacity_kg, 25.0);
+        assert_eq!(
+            sensors[2].counting,
+            EggCounting::Scale {
+                tare_kg: 0.4,
+                egg_weight_g: 62.0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn registry_sensors_read_together_by_zone() {
+        let registry: SensorRegistry = serde_json::from_str(
+            r#"{"sensors": [
+                {"id": "coop2-temp", "kind": "temperature", "zone": "coop2", "source": "sim"},
+                {"id": "coop1-temp", "kind": "temperature", "zone": "coop1", "source": "sim"},
+                {"id": "coop1-eggs", "kind": "egg-count", "zone": "coop1", "source": "sim",
+                 "probe": "left"}
+            ]}"#,
+        )
+        .expect("registry");
+        let sensors = registry
+            .build(&CalibrationConfig::default(), None)
+            .expect("build");
+        let readings = read_all(sensors).await;
+        assert_eq!(readings[0].0.id, "coop2-temp");
+
+        let zones = by_zone(&readings);
+        assert_eq!(
+            zones.keys().copied().collect::<Vec<_>>(),
+            ["coop1", "coop2"]
+        );
+        let coop1 = &zones["coop1"];
+        assert_eq!(coop1.len(), 2);
+        let temp = coop1[0].1.as_ref().expect("temperature");
+        assert_eq!(temp.sensor_id, "coop1-temp");
+        assert!(matches!(temp.value, SensorValue::Numeric(_)));
+        let eggs = coop1[1].1.as_ref().expect("eggs");
+        assert!(matches!(eggs.value, SensorValue::Count(_)));
+    }
+
+    #[tokio::test]
+    async fn derived_kinds_use_the_zones_calibrated_readings() {
+        let registry: SensorRegistry = serde_json::from_str(
+            r#"{"sensors": [
+                {"id": "coop1-thi", "kind": "thi", "zone": "coop1", "source": "sim"},
+                {"id": "coop1-temp", "kind": "temperature", "zone": "coop1", "source": "sim"},
+                {"id": "coop1-humidity", "kind": "humidity", "zone": "coop1", "source": "sim"}
+            ]}"#,
+        )
+        .expect("registry");
+        let mut calibration = CalibrationConfig::default();
+        calibration.record_point("coop1-temp", SensorKind::Temperature, 20.0, 30.0);
+        let sensors = registry.build(&calibration, None).expect("build");
+        let readings = read_all(sensors).await;
+
+        let thi = readings[0].1.as_ref().expect("thi");
+        let temperature = readings[1].1.as_ref().expect("temperature");
+        let humidity = readings[2].1.as_ref().expect("humidity");
+        let expected = derive(SensorKind::Thi, temperature, humidity).expect("derived");
+        assert_eq!(thi.sensor_id, "coop1-thi");
+        assert_eq!(thi.value, expected.value);
+    }
+}
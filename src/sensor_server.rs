@@ -0,0 +1,310 @@
+use crate::heat_stress::sample_or_derive;
+use crate::sensor_sources::{create_source_from_env, SensorSource};
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use crate::util::millis;
+use axum::extract::{Path, State};
+use axum::http::header::{HeaderName, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tower_http::cors::{Any, CorsLayer};
+
+#[derive(Clone)]
+struct AppState {
+    keys: Arc<HashMap<SensorKind, String>>,
+    /// Unlocks every configured kind in `GET /sensors`.
+    batch_key: Option<Arc<str>>,
+    source: Arc<dyn SensorSource>,
+}
+
+#[derive(Serialize)]
+struct ReadingResponse {
+    value: SensorValue,
+    timestamp_ms: u64,
+}
+
+#[derive(Serialize)]
+struct BatchReading {
+    value: SensorValue,
+    unit: &'static str,
+    timestamp_ms: u64,
+}
+
+/// `GET /sensors`: kinds without a configured source are left out, failing ones are listed
+/// under `errors`.
+#[derive(Serialize)]
+struct BatchResponse {
+    readings: BTreeMap<&'static str, BatchReading>,
+    errors: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+struct ApiResponse {
+    status: &'static str,
+    message: String,
+}
+
+fn authorized(headers: &HeaderMap, expected_key: &str) -> bool {
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v == expected_key)
+        .unwrap_or(false)
+}
+
+fn error_response(status: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    (
+        status,
+        Json(ApiResponse {
+            status: "error",
+            message,
+        }),
+    )
+}
+
+/// Serves `GET /sensors`, `GET /sensors/<kind>` and `GET /sensors/eggs/<box>`; each kind is
+/// guarded by the same key the client sends for it, and `batch_key` also opens `GET /sensors`.
+pub async fn run_sensor_server(
+    bind_addr: &str,
+    keys: HashMap<SensorKind, String>,
+    batch_key: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = create_source_from_env().map_err(std::io::Error::other)?;
+    let state = AppState {
+        keys: Arc::new(keys),
+        batch_key: batch_key.map(Arc::from),
+        source: Arc::from(source),
+    };
+
+    let listener = TcpListener::bind(bind_addr).await?;
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/sensors", get(read_all_sensors))
+        .route("/sensors/:kind", get(read_sensor))
+        .route("/sensors/eggs/:nest_box", get(read_nest_box))
+        .with_state(state)
+        .layer(cors_layer())
+}
+
+fn cors_layer() -> CorsLayer {
+    let x_api_key = HeaderName::from_static("x-api-key");
+    let base = CorsLayer::new()
+        .allow_methods([Method::GET, Method::OPTIONS])
+        .allow_headers([CONTENT_TYPE, x_api_key]);
+
+    match env::var("SENSOR_ALLOWED_ORIGIN") {
+        Ok(origin) if !origin.is_empty() && origin != "*" => {
+            if let Ok(value) = HeaderValue::from_str(&origin) {
+                base.allow_origin(value)
+            } else {
+                base.allow_origin(Any)
+            }
+        }
+        _ => base.allow_origin(Any),
+    }
+}
+
+async fn read_sensor(
+    State(state): State<AppState>,
+    Path(kind): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ReadingResponse>, (StatusCode, Json<ApiResponse>)> {
+    let kind = SensorKind::from_name(&kind)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, format!("unknown sensor `{kind}`")))?;
+
+    check_key(&state, kind, &headers)?;
+    reading_response(sample_or_derive(state.source.as_ref(), kind).await)
+}
+
+/// Every kind the presented key unlocks (all configured kinds for the batch key), in one
+/// document. Nest boxes keep their own endpoint.
+async fn read_all_sensors(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BatchResponse>, (StatusCode, Json<ApiResponse>)> {
+    let batch = state
+        .batch_key
+        .as_deref()
+        .is_some_and(|key| authorized(&headers, key));
+    let kinds: Vec<SensorKind> = SensorKind::ALL
+        .into_iter()
+        .filter(|kind| *kind != SensorKind::EggCount)
+        .filter(|kind| {
+            if batch {
+                state.keys.contains_key(kind)
+            } else {
+                check_key(&state, *kind, &headers).is_ok()
+            }
+        })
+        .collect();
+    if kinds.is_empty() {
+        return Err(error_response(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized".to_string(),
+        ));
+    }
+
+    let mut response = BatchResponse {
+        readings: BTreeMap::new(),
+        errors: BTreeMap::new(),
+    };
+    for kind in kinds {
+        match sample_or_derive(state.source.as_ref(), kind).await {
+            Ok(reading) => {
+                response.readings.insert(
+                    kind.as_str(),
+                    BatchReading {
+                        value: reading.value,
+                        unit: reading.unit.symbol(),
+                        timestamp_ms: millis(reading.captured_at),
+                    },
+                );
+            }
+            Err(SensorError::Unsupported(_)) => {}
+            Err(err) => {
+                response.errors.insert(kind.as_str(), err.to_string());
+            }
+        }
+    }
+    Ok(Json(response))
+}
+
+async fn read_nest_box(
+    State(state): State<AppState>,
+    Path(nest_box): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ReadingResponse>, (StatusCode, Json<ApiResponse>)> {
+    check_key(&state, SensorKind::EggCount, &headers)?;
+    reading_response(state.source.nest_box(&nest_box).await)
+}
+
+fn check_key(
+    state: &AppState,
+    kind: SensorKind,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ApiResponse>)> {
+    let expected_key = state.keys.get(&kind).map(String::as_str).unwrap_or("");
+    if expected_key.is_empty() || !authorized(headers, expected_key) {
+        return Err(error_response(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn reading_response(
+    reading: Result<Reading, SensorError>,
+) -> Result<Json<ReadingResponse>, (StatusCode, Json<ApiResponse>)> {
+    match reading {
+        Ok(reading) => Ok(Json(ReadingResponse {
+            value: reading.value,
+            timestamp_ms: millis(reading.captured_at),
+        })),
+        Err(err @ SensorError::Unsupported(_)) => {
+            Err(error_response(StatusCode::NOT_FOUND, err.to_string()))
+        }
+        Err(err) => Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            err.to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{router, AppState};
+    use crate::sensor_sources::SensorSource;
+    use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+    use tokio::net::TcpListener;
+
+    struct FixedSource;
+
+    #[async_trait]
+    impl SensorSource for FixedSource {
+        async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+            let value = match kind {
+                SensorKind::Temperature => SensorValue::Numeric(21.5),
+                SensorKind::Humidity => SensorValue::Numeric(60.0),
+                SensorKind::Motion => SensorValue::Binary(true),
+                SensorKind::Co2 => return Err(SensorError::Source("checksum".to_string())),
+                _ => return Err(SensorError::Unsupported(kind)),
+            };
+            Ok(Reading::new(kind.as_str(), kind, value, SystemTime::now()))
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_endpoint_serves_the_kinds_a_key_unlocks() {
+        let keys = HashMap::from([
+            (SensorKind::Temperature, "TEMP".to_string()),
+            (SensorKind::Humidity, "HUM".to_string()),
+            (SensorKind::Motion, "MOTION".to_string()),
+            (SensorKind::Co2, "CO2".to_string()),
+            (SensorKind::Thi, "TEMP".to_string()),
+        ]);
+        let state = AppState {
+            keys: Arc::new(keys),
+            batch_key: Some("BATCH".into()),
+            source: Arc::new(FixedSource),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+
+        let client = reqwest::Client::new();
+        let batch = |key: &'static str| {
+            client
+                .get(format!("http://{addr}/sensors"))
+                .header("x-api-key", key)
+                .send()
+        };
+        let kinds = |body: &serde_json::Value, field: &str| {
+            let mut kinds: Vec<String> = body[field]
+                .as_object()
+                .expect(field)
+                .keys()
+                .cloned()
+                .collect();
+            kinds.sort();
+            kinds
+        };
+
+        let all: serde_json::Value = batch("BATCH")
+            .await
+            .expect("batch")
+            .json()
+            .await
+            .expect("json body");
+        assert_eq!(
+            kinds(&all, "readings"),
+            ["humidity", "motion", "temperature", "thi"]
+        );
+        assert_eq!(kinds(&all, "errors"), ["co2"]);
+        assert_eq!(all["readings"]["temperature"]["value"], 21.5);
+        assert_eq!(all["readings"]["temperature"]["unit"], "C");
+
+        let temp: serde_json::Value = batch("TEMP")
+            .await
+            .expect("batch")
+            .json()
+            .await
+            .expect("json body");
+        assert_eq!(kinds(&temp, "readings"), ["temperature", "thi"]);
+
+        assert_eq!(batch("WRONG").await.expect("batch").status().as_u16(), 401);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::heat_stress::derive;
+use crate::levels::ContainerGeometry;
+use crate::sensor_sources::SensorSource;
+use crate::sensors::{Reading, SensorError, SensorKind, SensorValue};
+use crate::util::{parse_env, parse_optional_env};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::env;
+use std::f32::consts::PI;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Hour of the daily temperature peak; humidity bottoms out at the same time.
+const PEAK_HOUR: f32 = 15.0;
+const EGGS_LAID_HOUR: f32 = 8.0;
+const EGGS_COLLECTED_HOUR: f32 = 18.0;
+/// Feed and water are topped up at this hour and drawn down until roost.
+const REFILL_HOUR: f32 = 6.0;
+const ROOST_HOUR: f32 = 20.0;
+
+/// Most eggs are laid in the first hours after `EGGS_LAID_HOUR`.
+const LAYING_HOURS: f32 = 5.0;
+
+/// Baseline motion: busy at dawn and dusk, some daytime activity, quiet nights.
+const BASELINE_MOTION: [MotionWindow; 3] = [
+    MotionWindow {
+        start_hour: 5.5,
+        end_hour: 7.0,
+        probability: 0.7,
+    },
+    MotionWindow {
+        start_hour: 18.5,
+        end_hour: 20.0,
+        probability: 0.7,
+    },
+    MotionWindow {
+        start_hour: 7.0,
+        end_hour: 18.5,
+        probability: 0.15,
+    },
+];
+const NIGHT_MOTION_PROBABILITY: f32 = 0.02;
+
+fn in_window(hour: f32, start_hour: f32, end_hour: f32) -> bool {
+    if start_hour <= end_hour {
+        hour >= start_hour && hour < end_hour
+    } else {
+        hour >= start_hour || hour < end_hour
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MotionWindow {
+    pub start_hour: f32,
+    pub end_hour: f32,
+    pub probability: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Outage {
+    pub start_hour: f32,
+    pub end_hour: f32,
+    /// Affected sensors by gateway name; empty means every sensor.
+    #[serde(default)]
+    pub sensors: Vec<String>,
+}
+
+/// Deviations from the baseline day, loaded from a JSON file such as `scenarios/heat-wave.json`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub name: String,
+    pub temperature_offset: f32,
+    pub humidity_offset: f32,
+    pub motion_windows: Vec<MotionWindow>,
+    pub outages: Vec<Outage>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read scenario `{path}`: {e}"))?;
+        serde_json::from_str(&content).map_err(|e| format!("invalid scenario `{path}`: {e}"))
+    }
+}
+
+/// Deterministic noise in `[0, 1)` so repeated reads within a bucket agree.
+fn unit_noise(bucket: u64, salt: u64) -> f32 {
+    let mut z = bucket.wrapping_add(salt.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+pub struct SimSensorSource {
+    scenario: Scenario,
+    pinned_hour: Option<f32>,
+    utc_offset_hours: f32,
+}
+
+impl SimSensorSource {
+    pub fn new(scenario: Scenario) -> Self {
+        SimSensorSource {
+            scenario,
+            pinned_hour: None,
+            utc_offset_hours: 0.0,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let scenario = match env::var("SIM_SCENARIO") {
+            Ok(path) => Scenario::load(&path)?,
+            Err(_) => Scenario::default(),
+        };
+        let mut source = SimSensorSource::new(scenario);
+        source.pinned_hour = parse_optional_env("SIM_HOUR")?;
+        source.utc_offset_hours = parse_env("SIM_UTC_OFFSET_HOURS", source.utc_offset_hours)?;
+        Ok(source)
+    }
+
+    fn hour_at(&self, secs: u64) -> f32 {
+        if let Some(hour) = self.pinned_hour {
+            return hour.rem_euclid(24.0);
+        }
+        let day_secs = (secs % 86_400) as f32;
+        (day_secs / 3600.0 + self.utc_offset_hours).rem_euclid(24.0)
+    }
+
+    fn motion_probability(&self, hour: f32) -> f32 {
+        let scripted = self
+            .scenario
+            .motion_windows
+            .iter()
+            .filter(|w| in_window(hour, w.start_hour, w.end_hour))
+            .map(|w| w.probability)
+            .fold(None, |best: Option<f32>, p| {
+                Some(best.map_or(p, |b| b.max(p)))
+            });
+        scripted.unwrap_or_else(|| {
+            BASELINE_MOTION
+                .iter()
+                .find(|w| in_window(hour, w.start_hour, w.end_hour))
+                .map(|w| w.probability)
+                .unwrap_or(NIGHT_MOTION_PROBABILITY)
+        })
+    }
+
+    /// Eggs in a box at `hour`: one to three a day per box, laid through the morning and
+    /// collected at `EGGS_COLLECTED_HOUR`.
+    fn eggs_at(&self, hour: f32, secs: u64, salt: u64) -> u32 {
+        if !in_window(hour, EGGS_LAID_HOUR, EGGS_COLLECTED_HOUR) {
+            return 0;
+        }
+        let daily = 1.0 + (unit_noise(secs / 86_400, salt) * 3.0).floor();
+        let laid = ((hour - EGGS_LAID_HOUR) / LAYING_HOURS).min(1.0);
+        (daily * laid).ceil() as u32
+    }
+
+    fn in_outage(&self, name: &str, hour: f32) -> bool {
+        self.scenario.outages.iter().any(|o| {
+            in_window(hour, o.start_hour, o.end_hour)
+                && (o.sensors.is_empty() || o.sensors.iter().any(|s| s == name))
+        })
+    }
+
+    /// Value for `kind` at `hour` of the simulated day; `secs` only seeds the noise.
+    fn value_at(&self, kind: SensorKind, hour: f32, secs: u64) -> Result<SensorValue, SensorError> {
+        if self.in_outage(kind.as_str(), hour) {
+            return Err(SensorError::Timeout);
+        }
+
+        let diurnal = (2.0 * PI * (hour - PEAK_HOUR) / 24.0).cos();
+        let jitter = unit_noise(secs / 60, kind as u64) - 0.5;
+        let value = match kind {
+            SensorKind::Temperature => SensorValue::Numeric(
+                18.0 + 6.0 * diurnal + self.scenario.temperature_offset + 0.6 * jitter,
+            ),
+            SensorKind::Humidity => SensorValue::Numeric(
+                (65.0 - 15.0 * diurnal + self.scenario.humidity_offset + 2.0 * jitter)
+                    .clamp(0.0, 100.0),
+            ),
+            SensorKind::Motion => {
+                let roll = unit_noise(secs / 10, kind as u64);
+                SensorValue::Binary(roll < self.motion_probability(hour))
+            }
+            SensorKind::EggPresence => {
+                SensorValue::Binary(in_window(hour, EGGS_LAID_HOUR, EGGS_COLLECTED_HOUR))
+            }
+            SensorKind::Pressure => SensorValue::Numeric(1013.0 + 4.0 * jitter),
+            // Gases build up overnight while the coop is shut and vent during the day.
+            SensorKind::Ammonia => SensorValue::Numeric(10.0 - 5.0 * diurnal + 2.0 * jitter),
+            SensorKind::Co2 => SensorValue::Numeric(900.0 - 350.0 * diurnal + 60.0 * jitter),
+            // Levels are reported raw, like a real distance or weight sensor would.
+            SensorKind::FeedLevel | SensorKind::WaterLevel => {
+                let (full, drawn) = if kind == SensorKind::FeedLevel {
+                    (100.0, 60.0)
+                } else {
+                    (90.0, 40.0)
+                };
+                let percent = if hour < REFILL_HOUR {
+                    full - drawn
+                } else {
+                    full - drawn * (hour.min(ROOST_HOUR) - REFILL_HOUR) / (ROOST_HOUR - REFILL_HOUR)
+                };
+                let geometry = ContainerGeometry::from_env(kind).map_err(SensorError::Source)?;
+                SensorValue::Numeric(geometry.raw_at_percent(percent))
+            }
+            SensorKind::EggCount => SensorValue::Count(self.eggs_at(hour, secs, kind as u64)),
+            SensorKind::Thi | SensorKind::HeatIndex | SensorKind::DewPoint => {
+                let now = SystemTime::now();
+                let sample = |kind| {
+                    self.value_at(kind, hour, secs)
+                        .map(|value| Reading::new(kind.as_str(), kind, value, now))
+                };
+                let temperature = sample(SensorKind::Temperature)?;
+                let humidity = sample(SensorKind::Humidity)?;
+                return derive(kind, &temperature, &humidity).map(|reading| reading.value);
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl SensorSource for SimSensorSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let now = SystemTime::now();
+        let secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let value = self.value_at(kind, self.hour_at(secs), secs)?;
+        Ok(Reading::new(kind.as_str(), kind, value, now))
+    }
+
+    /// Boxes are simulated independently, seeded by name; outages on `eggs` cover them all.
+    async fn nest_box(&self, nest_box: &str) -> Result<Reading, SensorError> {
+        let now = SystemTime::now();
+        let secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let hour = self.hour_at(secs);
+        if self.in_outage(SensorKind::EggPresence.as_str(), hour) {
+            return Err(SensorError::Timeout);
+        }
+        let salt = nest_box
+            .bytes()
+            .fold(0u64, |h, b| h.wrapping_mul(31).wrapping_add(b as u64));
+        let eggs = self.eggs_at(hour, secs, salt);
+        Ok(Reading::new(
+            nest_box,
+            SensorKind::EggCount,
+            SensorValue::Count(eggs),
+            now,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scenario, SimSensorSource};
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+
+    fn numeric(source: &SimSensorSource, kind: SensorKind, hour: f32) -> f32 {
+        match source.value_at(kind, hour, 0) {
+            Ok(SensorValue::Numeric(v)) => v,
+            other => panic!("expected numeric value, got {other:?}"),
+        }
+    }
+
+    fn motion_count(source: &SimSensorSource, hour: f32) -> usize {
+        (0..200u64)
+            .filter(|i| {
+                source.value_at(SensorKind::Motion, hour, i * 10) == Ok(SensorValue::Binary(true))
+            })
+            .count()
+    }
+
+    #[test]
+    fn baseline_day_follows_diurnal_curves() {
+        let source = SimSensorSource::new(Scenario::default());
+
+        assert!(
+            numeric(&source, SensorKind::Temperature, 15.0)
+                > numeric(&source, SensorKind::Temperature, 4.0) + 8.0
+        );
+        assert!(
+            numeric(&source, SensorKind::Humidity, 15.0)
+                < numeric(&source, SensorKind::Humidity, 4.0)
+        );
+        assert!(motion_count(&source, 6.0) > motion_count(&source, 12.0));
+        assert!(motion_count(&source, 6.0) > motion_count(&source, 2.0));
+        assert_eq!(
+            source.value_at(SensorKind::EggPresence, 5.0, 0),
+            Ok(SensorValue::Binary(false))
+        );
+        assert_eq!(
+            source.value_at(SensorKind::EggPresence, 10.0, 0),
+            Ok(SensorValue::Binary(true))
+        );
+        assert_eq!(
+            source.value_at(SensorKind::EggCount, 5.0, 0),
+            Ok(SensorValue::Count(0))
+        );
+        assert!(source.eggs_at(14.0, 0, 7) >= source.eggs_at(9.0, 0, 7));
+        assert!((1..=3).contains(&source.eggs_at(14.0, 0, 7)));
+    }
+
+    #[test]
+    fn bundled_scenarios_change_the_day() {
+        let baseline = SimSensorSource::new(Scenario::default());
+        let heat_wave: Scenario =
+            serde_json::from_str(include_str!("../scenarios/heat-wave.json")).expect("heat wave");
+        let predator_night: Scenario =
+            serde_json::from_str(include_str!("../scenarios/predator-night.json"))
+                .expect("predator night");
+        let outage: Scenario =
+            serde_json::from_str(include_str!("../scenarios/gateway-outage.json"))
+                .expect("gateway outage");
+
+        let heat_wave = SimSensorSource::new(heat_wave);
+        assert!(
+            numeric(&heat_wave, SensorKind::Temperature, 15.0)
+                > numeric(&baseline, SensorKind::Temperature, 15.0) + 10.0
+        );
+
+        assert!(
+            numeric(&heat_wave, SensorKind::Thi, 15.0)
+                > numeric(&baseline, SensorKind::Thi, 15.0) + 10.0
+        );
+
+        let predator_night = SimSensorSource::new(predator_night);
+        assert!(motion_count(&predator_night, 1.0) > motion_count(&baseline, 1.0) * 5);
+
+        let outage = SimSensorSource::new(outage);
+        assert_eq!(
+            outage.value_at(SensorKind::Temperature, 12.5, 0),
+            Err(SensorError::Timeout)
+        );
+        assert!(outage.value_at(SensorKind::Temperature, 9.0, 0).is_ok());
+    }
+}
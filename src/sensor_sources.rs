@@ -0,0 +1,280 @@
+use crate::sensor_sim::SimSensorSource;
+use crate::sensors::{
+    EspHomeSource, Reading, SensorError, SensorKind, SensorValue, TasmotaSource, Unit,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::env;
+use std::time::SystemTime;
+use tokio::process::Command;
+
+/// Local origin of sensor values, served by `coop serve sensors`.
+#[async_trait]
+pub trait SensorSource: Send + Sync {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError>;
+
+    /// Extra named readings beyond the one per kind, e.g. additional temperature probes.
+    async fn auxiliary(&self) -> Vec<(String, Result<Reading, SensorError>)> {
+        Vec::new()
+    }
+
+    /// Eggs in one nest box, as a count or the raw weight on a nest scale in kg.
+    async fn nest_box(&self, nest_box: &str) -> Result<Reading, SensorError> {
+        let _ = nest_box;
+        Err(SensorError::Unsupported(SensorKind::EggCount))
+    }
+}
+
+pub fn create_source(backend: &str) -> Result<Box<dyn SensorSource>, String> {
+    match backend {
+        "command" => Ok(Box::new(CommandSensorSource::default())),
+        "sim" => Ok(Box::new(SimSensorSource::from_env()?)),
+        "ds18b20" => create_ds18b20_source(),
+        "bme280" | "sht31" => create_i2c_source(backend),
+        "pir" => create_pir_source(),
+        "mqtt" => create_mqtt_source(),
+        "air-quality" => create_air_quality_source(),
+        "levels" => create_level_source(),
+        "modbus" => Ok(Box::new(crate::modbus::modbus_source_from_env()?)),
+        esphome if esphome.starts_with("esphome:") => Ok(Box::new(EspHomeSource::new(
+            esphome.trim_start_matches("esphome:"),
+        ))),
+        tasmota if tasmota.starts_with("tasmota:") => Ok(Box::new(TasmotaSource::new(
+            tasmota.trim_start_matches("tasmota:"),
+        ))),
+        _ => Err(format!(
+            "unsupported SENSOR_BACKEND `{backend}` (expected `http`, `command`, `sim`, `ds18b20`, `bme280`, `sht31`, `pir`, `air-quality`, `levels`, `modbus`, `mqtt`, `esphome:<url>` or `tasmota:<url>`)"
+        )),
+    }
+}
+
+#[cfg(feature = "pi-hw")]
+fn create_ds18b20_source() -> Result<Box<dyn SensorSource>, String> {
+    Ok(Box::new(crate::ds18b20::Ds18b20Source::from_env()?))
+}
+
+#[cfg(not(feature = "pi-hw"))]
+fn create_ds18b20_source() -> Result<Box<dyn SensorSource>, String> {
+    Err("SENSOR_BACKEND=ds18b20 requires cargo feature `pi-hw`".to_string())
+}
+
+#[cfg(all(feature = "pi-hw", target_os = "linux"))]
+fn create_i2c_source(backend: &str) -> Result<Box<dyn SensorSource>, String> {
+    if backend == "bme280" {
+        Ok(Box::new(crate::i2c_sensors::bme280_source_from_env()?))
+    } else {
+        Ok(Box::new(crate::i2c_sensors::sht31_source_from_env()?))
+    }
+}
+
+#[cfg(all(feature = "pi-hw", target_os = "linux"))]
+fn create_pir_source() -> Result<Box<dyn SensorSource>, String> {
+    Ok(Box::new(crate::pir::PirMotionSource::from_env()?))
+}
+
+#[cfg(not(all(feature = "pi-hw", target_os = "linux")))]
+fn create_pir_source() -> Result<Box<dyn SensorSource>, String> {
+    Err("SENSOR_BACKEND=pir requires Linux and cargo feature `pi-hw`".to_string())
+}
+
+#[cfg(not(all(feature = "pi-hw", target_os = "linux")))]
+fn create_i2c_source(backend: &str) -> Result<Box<dyn SensorSource>, String> {
+    Err(format!(
+        "SENSOR_BACKEND={backend} requires Linux and cargo feature `pi-hw`"
+    ))
+}
+
+#[cfg(all(feature = "pi-hw", target_os = "linux"))]
+fn create_air_quality_source() -> Result<Box<dyn SensorSource>, String> {
+    Ok(Box::new(crate::air_quality::air_quality_source_from_env()?))
+}
+
+#[cfg(not(all(feature = "pi-hw", target_os = "linux")))]
+fn create_air_quality_source() -> Result<Box<dyn SensorSource>, String> {
+    Err("SENSOR_BACKEND=air-quality requires Linux and cargo feature `pi-hw`".to_string())
+}
+
+#[cfg(all(feature = "pi-hw", target_os = "linux"))]
+fn create_level_source() -> Result<Box<dyn SensorSource>, String> {
+    Ok(Box::new(crate::level_sensors::level_source_from_env()?))
+}
+
+#[cfg(not(all(feature = "pi-hw", target_os = "linux")))]
+fn create_level_source() -> Result<Box<dyn SensorSource>, String> {
+    Err("SENSOR_BACKEND=levels requires Linux and cargo feature `pi-hw`".to_string())
+}
+
+#[cfg(feature = "mqtt")]
+fn create_mqtt_source() -> Result<Box<dyn SensorSource>, String> {
+    Ok(Box::new(crate::mqtt::MqttSensorSource::from_env()?))
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn create_mqtt_source() -> Result<Box<dyn SensorSource>, String> {
+    Err("SENSOR_BACKEND=mqtt requires cargo feature `mqtt`".to_string())
+}
+
+/// Source for `coop serve sensors`; the gateway cannot serve the `http` backend itself.
+pub fn create_source_from_env() -> Result<Box<dyn SensorSource>, String> {
+    let backend = env::var("SENSOR_BACKEND").unwrap_or_else(|_| "command".to_string());
+    if backend == "http" {
+        return Err("serve sensors needs a local SENSOR_BACKEND, not `http`".to_string());
+    }
+    create_source(&backend)
+}
+
+fn read_cmd_env(kind: SensorKind) -> &'static str {
+    match kind {
+        SensorKind::Temperature => "TEMPERATURE_READ_CMD",
+        SensorKind::Humidity => "HUMIDITY_READ_CMD",
+        SensorKind::Motion => "MOTION_READ_CMD",
+        SensorKind::EggPresence => "EGGS_READ_CMD",
+        SensorKind::Pressure => "PRESSURE_READ_CMD",
+        SensorKind::Ammonia => "AMMONIA_READ_CMD",
+        SensorKind::Co2 => "CO2_READ_CMD",
+        SensorKind::FeedLevel => "FEED_READ_CMD",
+        SensorKind::WaterLevel => "WATER_READ_CMD",
+        SensorKind::EggCount => "EGG_COUNT_READ_CMD",
+        SensorKind::Thi => "THI_READ_CMD",
+        SensorKind::HeatIndex => "HEAT_INDEX_READ_CMD",
+        SensorKind::DewPoint => "DEW_POINT_READ_CMD",
+    }
+}
+
+/// Parses a plain-text value such as `21.5` or `on`, as printed by commands or published over MQTT.
+pub(crate) fn parse_value(kind: SensorKind, raw: &str) -> Result<SensorValue, SensorError> {
+    let raw = raw.trim();
+    if kind.unit() == Unit::Bool {
+        match raw.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(SensorValue::Binary(true)),
+            "0" | "false" | "no" | "off" => Ok(SensorValue::Binary(false)),
+            _ => Err(SensorError::Source(format!(
+                "expected a boolean for {}, got `{raw}`",
+                kind.as_str()
+            ))),
+        }
+    } else {
+        raw.parse::<f32>().map(SensorValue::Numeric).map_err(|_| {
+            SensorError::Source(format!(
+                "expected a number for {}, got `{raw}`",
+                kind.as_str()
+            ))
+        })
+    }
+}
+
+/// Runs a configured shell command per sensor and parses its stdout.
+pub struct CommandSensorSource {
+    commands: HashMap<SensorKind, String>,
+}
+
+impl Default for CommandSensorSource {
+    fn default() -> Self {
+        let commands = SensorKind::ALL
+            .into_iter()
+            .filter_map(|kind| env::var(read_cmd_env(kind)).ok().map(|cmd| (kind, cmd)))
+            .collect();
+        CommandSensorSource { commands }
+    }
+}
+
+impl CommandSensorSource {
+    /// Runs the command for `kind`; `nest_box` is passed to it as `NEST_BOX`.
+    async fn run(&self, kind: SensorKind, nest_box: Option<&str>) -> Result<String, SensorError> {
+        let command = self
+            .commands
+            .get(&kind)
+            .ok_or(SensorError::Unsupported(kind))?;
+
+        #[cfg(target_os = "windows")]
+        let mut shell = {
+            let mut shell = Command::new("powershell");
+            shell.args(["-NoProfile", "-Command", command]);
+            shell
+        };
+
+        #[cfg(not(target_os = "windows"))]
+        let mut shell = {
+            let mut shell = Command::new("sh");
+            shell.args(["-c", command]);
+            shell
+        };
+
+        if let Some(nest_box) = nest_box {
+            shell.env("NEST_BOX", nest_box);
+        }
+        let output = shell.output().await;
+
+        let output =
+            output.map_err(|e| SensorError::Source(format!("failed to execute command: {e}")))?;
+        if !output.status.success() {
+            return Err(SensorError::Source(format!(
+                "command failed with status: {}",
+                output.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl SensorSource for CommandSensorSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let value = parse_value(kind, &self.run(kind, None).await?)?;
+        Ok(Reading::new(kind.as_str(), kind, value, SystemTime::now()))
+    }
+
+    async fn nest_box(&self, nest_box: &str) -> Result<Reading, SensorError> {
+        let kind = SensorKind::EggCount;
+        let value = parse_value(kind, &self.run(kind, Some(nest_box)).await?)?;
+        Ok(Reading::new(nest_box, kind, value, SystemTime::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandSensorSource, SensorSource};
+    use crate::sensors::{SensorError, SensorKind, SensorValue};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn command_source_parses_stdout() {
+        let source = CommandSensorSource {
+            commands: HashMap::from([
+                (SensorKind::Temperature, "echo 21.5".to_string()),
+                (SensorKind::Motion, "echo on".to_string()),
+                (SensorKind::Humidity, "echo soggy".to_string()),
+            ]),
+        };
+
+        let temp = source.sample(SensorKind::Temperature).await.expect("temp");
+        assert_eq!(temp.value, SensorValue::Numeric(21.5));
+        let motion = source.sample(SensorKind::Motion).await.expect("motion");
+        assert_eq!(motion.value, SensorValue::Binary(true));
+        assert!(matches!(
+            source.sample(SensorKind::Humidity).await,
+            Err(SensorError::Source(_))
+        ));
+        assert_eq!(
+            source.sample(SensorKind::EggPresence).await,
+            Err(SensorError::Unsupported(SensorKind::EggPresence))
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn command_source_passes_the_nest_box() {
+        let source = CommandSensorSource {
+            commands: HashMap::from([(
+                SensorKind::EggCount,
+                r#"test "$NEST_BOX" = left && echo 2 || echo 0"#.to_string(),
+            )]),
+        };
+
+        let left = source.nest_box("left").await.expect("left box");
+        assert_eq!(left.sensor_id, "left");
+        assert_eq!(left.value, SensorValue::Numeric(2.0));
+        let right = source.nest_box("right").await.expect("right box");
+        assert_eq!(right.value, SensorValue::Numeric(0.0));
+    }
+}
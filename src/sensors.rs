@@ -1,12 +1,94 @@
-use reqwest::blocking::Client;
-use serde::Deserialize;
+use crate::calibration::Calibration;
+use crate::eggs::EggCounting;
+use crate::heat_stress::sample_or_derive;
+use crate::levels::ContainerGeometry;
+use crate::retry::{RetryPolicy, Retryable};
+use crate::sensor_filter::ReadingFilter;
+use crate::sensor_sources::{create_source, parse_value, SensorSource};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
-use std::time::Duration;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
 
 const SENSOR_API_BASE_URL_DEFAULT: &str = "http://127.0.0.1:8080";
+/// How long one `GET /sensors` document answers per-sensor reads, so a `status` run costs a
+/// single round trip.
+const BATCH_MAX_AGE: Duration = Duration::from_secs(5);
+/// How long a gateway without `GET /sensors` is read per sensor before probing again, so a
+/// long-running server notices a gateway upgrade.
+const BATCH_REPROBE_AFTER: Duration = Duration::from_secs(600);
 
-pub trait Sensor {
-    fn read(&self) -> SensorValue;
+#[async_trait]
+pub trait Sensor: Send + Sync {
+    async fn read(&self) -> Result<Reading, SensorError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SensorError {
+    Timeout,
+    Unauthorized,
+    Status(u16),
+    MalformedJson(String),
+    Transport(String),
+    Source(String),
+    Unsupported(SensorKind),
+    Implausible(String),
+    CircuitOpen(String),
+}
+
+impl fmt::Display for SensorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorError::Timeout => write!(f, "gateway timed out"),
+            SensorError::Unauthorized => write!(f, "gateway rejected sensor key"),
+            SensorError::Status(code) => write!(f, "gateway returned HTTP {code}"),
+            SensorError::MalformedJson(err) => write!(f, "malformed gateway response: {err}"),
+            SensorError::Transport(err) => write!(f, "gateway unreachable: {err}"),
+            SensorError::Source(err) => write!(f, "local source failed: {err}"),
+            SensorError::Unsupported(kind) => {
+                write!(f, "no source configured for {}", kind.as_str())
+            }
+            SensorError::Implausible(reason) => write!(f, "implausible reading: {reason}"),
+            SensorError::CircuitOpen(endpoint) => {
+                write!(
+                    f,
+                    "gateway circuit open after repeated failures: {endpoint}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SensorError {}
+
+impl Retryable for SensorError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            SensorError::Timeout | SensorError::Transport(_) => true,
+            SensorError::Status(code) => *code == 429 || *code >= 500,
+            _ => false,
+        }
+    }
+
+    fn circuit_open(endpoint: &str) -> Self {
+        SensorError::CircuitOpen(endpoint.to_string())
+    }
+}
+
+impl From<reqwest::Error> for SensorError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            SensorError::Timeout
+        } else {
+            SensorError::Transport(err.to_string())
+        }
+    }
 }
 
 fn redact_key(key: &str) -> String {
@@ -14,165 +96,1026 @@ fn redact_key(key: &str) -> String {
     format!("{shown}***")
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Logs a read from a gateway; local sources have no API or key to report.
+fn log_gateway_read(backend: &SensorBackend, what: &str, key: &str) {
+    if !matches!(backend, SensorBackend::Local(_)) {
+        println!("Reading {what} via API using key {}", redact_key(key));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
 pub enum SensorValue {
     Numeric(f32),
     Binary(bool),
+    Count(u32),
 }
 
-#[derive(Deserialize)]
-struct NumericResponse {
-    value: f32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SensorKind {
+    Temperature,
+    Humidity,
+    Motion,
+    EggPresence,
+    Pressure,
+    Ammonia,
+    Co2,
+    FeedLevel,
+    WaterLevel,
+    EggCount,
+    Thi,
+    HeatIndex,
+    DewPoint,
+}
+
+impl SensorKind {
+    pub const ALL: [SensorKind; 13] = [
+        SensorKind::Temperature,
+        SensorKind::Humidity,
+        SensorKind::Motion,
+        SensorKind::EggPresence,
+        SensorKind::Pressure,
+        SensorKind::Ammonia,
+        SensorKind::Co2,
+        SensorKind::FeedLevel,
+        SensorKind::WaterLevel,
+        SensorKind::EggCount,
+        SensorKind::Thi,
+        SensorKind::HeatIndex,
+        SensorKind::DewPoint,
+    ];
+
+    /// Gateway path segment (`/sensors/<name>`), also used as the default sensor id.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "temperature",
+            SensorKind::Humidity => "humidity",
+            SensorKind::Motion => "motion",
+            SensorKind::EggPresence => "eggs",
+            SensorKind::Pressure => "pressure",
+            SensorKind::Ammonia => "ammonia",
+            SensorKind::Co2 => "co2",
+            SensorKind::FeedLevel => "feed",
+            SensorKind::WaterLevel => "water",
+            SensorKind::EggCount => "egg-count",
+            SensorKind::Thi => "thi",
+            SensorKind::HeatIndex => "heat-index",
+            SensorKind::DewPoint => "dew-point",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SensorKind> {
+        SensorKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == name)
+    }
+
+    pub fn unit(&self) -> Unit {
+        match self {
+            SensorKind::Temperature | SensorKind::HeatIndex | SensorKind::DewPoint => Unit::Celsius,
+            SensorKind::Humidity | SensorKind::FeedLevel | SensorKind::WaterLevel => Unit::Percent,
+            SensorKind::Motion | SensorKind::EggPresence => Unit::Bool,
+            SensorKind::Pressure => Unit::Hectopascal,
+            SensorKind::Ammonia | SensorKind::Co2 => Unit::Ppm,
+            SensorKind::EggCount => Unit::Count,
+            SensorKind::Thi => Unit::Index,
+        }
+    }
+
+    /// Kinds computed from temperature and humidity when a source has no sensor for them.
+    pub fn is_derived(&self) -> bool {
+        matches!(
+            self,
+            SensorKind::Thi | SensorKind::HeatIndex | SensorKind::DewPoint
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Celsius,
+    Fahrenheit,
+    Percent,
+    Hectopascal,
+    Ppm,
+    Bool,
+    Count,
+    /// Dimensionless index such as THI.
+    Index,
+}
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Celsius => "C",
+            Unit::Fahrenheit => "F",
+            Unit::Percent => "%",
+            Unit::Hectopascal => "hPa",
+            Unit::Ppm => "ppm",
+            Unit::Bool | Unit::Count | Unit::Index => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub sensor_id: String,
+    pub kind: SensorKind,
+    pub unit: Unit,
+    pub value: SensorValue,
+    pub captured_at: SystemTime,
+}
+
+impl Reading {
+    pub fn new(
+        sensor_id: &str,
+        kind: SensorKind,
+        value: SensorValue,
+        captured_at: SystemTime,
+    ) -> Self {
+        Reading {
+            sensor_id: sensor_id.to_string(),
+            kind,
+            unit: kind.unit(),
+            value,
+            captured_at,
+        }
+    }
+
+    /// Time since capture; readings stamped in the future count as fresh.
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.captured_at)
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            SensorValue::Numeric(v) => write!(f, "{v:.1}{}", self.unit.symbol()),
+            SensorValue::Binary(v) => write!(f, "{v}"),
+            SensorValue::Count(v) => write!(f, "{v}"),
+        }
+    }
 }
 
 #[derive(Deserialize)]
-struct BinaryResponse {
-    value: bool,
+struct GatewayValue<T> {
+    value: T,
+    timestamp_ms: Option<u64>,
 }
 
 fn sensor_api_base_url() -> String {
     env::var("SENSOR_API_BASE_URL").unwrap_or_else(|_| SENSOR_API_BASE_URL_DEFAULT.to_string())
 }
 
-fn sensor_client() -> Client {
-    Client::builder()
-        .timeout(Duration::from_secs(2))
-        .build()
-        .expect("failed to build sensor http client")
+/// Shared pooled client so concurrent reads reuse gateway connections.
+fn sensor_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .expect("failed to build sensor http client")
+    })
+}
+
+async fn fetch_json<T: DeserializeOwned>(
+    base_url: &str,
+    path: &str,
+    key: &str,
+) -> Result<T, SensorError> {
+    fetch_json_expecting(base_url, path, key, |_| false).await
+}
+
+/// [`fetch_json`] for an endpoint the gateway may lack: errors matching `expected` do not count
+/// as failures in the retry metrics.
+async fn fetch_json_expecting<T: DeserializeOwned>(
+    base_url: &str,
+    path: &str,
+    key: &str,
+    expected: fn(&SensorError) -> bool,
+) -> Result<T, SensorError> {
+    let url = format!("{}/{}", base_url.trim_end_matches('/'), path);
+    let url = &url;
+    RetryPolicy::shared()
+        .run_expecting(url, expected, || async move {
+            // Firmware APIs take no key, so they get no header rather than an empty one.
+            let mut request = sensor_client().get(url);
+            if !key.is_empty() {
+                request = request.header("x-api-key", key);
+            }
+            let response = request.send().await?;
+            match response.status() {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    return Err(SensorError::Unauthorized)
+                }
+                status if !status.is_success() => return Err(SensorError::Status(status.as_u16())),
+                _ => {}
+            }
+            let body = response.text().await?;
+            serde_json::from_str(&body).map_err(|e| SensorError::MalformedJson(e.to_string()))
+        })
+        .await
+}
+
+/// Fetches a gateway value, preferring the gateway's capture time over ours.
+async fn fetch_value<T: DeserializeOwned>(
+    base_url: &str,
+    path: &str,
+    key: &str,
+) -> Result<(T, SystemTime), SensorError> {
+    let parsed: GatewayValue<T> = fetch_json(base_url, path, key).await?;
+    Ok((parsed.value, captured_at(parsed.timestamp_ms)))
+}
+
+fn captured_at(timestamp_ms: Option<u64>) -> SystemTime {
+    timestamp_ms
+        .map(|ms| UNIX_EPOCH + Duration::from_millis(ms))
+        .unwrap_or_else(SystemTime::now)
 }
 
-fn fetch_numeric(path: &str, key: &str) -> Option<f32> {
-    let url = format!("{}/{}", sensor_api_base_url().trim_end_matches('/'), path);
-    let response = sensor_client()
-        .get(url)
-        .header("x-api-key", key)
-        .send()
-        .ok()?;
-    let parsed: NumericResponse = response.error_for_status().ok()?.json().ok()?;
-    Some(parsed.value)
+async fn fetch_numeric(
+    base_url: &str,
+    path: &str,
+    key: &str,
+) -> Result<(f32, SystemTime), SensorError> {
+    fetch_value(base_url, path, key).await
 }
 
-fn fetch_binary(path: &str, key: &str) -> Option<bool> {
-    let url = format!("{}/{}", sensor_api_base_url().trim_end_matches('/'), path);
-    let response = sensor_client()
-        .get(url)
-        .header("x-api-key", key)
-        .send()
-        .ok()?;
-    let parsed: BinaryResponse = response.error_for_status().ok()?.json().ok()?;
-    Some(parsed.value)
+async fn fetch_binary(
+    base_url: &str,
+    path: &str,
+    key: &str,
+) -> Result<(bool, SystemTime), SensorError> {
+    fetch_value(base_url, path, key).await
+}
+
+/// `GET /sensors`: every kind the key may read, keyed by kind name. Units are informational.
+#[derive(Deserialize)]
+struct GatewayBatch {
+    readings: HashMap<String, GatewayValue<serde_json::Value>>,
+}
+
+type BatchCell = Arc<OnceCell<Option<Arc<GatewayBatch>>>>;
+
+#[derive(Default)]
+struct BatchCache {
+    /// Per gateway and batch key, so concurrent reads share one request.
+    fetched: HashMap<(String, String), (Instant, BatchCell)>,
+    /// Gateways without a batch endpoint, until they are probed again.
+    unsupported: HashMap<String, Instant>,
+}
+
+fn batch_cache() -> &'static Mutex<BatchCache> {
+    static CACHE: OnceLock<Mutex<BatchCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(BatchCache::default()))
+}
+
+/// The gateway's batch document for `key`, fetched at most once per [`BATCH_MAX_AGE`]. `None`
+/// when the gateway has no batch endpoint or the request failed; callers then fall back to the
+/// per-sensor endpoints. A missing endpoint is probed again after [`BATCH_REPROBE_AFTER`].
+async fn fetch_batch(base_url: &str, key: &str) -> Option<Arc<GatewayBatch>> {
+    let cell = {
+        let mut cache = batch_cache().lock().expect("batch cache poisoned");
+        let now = Instant::now();
+        match cache.unsupported.get(base_url) {
+            Some(&until) if now < until => return None,
+            Some(_) => {
+                cache.unsupported.remove(base_url);
+                cache.fetched.retain(|(url, _), _| url != base_url);
+            }
+            None => {}
+        }
+        let entry = cache
+            .fetched
+            .entry((base_url.to_string(), key.to_string()))
+            .or_insert_with(|| (now, BatchCell::default()));
+        if now.duration_since(entry.0) > BATCH_MAX_AGE {
+            *entry = (now, BatchCell::default());
+        }
+        Arc::clone(&entry.1)
+    };
+    cell.get_or_init(|| async {
+        let missing = |err: &SensorError| matches!(err, SensorError::Status(404 | 405));
+        match fetch_json_expecting::<GatewayBatch>(base_url, "sensors", key, missing).await {
+            Ok(batch) => Some(Arc::new(batch)),
+            Err(SensorError::Status(404 | 405)) => {
+                let mut cache = batch_cache().lock().expect("batch cache poisoned");
+                cache
+                    .unsupported
+                    .insert(base_url.to_string(), Instant::now() + BATCH_REPROBE_AFTER);
+                None
+            }
+            Err(_) => None,
+        }
+    })
+    .await
+    .clone()
+}
+
+/// Key for `GET /sensors`, from `SENSOR_BATCH_KEY`. It must unlock every kind the client reads,
+/// so all sensors share one document; unset skips the batch endpoint.
+fn batch_key() -> Option<String> {
+    env::var("SENSOR_BATCH_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+}
+
+/// `kind` from the gateway's batch document, or `None` when there is no batch key or the batch
+/// does not include it.
+async fn batch_reading(
+    base_url: &str,
+    id: &str,
+    kind: SensorKind,
+) -> Option<Result<Reading, SensorError>> {
+    let batch = fetch_batch(base_url, &batch_key()?).await?;
+    let entry = batch.readings.get(kind.as_str())?;
+    let value = if kind.unit() == Unit::Bool {
+        serde_json::from_value::<bool>(entry.value.clone()).map(SensorValue::Binary)
+    } else {
+        serde_json::from_value::<f32>(entry.value.clone()).map(SensorValue::Numeric)
+    };
+    Some(
+        value
+            .map(|value| Reading::new(id, kind, value, captured_at(entry.timestamp_ms)))
+            .map_err(|e| SensorError::MalformedJson(e.to_string())),
+    )
+}
+
+/// Where the client sensors get their values: an HTTP gateway or a local source.
+#[derive(Clone)]
+pub enum SensorBackend {
+    /// The gateway at `SENSOR_API_BASE_URL`.
+    Http,
+    /// A gateway at its own base URL, e.g. a second coop.
+    Gateway(String),
+    Local(Arc<dyn SensorSource>),
+}
+
+impl SensorBackend {
+    pub fn from_env() -> Result<SensorBackend, String> {
+        let backend = env::var("SENSOR_BACKEND").unwrap_or_else(|_| "http".to_string());
+        SensorBackend::from_name(&backend)
+    }
+
+    /// `http`, a gateway URL, or the name of a local source such as `ds18b20` or
+    /// `esphome:http://coop-node.local`.
+    pub fn from_name(name: &str) -> Result<SensorBackend, String> {
+        match name {
+            "http" => Ok(SensorBackend::Http),
+            url if url.starts_with("http://") || url.starts_with("https://") => {
+                Ok(SensorBackend::Gateway(url.to_string()))
+            }
+            other => Ok(SensorBackend::Local(Arc::from(create_source(other)?))),
+        }
+    }
+}
+
+/// `TEMPERATURE`, `HEAT_INDEX`, ... for per-kind environment variables.
+fn kind_env_name(kind: SensorKind) -> String {
+    kind.as_str().to_ascii_uppercase().replace('-', "_")
+}
+
+/// 404s from firmware mean the board has no such entity, so derived kinds can still be computed.
+fn missing_as_unsupported(kind: SensorKind) -> impl Fn(SensorError) -> SensorError {
+    move |err| match err {
+        SensorError::Status(404) => SensorError::Unsupported(kind),
+        err => err,
+    }
+}
+
+#[derive(Deserialize)]
+struct EspHomeState {
+    /// Absent or `null` while the entity has no state yet.
+    #[serde(default)]
+    value: Option<serde_json::Value>,
+}
+
+/// An ESPHome board's REST API: `GET /sensor/<id>` and, for motion and eggs,
+/// `GET /binary_sensor/<id>`. Entity ids default to the kind name with `_` for `-`
+/// (`temperature`, `heat_index`) and can be set with `ESPHOME_<KIND>_ID`.
+pub struct EspHomeSource {
+    base_url: String,
+    entities: HashMap<SensorKind, String>,
+}
+
+impl EspHomeSource {
+    pub fn new(base_url: &str) -> Self {
+        let entities = SensorKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let entity = env::var(format!("ESPHOME_{}_ID", kind_env_name(kind)))
+                    .unwrap_or_else(|_| kind.as_str().replace('-', "_"));
+                (kind, entity)
+            })
+            .collect();
+        EspHomeSource {
+            base_url: base_url.to_string(),
+            entities,
+        }
+    }
+}
+
+#[async_trait]
+impl SensorSource for EspHomeSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let entity = self
+            .entities
+            .get(&kind)
+            .ok_or(SensorError::Unsupported(kind))?;
+        let domain = if kind.unit() == Unit::Bool {
+            "binary_sensor"
+        } else {
+            "sensor"
+        };
+        let state: EspHomeState = fetch_json(&self.base_url, &format!("{domain}/{entity}"), "")
+            .await
+            .map_err(missing_as_unsupported(kind))?;
+        let value = match (kind.unit(), state.value) {
+            (Unit::Bool, Some(serde_json::Value::Bool(on))) => SensorValue::Binary(on),
+            (unit, Some(serde_json::Value::Number(number))) if unit != Unit::Bool => {
+                SensorValue::Numeric(number.as_f64().unwrap_or(f64::NAN) as f32)
+            }
+            _ => {
+                return Err(SensorError::Source(format!(
+                    "esphome {domain} `{entity}` has no state"
+                )))
+            }
+        };
+        Ok(Reading::new(kind.as_str(), kind, value, SystemTime::now()))
+    }
+}
+
+/// A Tasmota board's `Status 8` sensor report.
+///
+/// Numeric kinds read a field such as `Temperature` from the first sensor object in
+/// `StatusSNS` (alphabetically), or from `TASMOTA_SENSOR`, e.g. `BME280`; other sensor objects'
+/// temperatures are extra readings named after the object, e.g. `DS18B20-2`. Motion and eggs
+/// read a switch such as `Switch1`. `TASMOTA_<KIND>_FIELD` overrides the field.
+pub struct TasmotaSource {
+    base_url: String,
+    sensor: Option<String>,
+    fields: HashMap<SensorKind, String>,
+}
+
+impl TasmotaSource {
+    pub fn new(base_url: &str) -> Self {
+        let fields = SensorKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let default = match kind {
+                    SensorKind::Temperature => Some("Temperature"),
+                    SensorKind::Humidity => Some("Humidity"),
+                    SensorKind::Pressure => Some("Pressure"),
+                    SensorKind::DewPoint => Some("DewPoint"),
+                    SensorKind::Co2 => Some("CarbonDioxide"),
+                    SensorKind::Motion => Some("Switch1"),
+                    _ => None,
+                };
+                env::var(format!("TASMOTA_{}_FIELD", kind_env_name(kind)))
+                    .ok()
+                    .or_else(|| default.map(str::to_string))
+                    .map(|field| (kind, field))
+            })
+            .collect();
+        TasmotaSource {
+            base_url: base_url.to_string(),
+            sensor: env::var("TASMOTA_SENSOR").ok(),
+            fields,
+        }
+    }
+
+    async fn status(&self) -> Result<serde_json::Map<String, serde_json::Value>, SensorError> {
+        let mut report: serde_json::Value =
+            fetch_json(&self.base_url, "cm?cmnd=Status%208", "").await?;
+        match report.get_mut("StatusSNS").map(serde_json::Value::take) {
+            Some(serde_json::Value::Object(status)) => Ok(status),
+            _ => Err(SensorError::MalformedJson(
+                "tasmota response has no StatusSNS".to_string(),
+            )),
+        }
+    }
+
+    /// The configured sensor object, or the first one reporting `field`.
+    fn primary<'a>(
+        &self,
+        status: &'a serde_json::Map<String, serde_json::Value>,
+        field: &str,
+    ) -> Option<(&'a String, &'a serde_json::Value)> {
+        match &self.sensor {
+            Some(name) => status.get_key_value(name),
+            None => status
+                .iter()
+                .find(|(_, sensor)| sensor.get(field).is_some()),
+        }
+    }
+}
+
+/// Tasmota reports temperatures in its `TempUnit`; readings are always Celsius.
+fn tasmota_value(
+    status: &serde_json::Map<String, serde_json::Value>,
+    kind: SensorKind,
+    value: &serde_json::Value,
+) -> Option<f32> {
+    let value = value.as_f64()? as f32;
+    let fahrenheit = status.get("TempUnit").and_then(serde_json::Value::as_str) == Some("F");
+    Some(if kind.unit() == Unit::Celsius && fahrenheit {
+        (value - 32.0) * 5.0 / 9.0
+    } else {
+        value
+    })
+}
+
+#[async_trait]
+impl SensorSource for TasmotaSource {
+    async fn sample(&self, kind: SensorKind) -> Result<Reading, SensorError> {
+        let field = self
+            .fields
+            .get(&kind)
+            .ok_or(SensorError::Unsupported(kind))?;
+        let status = self.status().await?;
+        let value = if kind.unit() == Unit::Bool {
+            let switch = status
+                .get(field)
+                .and_then(serde_json::Value::as_str)
+                .ok_or(SensorError::Unsupported(kind))?;
+            parse_value(kind, switch)?
+        } else {
+            let value = self
+                .primary(&status, field)
+                .and_then(|(_, sensor)| sensor.get(field))
+                .ok_or(SensorError::Unsupported(kind))?;
+            let value = tasmota_value(&status, kind, value).ok_or_else(|| {
+                SensorError::MalformedJson(format!("tasmota {field} is not a number"))
+            })?;
+            SensorValue::Numeric(value)
+        };
+        Ok(Reading::new(kind.as_str(), kind, value, SystemTime::now()))
+    }
+
+    async fn auxiliary(&self) -> Vec<(String, Result<Reading, SensorError>)> {
+        let kind = SensorKind::Temperature;
+        let Some(field) = self.fields.get(&kind) else {
+            return Vec::new();
+        };
+        let status = match self.status().await {
+            Ok(status) => status,
+            Err(err) => return vec![("tasmota".to_string(), Err(err))],
+        };
+        let primary = self.primary(&status, field).map(|(name, _)| name);
+        status
+            .iter()
+            .filter(|(name, _)| Some(*name) != primary)
+            .filter_map(|(name, sensor)| {
+                let value = tasmota_value(&status, kind, sensor.get(field)?)?;
+                let reading =
+                    Reading::new(name, kind, SensorValue::Numeric(value), SystemTime::now());
+                Some((name.clone(), Ok(reading)))
+            })
+            .collect()
+    }
+}
+
+async fn read_kind(
+    backend: &SensorBackend,
+    id: &str,
+    kind: SensorKind,
+    key: &str,
+) -> Result<Reading, SensorError> {
+    read_path(
+        backend,
+        id,
+        kind,
+        key,
+        &format!("sensors/{}", kind.as_str()),
+    )
+    .await
+}
+
+/// Reads `kind` from `path` on a gateway; local sources ignore the path and sample `kind`.
+/// Reads of the default `sensors/<kind>` path are answered from the gateway's batch endpoint
+/// when it has one.
+pub(crate) async fn read_path(
+    backend: &SensorBackend,
+    id: &str,
+    kind: SensorKind,
+    key: &str,
+    path: &str,
+) -> Result<Reading, SensorError> {
+    let base_url = match backend {
+        SensorBackend::Http => sensor_api_base_url(),
+        SensorBackend::Gateway(base_url) => base_url.clone(),
+        SensorBackend::Local(source) => {
+            let mut reading = sample_or_derive(source.as_ref(), kind).await?;
+            reading.sensor_id = id.to_string();
+            return Ok(reading);
+        }
+    };
+    if path == format!("sensors/{}", kind.as_str()) {
+        if let Some(reading) = batch_reading(&base_url, id, kind).await {
+            return reading;
+        }
+    }
+    let (value, captured_at) = if kind.unit() == Unit::Bool {
+        let (value, captured_at) = fetch_binary(&base_url, path, key).await?;
+        (SensorValue::Binary(value), captured_at)
+    } else {
+        let (value, captured_at) = fetch_numeric(&base_url, path, key).await?;
+        (SensorValue::Numeric(value), captured_at)
+    };
+    Ok(Reading::new(id, kind, value, captured_at))
+}
+
+/// `read_kind` for numeric sensors: calibrates, then runs the plausibility filter if any.
+async fn read_numeric_kind(
+    backend: &SensorBackend,
+    id: &str,
+    kind: SensorKind,
+    key: &str,
+    calibration: &Calibration,
+    filter: Option<&ReadingFilter>,
+) -> Result<Reading, SensorError> {
+    let reading = calibration.apply(read_kind(backend, id, kind, key).await?);
+    match filter {
+        Some(filter) => filter.check(reading),
+        None => Ok(reading),
+    }
 }
 
 pub struct TemperatureSensor {
+    pub id: String,
     pub key: String,
+    pub backend: SensorBackend,
+    pub calibration: Calibration,
+    pub filter: Option<Arc<ReadingFilter>>,
 }
 
 impl TemperatureSensor {
     pub fn new(key: &str) -> Self {
         TemperatureSensor {
+            id: "temperature".to_string(),
             key: key.to_string(),
+            backend: SensorBackend::Http,
+            calibration: Calibration::default(),
+            filter: None,
         }
     }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Arc<ReadingFilter>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
 }
 
+#[async_trait]
 impl Sensor for TemperatureSensor {
-    fn read(&self) -> SensorValue {
-        println!(
-            "Reading temperature via API using key {}",
-            redact_key(&self.key)
-        );
-        let value = fetch_numeric("sensors/temperature", &self.key).unwrap_or_else(|| {
-            eprintln!("Temperature API unavailable; using 0.0 fallback");
-            0.0
-        });
-        SensorValue::Numeric(value)
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, "temperature", &self.key);
+        read_numeric_kind(
+            &self.backend,
+            &self.id,
+            SensorKind::Temperature,
+            &self.key,
+            &self.calibration,
+            self.filter.as_deref(),
+        )
+        .await
     }
 }
 
 pub struct HumiditySensor {
+    pub id: String,
     pub key: String,
+    pub backend: SensorBackend,
+    pub calibration: Calibration,
+    pub filter: Option<Arc<ReadingFilter>>,
 }
 
 impl HumiditySensor {
     pub fn new(key: &str) -> Self {
         HumiditySensor {
+            id: "humidity".to_string(),
             key: key.to_string(),
+            backend: SensorBackend::Http,
+            calibration: Calibration::default(),
+            filter: None,
         }
     }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Arc<ReadingFilter>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
 }
 
+#[async_trait]
 impl Sensor for HumiditySensor {
-    fn read(&self) -> SensorValue {
-        println!(
-            "Reading humidity via API using key {}",
-            redact_key(&self.key)
-        );
-        let value = fetch_numeric("sensors/humidity", &self.key).unwrap_or_else(|| {
-            eprintln!("Humidity API unavailable; using 0.0 fallback");
-            0.0
-        });
-        SensorValue::Numeric(value)
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, "humidity", &self.key);
+        read_numeric_kind(
+            &self.backend,
+            &self.id,
+            SensorKind::Humidity,
+            &self.key,
+            &self.calibration,
+            self.filter.as_deref(),
+        )
+        .await
     }
 }
 
 pub struct MotionSensor {
+    pub id: String,
     pub key: String,
+    pub backend: SensorBackend,
 }
 
 impl MotionSensor {
     pub fn new(key: &str) -> Self {
         MotionSensor {
+            id: "motion".to_string(),
             key: key.to_string(),
+            backend: SensorBackend::Http,
         }
     }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
 }
 
+#[async_trait]
 impl Sensor for MotionSensor {
-    fn read(&self) -> SensorValue {
-        println!("Reading motion via API using key {}", redact_key(&self.key));
-        let value = fetch_binary("sensors/motion", &self.key).unwrap_or_else(|| {
-            eprintln!("Motion API unavailable; using false fallback");
-            false
-        });
-        SensorValue::Binary(value)
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, "motion", &self.key);
+        read_kind(&self.backend, &self.id, SensorKind::Motion, &self.key).await
     }
 }
 
 pub struct EggPresenceSensor {
+    pub id: String,
     pub key: String,
+    pub backend: SensorBackend,
 }
 
 impl EggPresenceSensor {
     pub fn new(key: &str) -> Self {
         EggPresenceSensor {
+            id: "eggs".to_string(),
             key: key.to_string(),
+            backend: SensorBackend::Http,
         }
     }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
 }
 
+#[async_trait]
 impl Sensor for EggPresenceSensor {
-    fn read(&self) -> SensorValue {
-        println!(
-            "Reading egg presence via API using key {}",
-            redact_key(&self.key)
-        );
-        let value = fetch_binary("sensors/eggs", &self.key).unwrap_or_else(|| {
-            eprintln!("Egg presence API unavailable; using false fallback");
-            false
-        });
-        SensorValue::Binary(value)
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, "egg presence", &self.key);
+        read_kind(&self.backend, &self.id, SensorKind::EggPresence, &self.key).await
+    }
+}
+
+/// A numeric kind with no conversion beyond calibration, e.g. pressure, ammonia or CO2.
+pub struct NumericSensor {
+    pub id: String,
+    pub kind: SensorKind,
+    /// Name in the read log, e.g. `CO2`.
+    pub label: &'static str,
+    pub key: String,
+    pub backend: SensorBackend,
+    pub calibration: Calibration,
+    pub filter: Option<Arc<ReadingFilter>>,
+}
+
+impl NumericSensor {
+    pub fn new(kind: SensorKind, key: &str) -> Self {
+        NumericSensor {
+            id: kind.as_str().to_string(),
+            kind,
+            label: kind.as_str(),
+            key: key.to_string(),
+            backend: SensorBackend::Http,
+            calibration: Calibration::default(),
+            filter: None,
+        }
+    }
+
+    pub fn pressure(key: &str) -> Self {
+        NumericSensor::new(SensorKind::Pressure, key)
+    }
+
+    pub fn ammonia(key: &str) -> Self {
+        NumericSensor::new(SensorKind::Ammonia, key)
+    }
+
+    pub fn co2(key: &str) -> Self {
+        NumericSensor {
+            label: "CO2",
+            ..NumericSensor::new(SensorKind::Co2, key)
+        }
+    }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Arc<ReadingFilter>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+#[async_trait]
+impl Sensor for NumericSensor {
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, self.label, &self.key);
+        read_numeric_kind(
+            &self.backend,
+            &self.id,
+            self.kind,
+            &self.key,
+            &self.calibration,
+            self.filter.as_deref(),
+        )
+        .await
+    }
+}
+
+/// Raw distance or weight from the backend, reported as percent full via `geometry`.
+pub struct FeedLevelSensor {
+    pub id: String,
+    pub key: String,
+    pub backend: SensorBackend,
+    pub geometry: ContainerGeometry,
+    pub calibration: Calibration,
+}
+
+impl FeedLevelSensor {
+    pub fn new(key: &str, geometry: ContainerGeometry) -> Self {
+        FeedLevelSensor {
+            id: "feed".to_string(),
+            key: key.to_string(),
+            backend: SensorBackend::Http,
+            geometry,
+            calibration: Calibration::default(),
+        }
+    }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+}
+
+#[async_trait]
+impl Sensor for FeedLevelSensor {
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, "feed level", &self.key);
+        let raw = read_kind(&self.backend, &self.id, SensorKind::FeedLevel, &self.key).await?;
+        Ok(self.geometry.apply(self.calibration.apply(raw)))
+    }
+}
+
+/// Raw distance or weight from the backend, reported as percent full via `geometry`.
+pub struct WaterLevelSensor {
+    pub id: String,
+    pub key: String,
+    pub backend: SensorBackend,
+    pub geometry: ContainerGeometry,
+    pub calibration: Calibration,
+}
+
+impl WaterLevelSensor {
+    pub fn new(key: &str, geometry: ContainerGeometry) -> Self {
+        WaterLevelSensor {
+            id: "water".to_string(),
+            key: key.to_string(),
+            backend: SensorBackend::Http,
+            geometry,
+            calibration: Calibration::default(),
+        }
+    }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+}
+
+#[async_trait]
+impl Sensor for WaterLevelSensor {
+    async fn read(&self) -> Result<Reading, SensorError> {
+        log_gateway_read(&self.backend, "water level", &self.key);
+        let raw = read_kind(&self.backend, &self.id, SensorKind::WaterLevel, &self.key).await?;
+        Ok(self.geometry.apply(self.calibration.apply(raw)))
+    }
+}
+
+/// Eggs in one nest box, from `/sensors/eggs/<box>` or the local source's nest box reading.
+pub struct EggCountSensor {
+    pub id: String,
+    pub key: String,
+    pub backend: SensorBackend,
+    pub counting: EggCounting,
+}
+
+impl EggCountSensor {
+    pub fn new(key: &str, nest_box: &str) -> Self {
+        EggCountSensor {
+            id: nest_box.to_string(),
+            key: key.to_string(),
+            backend: SensorBackend::Http,
+            counting: EggCounting::Count,
+        }
+    }
+
+    pub fn with_backend(mut self, backend: SensorBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_counting(mut self, counting: EggCounting) -> Self {
+        self.counting = counting;
+        self
+    }
+}
+
+#[async_trait]
+impl Sensor for EggCountSensor {
+    async fn read(&self) -> Result<Reading, SensorError> {
+        let what = format!("eggs in nest box {}", self.id);
+        log_gateway_read(&self.backend, &what, &self.key);
+        let raw = match &self.backend {
+            SensorBackend::Local(source) => source.nest_box(&self.id).await?,
+            gateway => {
+                let path = format!("sensors/eggs/{}", self.id);
+                read_path(gateway, &self.id, SensorKind::EggCount, &self.key, &path).await?
+            }
+        };
+        self.counting.apply(raw)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        EggPresenceSensor, HumiditySensor, MotionSensor, Sensor, SensorValue, TemperatureSensor,
+        batch_cache, Calibration, EggCountSensor, EggCounting, EggPresenceSensor, EspHomeSource,
+        HumiditySensor, MotionSensor, NumericSensor, ReadingFilter, Sensor, SensorBackend,
+        SensorError, SensorKind, SensorValue, TasmotaSource, TemperatureSensor, Unit,
     };
+    use crate::retry;
+    use crate::sensor_sources::SensorSource;
     use std::io::{Read, Write};
     use std::net::TcpListener;
-    use std::sync::{Mutex, OnceLock};
+    use std::sync::{Arc, OnceLock};
     use std::thread;
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+    use tokio::sync::Mutex;
 
     fn env_lock() -> &'static Mutex<()> {
         static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
@@ -180,17 +1123,43 @@ mod tests {
     }
 
     fn spawn_json_server(body: &'static str) -> String {
+        spawn_gateway("200 OK", body)
+    }
+
+    fn spawn_gateway(status_line: &'static str, body: &'static str) -> String {
+        spawn_fake_gateway(Vec::new(), vec![(status_line, body); 4])
+    }
+
+    /// Answers `GET <path>` from `routes` and any other path with the next of `responses`, then
+    /// 404 once they run out; `/sensors` is only answered from `routes`. A request carrying an
+    /// empty `x-api-key` header, which clients leave out when they have no key, gets 400.
+    fn spawn_fake_gateway(
+        routes: Vec<(&'static str, &'static str)>,
+        responses: Vec<(&'static str, &'static str)>,
+    ) -> String {
         let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
         let addr = listener.local_addr().expect("local addr");
         thread::spawn(move || {
-            for _ in 0..4 {
-                let (mut stream, _) = listener.accept().expect("accept");
+            let mut responses = responses.into_iter();
+            for stream in listener.incoming() {
+                let mut stream = stream.expect("accept");
                 let mut buffer = [0_u8; 1024];
-                let _ = stream.read(&mut buffer);
+                let size = stream.read(&mut buffer).unwrap_or(0);
+                let request = String::from_utf8_lossy(&buffer[..size]);
+                let path = request.split_whitespace().nth(1).unwrap_or("");
+                let empty_key = request
+                    .lines()
+                    .any(|line| line.trim_end().eq_ignore_ascii_case("x-api-key:"));
+                let route = routes.iter().find(|(route, _)| *route == path);
+                let (status_line, body) = match (route, path) {
+                    _ if empty_key => ("400 Bad Request", "{}"),
+                    (Some((_, body)), _) => ("200 OK", *body),
+                    (None, "/sensors") => ("404 Not Found", "{}"),
+                    (None, _) => responses.next().unwrap_or(("404 Not Found", "")),
+                };
                 let response = format!(
-                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
-                    body.len(),
-                    body
+                    "HTTP/1.1 {status_line}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
                 );
                 stream.write_all(response.as_bytes()).expect("write");
                 stream.flush().expect("flush");
@@ -199,30 +1168,313 @@ mod tests {
         format!("http://{addr}")
     }
 
-    #[test]
-    fn sensors_read_from_http_gateway() {
-        let _guard = env_lock().lock().expect("env lock");
+    #[tokio::test]
+    async fn sensors_read_from_http_gateway() {
+        let _guard = env_lock().lock().await;
         let url = spawn_json_server(r#"{"value":21.5}"#);
         std::env::set_var("SENSOR_API_BASE_URL", url);
 
         let temp = TemperatureSensor::new("TEMP");
         let humidity = HumiditySensor::new("HUM");
-        assert_eq!(temp.read(), SensorValue::Numeric(21.5));
-        assert_eq!(humidity.read(), SensorValue::Numeric(21.5));
+        assert_eq!(
+            temp.read().await.map(|r| r.value),
+            Ok(SensorValue::Numeric(21.5))
+        );
+        assert_eq!(
+            humidity.read().await.map(|r| r.value),
+            Ok(SensorValue::Numeric(21.5))
+        );
+
+        let calibrated = TemperatureSensor::new("TEMP").with_calibration(Calibration {
+            offset: -1.5,
+            ..Calibration::default()
+        });
+        assert_eq!(
+            calibrated.read().await.map(|r| r.value),
+            Ok(SensorValue::Numeric(20.0))
+        );
 
         std::env::remove_var("SENSOR_API_BASE_URL");
     }
 
-    #[test]
-    fn binary_sensors_read_from_http_gateway() {
-        let _guard = env_lock().lock().expect("env lock");
+    #[tokio::test]
+    async fn binary_sensors_read_from_http_gateway() {
+        let _guard = env_lock().lock().await;
         let url = spawn_json_server(r#"{"value":true}"#);
         std::env::set_var("SENSOR_API_BASE_URL", url);
 
         let motion = MotionSensor::new("MOTION");
         let eggs = EggPresenceSensor::new("EGG");
-        assert_eq!(motion.read(), SensorValue::Binary(true));
-        assert_eq!(eggs.read(), SensorValue::Binary(true));
+        assert_eq!(
+            motion.read().await.map(|r| r.value),
+            Ok(SensorValue::Binary(true))
+        );
+        assert_eq!(
+            eggs.read().await.map(|r| r.value),
+            Ok(SensorValue::Binary(true))
+        );
+
+        std::env::remove_var("SENSOR_API_BASE_URL");
+    }
+
+    #[tokio::test]
+    async fn nest_boxes_report_counts_or_weights() {
+        let _guard = env_lock().lock().await;
+        let url = spawn_json_server(r#"{"value":2}"#);
+        std::env::set_var("SENSOR_API_BASE_URL", url);
+
+        let counted = EggCountSensor::new("EGG", "left")
+            .read()
+            .await
+            .expect("count");
+        assert_eq!(counted.sensor_id, "left");
+        assert_eq!(counted.value, SensorValue::Count(2));
+        assert_eq!(counted.to_string(), "2");
+
+        // A 2 kg reading off a nest scale with 1.884 kg tare is two 58 g eggs.
+        let weighed = EggCountSensor::new("EGG", "right").with_counting(EggCounting::Scale {
+            tare_kg: 1.884,
+            egg_weight_g: 58.0,
+        });
+        assert_eq!(
+            weighed.read().await.map(|r| r.value),
+            Ok(SensorValue::Count(2))
+        );
+
+        std::env::remove_var("SENSOR_API_BASE_URL");
+    }
+
+    #[tokio::test]
+    async fn esphome_entities_back_the_sensors() {
+        let url = spawn_fake_gateway(
+            vec![
+                (
+                    "/sensor/coop_temp",
+                    r#"{"id":"sensor-coop_temp","value":21.5,"state":"21.5 °C"}"#,
+                ),
+                (
+                    "/binary_sensor/motion",
+                    r#"{"id":"binary_sensor-motion","value":true,"state":"ON"}"#,
+                ),
+                (
+                    "/sensor/humidity",
+                    r#"{"id":"sensor-humidity","state":"NA"}"#,
+                ),
+            ],
+            Vec::new(),
+        );
+        let mut source = EspHomeSource::new(&url);
+        source
+            .entities
+            .insert(SensorKind::Temperature, "coop_temp".to_string());
+        let node = SensorBackend::Local(Arc::new(source));
+
+        let temp = TemperatureSensor::new("").with_backend(node.clone());
+        assert_eq!(
+            temp.read().await.map(|r| r.value),
+            Ok(SensorValue::Numeric(21.5))
+        );
+        let motion = MotionSensor::new("").with_backend(node.clone());
+        assert_eq!(
+            motion.read().await.map(|r| r.value),
+            Ok(SensorValue::Binary(true))
+        );
+        let humidity = HumiditySensor::new("").with_backend(node.clone());
+        assert!(matches!(humidity.read().await, Err(SensorError::Source(_))));
+        let pressure = NumericSensor::pressure("").with_backend(node);
+        assert_eq!(
+            pressure.read().await,
+            Err(SensorError::Unsupported(SensorKind::Pressure))
+        );
+    }
+
+    #[tokio::test]
+    async fn tasmota_status_reports_back_the_sensors() {
+        let _guard = env_lock().lock().await;
+        let url = spawn_fake_gateway(
+            vec![(
+                "/cm?cmnd=Status%208",
+                r#"{"StatusSNS":{"Time":"2024-05-01T12:00:00",
+                "AM2301":{"Temperature":70.7,"Humidity":55.0,"DewPoint":54.0},
+                "DS18B20":{"Id":"0316A2791A8C","Temperature":68.0},
+                "Switch1":"ON","TempUnit":"F"}}"#,
+            )],
+            Vec::new(),
+        );
+        let source = TasmotaSource::new(&url);
+
+        // Fahrenheit readings come back in Celsius; humidity is left alone.
+        let temp = source
+            .sample(SensorKind::Temperature)
+            .await
+            .expect("temperature");
+        assert!(matches!(temp.value, SensorValue::Numeric(c) if (c - 21.5).abs() < 0.01));
+        assert_eq!(
+            source.sample(SensorKind::Humidity).await.map(|r| r.value),
+            Ok(SensorValue::Numeric(55.0))
+        );
+        assert_eq!(
+            source.sample(SensorKind::Motion).await.map(|r| r.value),
+            Ok(SensorValue::Binary(true))
+        );
+        assert_eq!(
+            source.sample(SensorKind::Pressure).await,
+            Err(SensorError::Unsupported(SensorKind::Pressure))
+        );
+        let auxiliary = source.auxiliary().await;
+        assert_eq!(auxiliary.len(), 1);
+        assert_eq!(auxiliary[0].0, "DS18B20");
+        assert_eq!(
+            auxiliary[0].1.as_ref().map(|r| r.value),
+            Ok(SensorValue::Numeric(20.0))
+        );
+    }
+
+    #[tokio::test]
+    async fn transient_gateway_errors_are_retried() {
+        let _guard = env_lock().lock().await;
+        let url = spawn_fake_gateway(
+            Vec::new(),
+            vec![
+                ("503 Service Unavailable", "{}"),
+                ("200 OK", r#"{"value":21.5}"#),
+                ("200 OK", r#"{"value":21.0}"#),
+                ("200 OK", r#"{"value":20.5}"#),
+            ],
+        );
+        std::env::set_var("SENSOR_API_BASE_URL", &url);
+        std::env::set_var("SENSOR_BATCH_KEY", "BATCH");
+
+        assert_eq!(
+            TemperatureSensor::new("TEMP").read().await.map(|r| r.value),
+            Ok(SensorValue::Numeric(21.5))
+        );
+        let stats = retry::stats()[&format!("{url}/sensors/temperature")];
+        assert_eq!((stats.attempts, stats.retries), (2, 1));
+        // The gateway has no batch endpoint; probing for it is not a failure.
+        let probes = retry::stats()[&format!("{url}/sensors")];
+        assert_eq!((probes.attempts, probes.failures), (1, 0));
+
+        // Later reads skip the probe until it is due again.
+        assert!(TemperatureSensor::new("TEMP").read().await.is_ok());
+        assert_eq!(retry::stats()[&format!("{url}/sensors")].attempts, 1);
+        batch_cache()
+            .lock()
+            .expect("batch cache")
+            .unsupported
+            .insert(url.clone(), Instant::now());
+        assert!(TemperatureSensor::new("TEMP").read().await.is_ok());
+        assert_eq!(retry::stats()[&format!("{url}/sensors")].attempts, 2);
+
+        std::env::remove_var("SENSOR_API_BASE_URL");
+        std::env::remove_var("SENSOR_BATCH_KEY");
+    }
+
+    #[tokio::test]
+    async fn batch_endpoint_answers_reads_with_per_sensor_fallback() {
+        let _guard = env_lock().lock().await;
+        let url = spawn_fake_gateway(
+            vec![(
+                "/sensors",
+                r#"{"readings":{
+                    "temperature":{"value":19.0,"unit":"C","timestamp_ms":1700000000000},
+                    "motion":{"value":true,"unit":"","timestamp_ms":1700000000000}
+                },"errors":{}}"#,
+            )],
+            vec![("200 OK", r#"{"value":55.0}"#)],
+        );
+        std::env::set_var("SENSOR_API_BASE_URL", &url);
+        std::env::set_var("SENSOR_BATCH_KEY", "BATCH");
+
+        let temp = TemperatureSensor::new("KEY")
+            .read()
+            .await
+            .expect("temperature");
+        assert_eq!(temp.value, SensorValue::Numeric(19.0));
+        assert_eq!(
+            temp.captured_at,
+            UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)
+        );
+        assert_eq!(
+            MotionSensor::new("KEY").read().await.map(|r| r.value),
+            Ok(SensorValue::Binary(true))
+        );
+        // Humidity is not in the batch, so it comes from `/sensors/humidity`.
+        assert_eq!(
+            HumiditySensor::new("KEY").read().await.map(|r| r.value),
+            Ok(SensorValue::Numeric(55.0))
+        );
+        assert_eq!(retry::stats()[&format!("{url}/sensors")].attempts, 1);
+
+        std::env::remove_var("SENSOR_API_BASE_URL");
+        std::env::remove_var("SENSOR_BATCH_KEY");
+    }
+
+    #[tokio::test]
+    async fn gateway_failures_surface_as_typed_errors() {
+        let _guard = env_lock().lock().await;
+
+        std::env::set_var(
+            "SENSOR_API_BASE_URL",
+            spawn_gateway("401 Unauthorized", "{}"),
+        );
+        assert_eq!(
+            TemperatureSensor::new("BAD").read().await,
+            Err(SensorError::Unauthorized)
+        );
+
+        std::env::set_var(
+            "SENSOR_API_BASE_URL",
+            spawn_gateway("503 Service Unavailable", "{}"),
+        );
+        assert_eq!(
+            MotionSensor::new("MOTION").read().await,
+            Err(SensorError::Status(503))
+        );
+
+        std::env::set_var(
+            "SENSOR_API_BASE_URL",
+            spawn_json_server(r#"{"temp":"hot"}"#),
+        );
+        assert!(matches!(
+            HumiditySensor::new("HUM").read().await,
+            Err(SensorError::MalformedJson(_))
+        ));
+
+        std::env::set_var(
+            "SENSOR_API_BASE_URL",
+            spawn_json_server(r#"{"value":85.0}"#),
+        );
+        let filter = Arc::new(ReadingFilter::new(3, Duration::from_secs(3600)));
+        assert!(matches!(
+            TemperatureSensor::new("TEMP")
+                .with_filter(filter)
+                .read()
+                .await,
+            Err(SensorError::Implausible(_))
+        ));
+
+        std::env::remove_var("SENSOR_API_BASE_URL");
+    }
+
+    #[tokio::test]
+    async fn readings_carry_identity_unit_and_gateway_timestamp() {
+        let _guard = env_lock().lock().await;
+        let url = spawn_json_server(r#"{"value":21.5,"timestamp_ms":1700000000000}"#);
+        std::env::set_var("SENSOR_API_BASE_URL", url);
+
+        let reading = TemperatureSensor::new("TEMP")
+            .read()
+            .await
+            .expect("temperature reading");
+        assert_eq!(reading.sensor_id, "temperature");
+        assert_eq!(reading.kind, SensorKind::Temperature);
+        assert_eq!(reading.unit, Unit::Celsius);
+        assert_eq!(
+            reading.captured_at,
+            UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)
+        );
+        assert_eq!(reading.to_string(), "21.5C");
 
         std::env::remove_var("SENSOR_API_BASE_URL");
     }
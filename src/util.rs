@@ -0,0 +1,63 @@
+use std::env;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Parses `name`, or returns `default` when it is unset.
+pub fn parse_env<T: FromStr>(name: &str, default: T) -> Result<T, String> {
+    Ok(parse_optional_env(name)?.unwrap_or(default))
+}
+
+/// Parses `name`, or returns `None` when it is unset.
+pub fn parse_optional_env<T: FromStr>(name: &str) -> Result<Option<T>, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| format!("invalid value for {name}: {value}")),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Reads a switch such as `1`, `true`, `yes` or `on` (and their opposites).
+pub fn parse_bool_env(name: &str, default: bool) -> Result<bool, String> {
+    match env::var(name) {
+        Ok(value) => match value.as_str() {
+            "1" | "true" | "TRUE" | "yes" | "on" => Ok(true),
+            "0" | "false" | "FALSE" | "no" | "off" => Ok(false),
+            _ => Err(format!("invalid value for {name}: {value}")),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+/// Unix milliseconds, as stored in the state files; times before the epoch become 0.
+pub fn millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{millis, parse_bool_env, parse_env, parse_optional_env};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn env_values_parse_or_fall_back() {
+        std::env::set_var("UTIL_TEST_NUMBER", "42");
+        std::env::set_var("UTIL_TEST_SWITCH", "off");
+        std::env::set_var("UTIL_TEST_BAD", "many");
+        assert_eq!(parse_env::<u64>("UTIL_TEST_NUMBER", 7), Ok(42));
+        assert_eq!(parse_env::<u64>("UTIL_TEST_UNSET", 7), Ok(7));
+        assert_eq!(parse_optional_env::<f32>("UTIL_TEST_UNSET"), Ok(None));
+        assert_eq!(parse_bool_env("UTIL_TEST_SWITCH", true), Ok(false));
+        assert_eq!(
+            parse_env::<u8>("UTIL_TEST_BAD", 0),
+            Err("invalid value for UTIL_TEST_BAD: many".to_string())
+        );
+        assert!(parse_bool_env("UTIL_TEST_BAD", true).is_err());
+
+        assert_eq!(millis(UNIX_EPOCH + Duration::from_millis(1500)), 1500);
+        assert_eq!(millis(UNIX_EPOCH - Duration::from_secs(1)), 0);
+    }
+}